@@ -1,4 +1,5 @@
 #import bevy_sprite::mesh2d_functions::mesh2d_position_world_to_clip;
+#import bevy_sprite::mesh2d_view_bindings::view;
 
 const Z_R_VALUE_INDEX: u32 = 0;
 const Z_I_VALUE_INDEX: u32 = 1;
@@ -8,8 +9,24 @@ const P_R_VALUE_INDEX: u32 = 4;
 const P_I_VALUE_INDEX: u32 = 5;
 const PIXEL_X_INDEX: u32 = 6;
 const PIXEL_Y_INDEX: u32 = 7;
-
-const PARAM_ARRAY_SIZE: u32 = 16;
+const PIXEL_RADIUS_INDEX: u32 = 8;
+const PIXEL_ANGLE_INDEX: u32 = 9;
+const INVERSE_PIXEL_R_INDEX: u32 = 10;
+const INVERSE_PIXEL_I_INDEX: u32 = 11;
+const TRANSFORMED_PIXEL_X_INDEX: u32 = 12;
+const TRANSFORMED_PIXEL_Y_INDEX: u32 = 13;
+const DERIVED_PIXEL_PARAM_COUNT: u32 = 6;
+const ROOT_VALUE_INDEX_START: u32 = 14;
+const TRAP_CENTER_R_VALUE_INDEX: u32 = 26;
+const TRAP_CENTER_I_VALUE_INDEX: u32 = 27;
+const TRAP_RADIUS_VALUE_INDEX: u32 = 28;
+const TRAP_ANGLE_VALUE_INDEX: u32 = 29;
+
+const PARAM_ARRAY_SIZE: u32 = 32;
+
+const MAX_ROOT_COUNT: u32 = 6;
+// root index of a point that didn't converge to any root
+const NO_ROOT: u32 = MAX_ROOT_COUNT;
 
 // const LARGE_FLOAT: f32 = 1e38;
 const MAX_INT_POW: u32 = 15;
@@ -23,17 +40,106 @@ const EXP_REAL: u32 = 4;
 const EXP_COMPLEX: u32 = 5;
 const EXP_NEG_2: u32 = 6;
 
+// Formulas
+const FORMULA_MANDELBROT: u32 = 0;
+const FORMULA_BURNING_SHIP: u32 = 1;
+const FORMULA_TRICORN: u32 = 2;
+const FORMULA_CELTIC: u32 = 3;
+const FORMULA_BUFFALO: u32 = 4;
+const FORMULA_NEWTON: u32 = 5;
+const FORMULA_CUSTOM: u32 = 6;
+
+const MAX_COLOR_STOPS: u32 = 16;
+
+// palette interpolation modes
+const INTERPOLATION_LINEAR: u32 = 0;
+const INTERPOLATION_SMOOTH: u32 = 1;
+const INTERPOLATION_CONSTANT: u32 = 2;
+
+// interior coloring modes
+const INTERIOR_BLACK: u32 = 0;
+const INTERIOR_FINAL_MAGNITUDE: u32 = 1;
+const INTERIOR_FINAL_ANGLE: u32 = 2;
+const INTERIOR_PERIOD: u32 = 3;
+const INTERIOR_AVERAGE_DISTANCE: u32 = 4;
+const INTERIOR_ORBIT_TRAP: u32 = 5;
+
+// orbit trap shapes
+const TRAP_NONE: u32 = 0;
+const TRAP_POINT: u32 = 1;
+const TRAP_LINE: u32 = 2;
+const TRAP_CROSS: u32 = 3;
+const TRAP_CIRCLE: u32 = 4;
+// trap distance of orbits that haven't been near the trap yet
+const MAX_TRAP_DISTANCE: f32 = 1e30;
+// stands in for the infinite inverse of 0, so that it escapes instead of turning into NaN
+const INVERSE_OF_ZERO: f32 = 1e30;
+
+// exterior coloring modes
+const EXTERIOR_ITERATIONS: u32 = 0;
+const EXTERIOR_DISTANCE_ESTIMATE: u32 = 1;
+const EXTERIOR_STRIPE_AVERAGE: u32 = 2;
+const EXTERIOR_TRIANGLE_INEQUALITY: u32 = 3;
+const EXTERIOR_HISTOGRAM: u32 = 4;
+
+// what the material draws, the histogram equalized coloring takes two passes
+const HISTOGRAM_PASS_NONE: u32 = 0;
+// draws the fractal and writes the smoothed iteration count of every pixel for the histogram
+const HISTOGRAM_PASS_VALUES: u32 = 1;
+// only maps the values written by the first pass through the cumulative distribution of the histogram
+const HISTOGRAM_PASS_COLORS: u32 = 2;
+
+// the same as in the histogram shader
+const HISTOGRAM_BIN_COUNT: u32 = 4096;
+const CDF_LENGTH: u32 = 4097;
+
+// the lighting takes two passes too
+const LIGHTING_PASS_NONE: u32 = 0;
+// draws the fractal and writes the height of every pixel
+const LIGHTING_PASS_HEIGHTS: u32 = 1;
+// only shades the fractal underneath using the heights around each pixel
+const LIGHTING_PASS_SHADING: u32 = 2;
+
+const HEIGHT_ITERATIONS: u32 = 0;
+const HEIGHT_DISTANCE_ESTIMATE: u32 = 1;
+
+// fraction of the light that reaches surfaces facing away from it
+const AMBIENT_LIGHT: f32 = 0.2;
+const SHININESS: f32 = 32.0;
+// the lighting is calculated in world units, where the longer side of the viewport is 2 units,
+// and scaled to look the same as on a viewport 1024 pixels wide regardless of the resolution
+const LIGHTING_REFERENCE_PIXEL_SIZE: f32 = 2.0 / 1024.0;
+// distance estimates are clamped to this many reference pixels,
+// so the interior isn't infinitely high
+const MIN_HEIGHT_DISTANCE: f32 = 0.01;
+
+// two points of an orbit closer than this are considered to be the same point of a cycle
+const PERIOD_TOLERANCE_SQUARED: f32 = 1e-8;
+// spreads consecutive periods far apart on the palette
+const GOLDEN_RATIO_CONJUGATE: f32 = 0.618034;
+const TAU: f32 = 6.283185307179586;
+
 /// Floating point type, either f32 or f64
-#ifndef DOUBLE_PRECISION
+#ifdef DOUBLE_PRECISION
+alias fp = f64;
+// f64 is encoded as two u32s
+alias EncodedFp = vec2u;
+alias EncodedVec2 = vec4u;
+#else ifdef DOUBLE_SINGLE
+// most of the shader is calculated in f32, only the main loop uses emulated f64
 alias fp = f32;
+// emulated f64 is encoded as two f32s, the value is their sum
+alias EncodedFp = vec2f;
+alias EncodedVec2 = vec4f;
 #else
-alias fp = f64;
+alias fp = f32;
+#endif
 
+#ifdef ENCODED_FLOATS
 struct EncodedComplexParameter {
-    // f64 is encoded as two u32s
-    real_value: vec2u,
+    real_value: EncodedFp,
     real_index: u32,
-    imag_value: vec2u,
+    imag_value: EncodedFp,
     imag_index: u32,
 }
 #endif
@@ -45,6 +151,16 @@ struct ComplexParameter {
     imag_index: u32,
 }
 
+// colors are f32 in every precision, so the palette doesn't need decoding
+struct Palette {
+    // rgb is the color, w is the position, sorted by position
+    stops: array<vec4f, MAX_COLOR_STOPS>,
+    stop_count: u32,
+    interpolation: u32,
+    cycle_length: f32,
+    offset: f32,
+}
+
 struct FractalMaterial {
     iteration_count: u32,
     scale: fp,
@@ -52,21 +168,72 @@ struct FractalMaterial {
     initial_z: ComplexParameter,
     c: ComplexParameter,
     p: ComplexParameter,
+    roots: array<ComplexParameter, MAX_ROOT_COUNT>,
     escape_radius: fp,
+    formula: u32,
+    root_count: u32,
+    tolerance: fp,
+    reference_offset: vec2<fp>,
+    reference_length: u32,
+    perturbation: u32,
+    palette: Palette,
+    // structs have to be aligned to 16 bytes, so these go before the u32s
+    trap_center: ComplexParameter,
+    // radius in the real part, angle in the imaginary part
+    trap_geometry: ComplexParameter,
+    interior_coloring: u32,
+    trap_shape: u32,
+    exterior_coloring: u32,
+    boundary_thickness: f32,
+    stripe_density: f32,
+    histogram_pass: u32,
+    lighting_pass: u32,
+    height_source: u32,
+    // in radians
+    light_azimuth: f32,
+    light_elevation: f32,
+    height_scale: f32,
+    specular: f32,
+    pixel_transform: mat2x2f,
+    pixel_transform_offset: vec2f,
 }
 
-#ifndef DOUBLE_PRECISION
+#ifndef ENCODED_FLOATS
 // no encoding needed for f32
 alias EncodedFractalMaterial = FractalMaterial;
 #else
 struct EncodedFractalMaterial {
     iteration_count: u32,
-    scale: vec2u,
-    offset: vec4u,
+    scale: EncodedFp,
+    offset: EncodedVec2,
     initial_z: EncodedComplexParameter,
     c: EncodedComplexParameter,
     p: EncodedComplexParameter,
-    escape_radius: vec2u,
+    roots: array<EncodedComplexParameter, MAX_ROOT_COUNT>,
+    escape_radius: EncodedFp,
+    formula: u32,
+    root_count: u32,
+    tolerance: EncodedFp,
+    reference_offset: EncodedVec2,
+    reference_length: u32,
+    perturbation: u32,
+    palette: Palette,
+    trap_center: EncodedComplexParameter,
+    trap_geometry: EncodedComplexParameter,
+    interior_coloring: u32,
+    trap_shape: u32,
+    exterior_coloring: u32,
+    boundary_thickness: f32,
+    stripe_density: f32,
+    histogram_pass: u32,
+    lighting_pass: u32,
+    height_source: u32,
+    light_azimuth: f32,
+    light_elevation: f32,
+    height_scale: f32,
+    specular: f32,
+    pixel_transform: mat2x2f,
+    pixel_transform_offset: vec2f,
 }
 #endif
 
@@ -83,6 +250,28 @@ struct FragmentInput {
 struct FractalResult {
     exit_iteration: u32,
     final_z: vec2<fp>,
+    // smallest distance between the orbit and the trap
+    trap_distance: fp,
+    // magnitude of the derivative of z with respect to the pixel, 0 if it wasn't tracked
+    derivative: fp,
+    orbit_average: OrbitAverage,
+}
+
+// running average of a statistic of the orbit, used by the stripe and triangle inequality coloring
+struct OrbitAverage {
+    sum: f32,
+    // the last value added to the sum
+    last: f32,
+    count: u32,
+    // sum of the distances from the origin, for the average distance interior coloring
+    distance_sum: f32,
+}
+
+struct NewtonResult {
+    root_index: u32,
+    iterations: u32,
+    // distance to the root at the last iteration
+    root_distance: fp,
 }
 
 struct FractalParams {
@@ -92,53 +281,132 @@ struct FractalParams {
     escape_radius: fp,
     iteration_count: u32,
     exp_mode: u32,
+    formula: u32,
+    roots: array<vec2<fp>, MAX_ROOT_COUNT>,
+    root_count: u32,
+    tolerance: fp,
+    interior_coloring: u32,
+    trap_shape: u32,
+    trap_center: vec2<fp>,
+    trap_radius: fp,
+    // unit vector along the line, or one of the lines of the cross
+    trap_direction: vec2<fp>,
+    exterior_coloring: u32,
+    boundary_thickness: f32,
+    stripe_density: f32,
+    // whether the derivative has to be tracked for the distance estimate
+    track_derivative: bool,
+    // size of a pixel in the complex plane
+    pixel_size: fp,
+    p_magnitude: fp,
+    // derivatives of the initial z and c with respect to the pixel
+    derivative_z: fp,
+    derivative_c: fp,
 }
 
 @group(2) @binding(0) var<uniform> encoded_material: EncodedFractalMaterial;
 
-#ifndef DOUBLE_PRECISION
+#ifndef ENCODED_FLOATS
+@group(2) @binding(1) var<storage, read> reference_orbit: array<vec2<fp>>;
+#else
+@group(2) @binding(1) var<storage, read> reference_orbit: array<EncodedVec2>;
+#endif
+
+struct Histogram {
+    bins: array<u32, HISTOGRAM_BIN_COUNT>,
+    // fraction of the values below the start of each bin
+    cdf: array<f32, CDF_LENGTH>,
+}
+
+// one value per pixel of the viewport, negative for pixels that aren't colored by the histogram
+@group(2) @binding(2) var<storage, read_write> histogram_values: array<f32>;
+@group(2) @binding(3) var<storage, read> histogram: Histogram;
+// one height per pixel of the viewport
+@group(2) @binding(4) var<storage, read_write> heights: array<f32>;
+
+#ifndef ENCODED_FLOATS
 fn decode_material() -> FractalMaterial {
     return encoded_material;
 }
+
+fn reference_orbit_point(i: u32) -> vec2<fp> {
+    return reference_orbit[i];
+}
 #else
-fn decode_f64(encoded: vec2u) -> f64 {
+#ifdef DOUBLE_PRECISION
+fn decode_fp(encoded: EncodedFp) -> fp {
     let lo = encoded.x;
     let hi = encoded.y;
 
     let bits: u64 = u64(lo) | (u64(hi) << 32);
     return bitcast<f64>(bits);
 }
+#else
+fn decode_fp(encoded: EncodedFp) -> fp {
+    return encoded.x + encoded.y;
+}
+#endif
 
 fn decode_complex_parameter(encoded: EncodedComplexParameter) -> ComplexParameter {
     var out: ComplexParameter;
 
     out.real_index = encoded.real_index;
     out.imag_index = encoded.imag_index;
-    out.real_value = decode_f64(encoded.real_value);
-    out.imag_value = decode_f64(encoded.imag_value);
+    out.real_value = decode_fp(encoded.real_value);
+    out.imag_value = decode_fp(encoded.imag_value);
 
     return out;
 }
 
-fn decode_vec2(encoded: vec4u) -> vec2<f64> {
+fn decode_vec2(encoded: EncodedVec2) -> vec2<fp> {
     let x = vec2(encoded.x, encoded.y);
     let y = vec2(encoded.z, encoded.w);
 
-    return vec2(decode_f64(x), decode_f64(y));
+    return vec2(decode_fp(x), decode_fp(y));
 }
 
 fn decode_material() -> FractalMaterial {
     var out: FractalMaterial;
 
     out.iteration_count = encoded_material.iteration_count;
-    out.scale = decode_f64(encoded_material.scale);
-    out.escape_radius = decode_f64(encoded_material.escape_radius);
+    out.scale = decode_fp(encoded_material.scale);
+    out.escape_radius = decode_fp(encoded_material.escape_radius);
     out.offset = decode_vec2(encoded_material.offset);
     out.initial_z = decode_complex_parameter(encoded_material.initial_z);
     out.c = decode_complex_parameter(encoded_material.c);
     out.p = decode_complex_parameter(encoded_material.p);
+    out.formula = encoded_material.formula;
+    for (var i: u32 = 0; i < MAX_ROOT_COUNT; i += 1u) {
+        out.roots[i] = decode_complex_parameter(encoded_material.roots[i]);
+    }
+    out.root_count = encoded_material.root_count;
+    out.tolerance = decode_fp(encoded_material.tolerance);
+    out.reference_offset = decode_vec2(encoded_material.reference_offset);
+    out.reference_length = encoded_material.reference_length;
+    out.perturbation = encoded_material.perturbation;
+    out.palette = encoded_material.palette;
+    out.trap_center = decode_complex_parameter(encoded_material.trap_center);
+    out.trap_geometry = decode_complex_parameter(encoded_material.trap_geometry);
+    out.interior_coloring = encoded_material.interior_coloring;
+    out.trap_shape = encoded_material.trap_shape;
+    out.exterior_coloring = encoded_material.exterior_coloring;
+    out.boundary_thickness = encoded_material.boundary_thickness;
+    out.stripe_density = encoded_material.stripe_density;
+    out.histogram_pass = encoded_material.histogram_pass;
+    out.lighting_pass = encoded_material.lighting_pass;
+    out.height_source = encoded_material.height_source;
+    out.light_azimuth = encoded_material.light_azimuth;
+    out.light_elevation = encoded_material.light_elevation;
+    out.height_scale = encoded_material.height_scale;
+    out.specular = encoded_material.specular;
+    out.pixel_transform = encoded_material.pixel_transform;
+    out.pixel_transform_offset = encoded_material.pixel_transform_offset;
     return out;
 }
+
+fn reference_orbit_point(i: u32) -> vec2<fp> {
+    return decode_vec2(reference_orbit[i]);
+}
 #endif
 
 @vertex
@@ -153,15 +421,168 @@ fn vertex(in: FractalVertexInput) -> FragmentInput {
 @fragment
 fn fragment(in: FragmentInput) -> @location(0) vec4f {
     let material = decode_material();
+    // has to be done before any branching
+    let world_pixel_size = fwidth(in.world_pos);
 
     let x = fp(in.world_pos.x) * material.scale + material.offset.x;
     let y = fp(in.world_pos.y) * material.scale + material.offset.y;
-    let params = get_fractal_params(x, y, material);
+    var params = get_fractal_params(x, y, material);
+    params.pixel_size = fp(max(world_pixel_size.x, world_pixel_size.y)) * material.scale;
+
+    if material.histogram_pass == HISTOGRAM_PASS_COLORS {
+        return histogram_color(in.clip_pos.xy, material.iteration_count);
+    }
+    if material.lighting_pass == LIGHTING_PASS_SHADING {
+        return shading(in.clip_pos.xy, world_pixel_size, material);
+    }
+
+    if material.perturbation != 0u {
+        // distance from the center of the reference orbit
+        let dx = fp(in.world_pos.x) * material.scale + material.reference_offset.x;
+        let dy = fp(in.world_pos.y) * material.scale + material.reference_offset.y;
+        let perturbation_params = get_perturbation_params(dx, dy, material);
+
+        let res = perturbation(perturbation_params, material.reference_length, params.c);
+        write_histogram_value(in.clip_pos.xy, res, params, material);
+        write_height(in.clip_pos.xy, res, params, material);
+        // coloring the interior needs the actual coordinates, which are only approximate at deep zooms
+        return vec4(fractal_res_to_color(res, params), 1.0);
+    }
+
+    if params.formula == FORMULA_NEWTON {
+        let res = newton(params);
+        return vec4(newton_res_to_color(res, params), 1.0);
+    }
+
+#ifdef DOUBLE_SINGLE
+    let res = fractal_double_single(in.world_pos, material, params);
+#else
     let res = fractal(params);
+#endif
 
+    write_histogram_value(in.clip_pos.xy, res, params, material);
+    write_height(in.clip_pos.xy, res, params, material);
     return vec4(fractal_res_to_color(res, params), 1.0);
 }
 
+fn write_histogram_value(position: vec2f, res: FractalResult, params: FractalParams, material: FractalMaterial) {
+    if material.histogram_pass != HISTOGRAM_PASS_VALUES {
+        return;
+    }
+
+    let index = pixel_index(position);
+    if index >= arrayLength(&histogram_values) {
+        return;
+    }
+
+    // the interior and orbit traps keep the color from the first pass
+    var value = -1.0;
+    if res.exit_iteration != params.iteration_count && params.trap_shape == TRAP_NONE {
+        value = f32(res.exit_iteration) + escape_fraction(res, params);
+    }
+    histogram_values[index] = value;
+}
+
+// the first pass is drawn underneath, transparent pixels show its color
+fn histogram_color(position: vec2f, iteration_count: u32) -> vec4f {
+    let index = pixel_index(position);
+    if index >= arrayLength(&histogram_values) {
+        return vec4(0.0);
+    }
+
+    let value = histogram_values[index];
+    if value < 0.0 {
+        return vec4(0.0);
+    }
+
+    let bin_position = histogram_position(value, iteration_count);
+    let bin = min(u32(bin_position), HISTOGRAM_BIN_COUNT - 1u);
+    let t = mix(histogram.cdf[bin], histogram.cdf[bin + 1u], saturate(bin_position - f32(bin)));
+    return vec4(palette_sample(t), 1.0);
+}
+
+// index of the pixel in the buffers with a value for every pixel of the viewport
+fn pixel_index(position: vec2f) -> u32 {
+    let pixel = vec2<u32>(position - view.viewport.xy);
+    return pixel.y * u32(view.viewport.z) + pixel.x;
+}
+
+fn write_height(position: vec2f, res: FractalResult, params: FractalParams, material: FractalMaterial) {
+    if material.lighting_pass != LIGHTING_PASS_HEIGHTS {
+        return;
+    }
+
+    let index = pixel_index(position);
+    if index < arrayLength(&heights) {
+        heights[index] = height(res, params, material);
+    }
+}
+
+// height of the point in the height field the lighting is calculated for
+fn height(res: FractalResult, params: FractalParams, material: FractalMaterial) -> f32 {
+    let escaped = res.exit_iteration != params.iteration_count;
+
+    if material.height_source == HEIGHT_DISTANCE_ESTIMATE {
+        // higher closer to the boundary, the interior is the highest
+        var distance = 0.0;
+        if escaped && res.derivative > 0.0 {
+            let reference_pixel_size = material.scale * fp(LIGHTING_REFERENCE_PIXEL_SIZE);
+            distance = f32(distance_estimate(res) / reference_pixel_size);
+        }
+        return -log2(max(distance, MIN_HEIGHT_DISTANCE));
+    }
+
+    // the logarithm keeps the slopes similar between the first and the last iterations
+    if !escaped {
+        return log2(2.0 + f32(params.iteration_count));
+    }
+    return log2(1.0 + f32(res.exit_iteration) + escape_fraction(res, params));
+}
+
+// the blend state of the shading pass multiplies the color underneath by alpha and adds rgb to it
+fn shading(position: vec2f, world_pixel_size: vec2f, material: FractalMaterial) -> vec4f {
+    let pixel = vec2<i32>(position - view.viewport.xy);
+
+    // framebuffer y goes down, while the light's y goes up
+    let height_difference = vec2(
+        height_at(pixel + vec2(1, 0)) - height_at(pixel - vec2(1, 0)),
+        height_at(pixel - vec2(0, 1)) - height_at(pixel + vec2(0, 1)),
+    );
+    let slope = height_difference / (2.0 * world_pixel_size) * LIGHTING_REFERENCE_PIXEL_SIZE
+        * material.height_scale;
+    let normal = normalize(vec3(-slope, 1.0));
+
+    let light = vec3(
+        cos(material.light_elevation) * vec2(cos(material.light_azimuth), sin(material.light_azimuth)),
+        sin(material.light_elevation),
+    );
+    let diffuse = max(dot(normal, light), 0.0);
+
+    // blinn-phong, looking straight down at the screen
+    let half_vector = normalize(light + vec3(0.0, 0.0, 1.0));
+    let specular = material.specular * pow(max(dot(normal, half_vector), 0.0), SHININESS);
+
+    return vec4(vec3(specular), AMBIENT_LIGHT + (1.0 - AMBIENT_LIGHT) * diffuse);
+}
+
+// height of the pixel, pixels outside the viewport get the height of the closest one inside,
+// exported tiles are rendered with a border around them so this only happens at the edges of the image
+fn height_at(pixel: vec2<i32>) -> f32 {
+    let size = vec2<i32>(view.viewport.zw);
+    let clamped = vec2<u32>(clamp(pixel, vec2(0), size - 1));
+    let index = clamped.y * u32(size.x) + clamped.x;
+
+    if index >= arrayLength(&heights) {
+        return 0.0;
+    }
+    return heights[index];
+}
+
+// the same as in the histogram shader
+fn histogram_position(value: f32, iteration_count: u32) -> f32 {
+    return log2(1.0 + value) / log2(1.0 + f32(iteration_count)) * f32(HISTOGRAM_BIN_COUNT);
+}
+
 fn get_fractal_params(x: fp, y: fp, material: FractalMaterial) -> FractalParams {
     var param_array: array<fp, PARAM_ARRAY_SIZE>;
     param_array[Z_R_VALUE_INDEX] = material.initial_z.real_value;
@@ -170,8 +591,23 @@ fn get_fractal_params(x: fp, y: fp, material: FractalMaterial) -> FractalParams
     param_array[C_I_VALUE_INDEX] = material.c.imag_value;
     param_array[PIXEL_X_INDEX] = x;
     param_array[PIXEL_Y_INDEX] = y;
+    var derived = derived_pixel_params(x, y, material);
+    for (var i: u32 = 0; i < DERIVED_PIXEL_PARAM_COUNT; i += 1u) {
+        param_array[PIXEL_RADIUS_INDEX + i] = derived[i];
+    }
     param_array[P_R_VALUE_INDEX] = material.p.real_value;
     param_array[P_I_VALUE_INDEX] = material.p.imag_value;
+    param_array[TRAP_CENTER_R_VALUE_INDEX] = material.trap_center.real_value;
+    param_array[TRAP_CENTER_I_VALUE_INDEX] = material.trap_center.imag_value;
+    param_array[TRAP_RADIUS_VALUE_INDEX] = material.trap_geometry.real_value;
+    param_array[TRAP_ANGLE_VALUE_INDEX] = material.trap_geometry.imag_value;
+
+    // copying so that it can be indexed dynamically
+    var roots = material.roots;
+    for (var i: u32 = 0; i < MAX_ROOT_COUNT; i += 1u) {
+        param_array[ROOT_VALUE_INDEX_START + 2u * i] = roots[i].real_value;
+        param_array[ROOT_VALUE_INDEX_START + 2u * i + 1u] = roots[i].imag_value;
+    }
 
     var out: FractalParams;
     out.z.x = param_array[material.initial_z.real_index];
@@ -184,10 +620,115 @@ fn get_fractal_params(x: fp, y: fp, material: FractalMaterial) -> FractalParams
     out.exp_mode = get_exp_mode(material);
     out.escape_radius = material.escape_radius;
     out.iteration_count = material.iteration_count;
+    out.formula = material.formula;
+    for (var i: u32 = 0; i < MAX_ROOT_COUNT; i += 1u) {
+        out.roots[i].x = param_array[roots[i].real_index];
+        out.roots[i].y = param_array[roots[i].imag_index];
+    }
+    out.root_count = min(material.root_count, MAX_ROOT_COUNT);
+    out.tolerance = material.tolerance;
+    out.interior_coloring = material.interior_coloring;
+    out.trap_shape = material.trap_shape;
+    out.trap_center.x = param_array[material.trap_center.real_index];
+    out.trap_center.y = param_array[material.trap_center.imag_index];
+    out.trap_radius = param_array[material.trap_geometry.real_index];
+    // the direction doesn't need to be precise
+    let trap_angle = f32(param_array[material.trap_geometry.imag_index]);
+    out.trap_direction = vec2<fp>(vec2(cos(trap_angle), sin(trap_angle)));
+
+    out.exterior_coloring = material.exterior_coloring;
+    out.boundary_thickness = material.boundary_thickness;
+    out.stripe_density = material.stripe_density;
+    out.track_derivative = material.exterior_coloring == EXTERIOR_DISTANCE_ESTIMATE
+        || (material.lighting_pass == LIGHTING_PASS_HEIGHTS && material.height_source == HEIGHT_DISTANCE_ESTIMATE);
+    out.p_magnitude = length(out.p);
+    out.derivative_z = pixel_derivative(material.initial_z, derived, material);
+    out.derivative_c = pixel_derivative(material.c, derived, material);
 
     return out;
 }
 
+// the parameters calculated from the pixel, in the order of their indices starting at PIXEL_RADIUS_INDEX
+fn derived_pixel_params(x: fp, y: fp, material: FractalMaterial) -> array<fp, DERIVED_PIXEL_PARAM_COUNT> {
+    let pixel = vec2(x, y);
+    let inverse = complex_inv(pixel);
+    // the transformation doesn't need to be precise, it's only stored in f32
+    let transform = material.pixel_transform;
+    let offset = material.pixel_transform_offset;
+    let transformed_x = fp(transform[0].x) * x + fp(transform[1].x) * y + fp(offset.x);
+    let transformed_y = fp(transform[0].y) * x + fp(transform[1].y) * y + fp(offset.y);
+
+    return array<fp, DERIVED_PIXEL_PARAM_COUNT>(
+        length(pixel), fp_atan2(y, x), inverse.x, inverse.y, transformed_x, transformed_y
+    );
+}
+
+// magnitude of the derivative of the parameter with respect to the pixel,
+// the parts can be calculated differently so it's the larger of the two
+fn pixel_derivative(
+    param: ComplexParameter,
+    derived: array<fp, DERIVED_PIXEL_PARAM_COUNT>,
+    material: FractalMaterial
+) -> fp {
+    return max(
+        pixel_index_derivative(param.real_index, derived, material),
+        pixel_index_derivative(param.imag_index, derived, material)
+    );
+}
+
+// how much the value at the index changes when the pixel moves by 1
+fn pixel_index_derivative(
+    index: u32,
+    derived: array<fp, DERIVED_PIXEL_PARAM_COUNT>,
+    material: FractalMaterial
+) -> fp {
+    // 1 / |pixel|
+    let inverse_length = length(vec2(
+        derived[INVERSE_PIXEL_R_INDEX - PIXEL_RADIUS_INDEX],
+        derived[INVERSE_PIXEL_I_INDEX - PIXEL_RADIUS_INDEX]
+    ));
+
+    switch index {
+        case PIXEL_X_INDEX, PIXEL_Y_INDEX, PIXEL_RADIUS_INDEX {
+            return fp(1.0);
+        }
+        case PIXEL_ANGLE_INDEX {
+            return inverse_length;
+        }
+        case INVERSE_PIXEL_R_INDEX, INVERSE_PIXEL_I_INDEX {
+            return inverse_length * inverse_length;
+        }
+        case TRANSFORMED_PIXEL_X_INDEX, TRANSFORMED_PIXEL_Y_INDEX {
+            return fp(matrix_norm(material.pixel_transform));
+        }
+        default {
+            return fp(0.0);
+        }
+    }
+}
+
+// the most the matrix stretches a vector by, its largest singular value
+fn matrix_norm(m: mat2x2f) -> f32 {
+    let sum_of_squares = dot(m[0], m[0]) + dot(m[1], m[1]);
+    let det = determinant(m);
+    let discriminant = max(sum_of_squares * sum_of_squares - 4.0 * det * det, 0.0);
+    return sqrt(0.5 * (sum_of_squares + sqrt(discriminant)));
+}
+
+// the same as get_fractal_params, but z and c are the differences from the reference orbit
+// the other parameters calculated from the pixel would be wrong for the difference,
+// they aren't supported with perturbation, see `perturbation_supported`
+fn get_perturbation_params(dx: fp, dy: fp, material: FractalMaterial) -> FractalParams {
+    // the constant parts of the parameters are already included in the reference orbit
+    var m = material;
+    m.initial_z.real_value = fp(0.0);
+    m.initial_z.imag_value = fp(0.0);
+    m.c.real_value = fp(0.0);
+    m.c.imag_value = fp(0.0);
+
+    return get_fractal_params(dx, dy, m);
+}
+
 fn get_exp_mode(m: FractalMaterial) -> u32 {
     // if the exponent is constant
     if m.p.imag_index == P_I_VALUE_INDEX && m.p.imag_value == 0.0 {
@@ -219,35 +760,65 @@ fn fractal(params: FractalParams) -> FractalResult {
     var z = params.z;
     let c = params.c;
     let p = params.p;
+    let f = params.formula;
     var out: FractalResult;
+    var trap_distance = fp(MAX_TRAP_DISTANCE);
+    var derivative = vec2<fp>(params.derivative_z, 0.0);
+    var average: OrbitAverage;
 
     let r_squared = params.escape_radius * params.escape_radius;
 
     var i: u32 = 0;
 
+    // the custom formula can be anything, so it gets its own loop
+    if params.formula == FORMULA_CUSTOM {
+        for (; i < params.iteration_count; i += 1u) {
+            z = custom_formula(z, c, p);
+            trap_distance = min(trap_distance, distance_to_trap(z, params));
+            add_to_orbit_average(&average, z, c, params);
+
+            if z.x * z.x + z.y * z.y > r_squared {
+                break;
+            }
+        }
+
+        out.exit_iteration = i;
+        out.final_z = z;
+        out.trap_distance = trap_distance;
+        out.orbit_average = average;
+        return out;
+    }
+
     // if starting z is 0, there can be a lot of issues with exponentioation 
     // blowing up
     // so we just do a dummy iteration
     if z.x == 0 && z.y == 0.0 {
         z = c;
+        trap_distance = distance_to_trap(z, params);
+        derivative = vec2<fp>(params.derivative_c, 0.0);
 
         if z.x * z.x + z.y * z.y > r_squared {
             out.exit_iteration = i;
             out.final_z = z;
+            out.trap_distance = trap_distance;
+            out.orbit_average = average;
+            out.derivative = length(derivative);
             return out;
         }
         if params.iteration_count > 0 {
             i += 1u;
         }
     }
-    // TODO: maybe we should just use f32 in non integer exponents, since 
-    // casting to f32 and back is not making the result look any better
-    
     // hoisting all the branches out of the loop
+    // the formula is the same for every pixel, so branching on it inside the loop is cheap
     switch params.exp_mode {
         case EXP_2 {
             for (; i < params.iteration_count; i += 1u) {
-                z = complex_square(z) + c;
+                let powered = complex_square(formula_pre(z, f));
+                derivative = next_derivative(derivative, z, powered, params);
+                z = formula_post(powered, f) + c;
+                trap_distance = min(trap_distance, distance_to_trap(z, params));
+                add_to_orbit_average(&average, z, c, params);
 
                 if z.x * z.x + z.y * z.y > r_squared {
                     break;
@@ -256,7 +827,11 @@ fn fractal(params: FractalParams) -> FractalResult {
         }
         case EXP_NEG_2 {
             for (; i < params.iteration_count; i += 1u) {
-                z = complex_inv_square(z) + c;
+                let powered = complex_inv_square(formula_pre(z, f));
+                derivative = next_derivative(derivative, z, powered, params);
+                z = formula_post(powered, f) + c;
+                trap_distance = min(trap_distance, distance_to_trap(z, params));
+                add_to_orbit_average(&average, z, c, params);
 
                 if z.x * z.x + z.y * z.y > r_squared {
                     break;
@@ -266,7 +841,11 @@ fn fractal(params: FractalParams) -> FractalResult {
         case EXP_0 {
             for (; i < params.iteration_count; i += 1u) {
                 let z_is_zero = z.x == 0.0 && z.y == 0.0;
+                // p is 0, so only c contributes to the derivative
+                derivative = next_derivative(derivative, z, z, params);
                 z = vec2(fp(!z_is_zero), 0.0) + c;
+                trap_distance = min(trap_distance, distance_to_trap(z, params));
+                add_to_orbit_average(&average, z, c, params);
 
                 if z.x * z.x + z.y * z.y > r_squared {
                     break;
@@ -275,7 +854,11 @@ fn fractal(params: FractalParams) -> FractalResult {
         }
         case EXP_POS_INT {
             for (; i < params.iteration_count; i += 1u) {
-                z = complex_pow_pos_int(z, u32(p.x)) + c;
+                let powered = complex_pow_pos_int(formula_pre(z, f), u32(p.x));
+                derivative = next_derivative(derivative, z, powered, params);
+                z = formula_post(powered, f) + c;
+                trap_distance = min(trap_distance, distance_to_trap(z, params));
+                add_to_orbit_average(&average, z, c, params);
 
                 if z.x * z.x + z.y * z.y > r_squared {
                     break;
@@ -284,7 +867,11 @@ fn fractal(params: FractalParams) -> FractalResult {
         }
         case EXP_NEG_INT {
             for (; i < params.iteration_count; i += 1u) {
-                z = complex_pow_neg_int(z, i32(p.x)) + c;
+                let powered = complex_pow_neg_int(formula_pre(z, f), i32(p.x));
+                derivative = next_derivative(derivative, z, powered, params);
+                z = formula_post(powered, f) + c;
+                trap_distance = min(trap_distance, distance_to_trap(z, params));
+                add_to_orbit_average(&average, z, c, params);
 
                 if z.x * z.x + z.y * z.y > r_squared {
                     break;
@@ -293,7 +880,11 @@ fn fractal(params: FractalParams) -> FractalResult {
         }
         case EXP_REAL {
             for (; i < params.iteration_count; i += 1u) {
-                z = complex_pow_real(z, p.x) + c;
+                let powered = complex_pow_real(formula_pre(z, f), p.x);
+                derivative = next_derivative(derivative, z, powered, params);
+                z = formula_post(powered, f) + c;
+                trap_distance = min(trap_distance, distance_to_trap(z, params));
+                add_to_orbit_average(&average, z, c, params);
 
                 if z.x * z.x + z.y * z.y > r_squared {
                     break;
@@ -302,7 +893,11 @@ fn fractal(params: FractalParams) -> FractalResult {
         }
         case EXP_COMPLEX {
             for (; i < params.iteration_count; i += 1u) {
-                z = complex_pow_complex(z, p) + c;
+                let powered = complex_pow_complex(formula_pre(z, f), p);
+                derivative = next_derivative(derivative, z, powered, params);
+                z = formula_post(powered, f) + c;
+                trap_distance = min(trap_distance, distance_to_trap(z, params));
+                add_to_orbit_average(&average, z, c, params);
 
                 if z.x * z.x + z.y * z.y > r_squared {
                     break;
@@ -314,32 +909,465 @@ fn fractal(params: FractalParams) -> FractalResult {
 
     out.exit_iteration = i;
     out.final_z = z;
+    out.trap_distance = trap_distance;
+    out.orbit_average = average;
+    out.derivative = length(derivative);
+    return out;
+}
+
+// a single iteration of the formula,
+// slower than the loops in `fractal` since it has to branch on the exponent every time
+fn fractal_step(z: vec2<fp>, params: FractalParams) -> vec2<fp> {
+    let c = params.c;
+    let p = params.p;
+    let f = params.formula;
+
+    if f == FORMULA_CUSTOM {
+        return custom_formula(z, c, p);
+    }
+    // the same as the dummy iteration in `fractal`
+    if z.x == 0.0 && z.y == 0.0 {
+        return c;
+    }
+
+    let pre = formula_pre(z, f);
+    var powered: vec2<fp>;
+    switch params.exp_mode {
+        case EXP_2 {
+            powered = complex_square(pre);
+        }
+        case EXP_NEG_2 {
+            powered = complex_inv_square(pre);
+        }
+        case EXP_0 {
+            powered = vec2<fp>(1.0, 0.0);
+        }
+        case EXP_POS_INT {
+            powered = complex_pow_pos_int(pre, u32(p.x));
+        }
+        case EXP_NEG_INT {
+            powered = complex_pow_neg_int(pre, i32(p.x));
+        }
+        case EXP_REAL {
+            powered = complex_pow_real(pre, p.x);
+        }
+        default {
+            powered = complex_pow_complex(pre, p);
+        }
+    }
+
+    return formula_post(powered, f) + c;
+}
+
+// the derivative of z along the real axis of the pixel after one iteration,
+// `powered` is z^p after `formula_pre`,
+// the absolute values in some formulas make the complex derivative meaningless,
+// so for those only a bound of its magnitude is tracked in the real part,
+// using |d(z^p)/dz| = |p| * |z^p| / |z|
+fn next_derivative(derivative: vec2<fp>, z: vec2<fp>, powered: vec2<fp>, params: FractalParams) -> vec2<fp> {
+    if !params.track_derivative {
+        return derivative;
+    }
+
+    let dc = vec2<fp>(params.derivative_c, 0.0);
+    let pre = formula_pre(z, params.formula);
+    if pre.x == 0.0 && pre.y == 0.0 {
+        return dc;
+    }
+
+    if formula_has_abs(params.formula) {
+        let bound = params.p_magnitude * length(powered) / length(pre) * length(derivative);
+        return vec2<fp>(bound, 0.0) + dc;
+    }
+
+    // dz = p * z^(p - 1) * dz + dc, conjugating is linear so it applies to dz the same way as to z
+    let chain = complex_mult(params.p, complex_div(powered, pre));
+    return complex_mult(chain, formula_pre(derivative, params.formula)) + dc;
+}
+
+// whether the formula takes absolute values of parts of z, which makes it not differentiable
+fn formula_has_abs(formula: u32) -> bool {
+    return formula == FORMULA_BURNING_SHIP || formula == FORMULA_CELTIC || formula == FORMULA_BUFFALO;
+}
+
+// adds the statistic of the coloring method for the latest point of the orbit to its average
+fn add_to_orbit_average(average: ptr<function, OrbitAverage>, z: vec2<fp>, c: vec2<fp>, params: FractalParams) {
+    if params.interior_coloring == INTERIOR_AVERAGE_DISTANCE {
+        (*average).distance_sum += f32(length(z));
+    }
+
+    var value: f32;
+    switch params.exterior_coloring {
+        case EXTERIOR_STRIPE_AVERAGE {
+            value = 0.5 * sin(params.stripe_density * atan2(f32(z.y), f32(z.x))) + 0.5;
+        }
+        case EXTERIOR_TRIANGLE_INEQUALITY {
+            // where |z| is between the smallest and largest values |z - c + c| could have,
+            // the absolute values in some formulas don't change the magnitude of z - c
+            let z_length = f32(length(z));
+            let powered_length = f32(length(z - c));
+            let c_length = f32(length(c));
+            let lower = abs(powered_length - c_length);
+            let upper = powered_length + c_length;
+            if upper - lower <= 0.0 {
+                return;
+            }
+            value = (z_length - lower) / (upper - lower);
+        }
+        default {
+            return;
+        }
+    }
+
+    (*average).sum += value;
+    (*average).last = value;
+    (*average).count += 1u;
+}
+
+fn distance_to_trap(z: vec2<fp>, params: FractalParams) -> fp {
+    let offset = z - params.trap_center;
+    // distances along and across the direction of the trap
+    let along = abs(dot(offset, params.trap_direction));
+    let across = abs(offset.x * params.trap_direction.y - offset.y * params.trap_direction.x);
+
+    switch params.trap_shape {
+        case TRAP_POINT {
+            return length(offset);
+        }
+        case TRAP_LINE {
+            return across;
+        }
+        case TRAP_CROSS {
+            return min(along, across);
+        }
+        case TRAP_CIRCLE {
+            return abs(length(offset) - params.trap_radius);
+        }
+        default {
+            return fp(MAX_TRAP_DISTANCE);
+        }
+    }
+}
+
+// iterates the difference between the pixel's orbit and the reference orbit,
+// only supports formulas where that difference can be calculated precisely,
+// with positive integer exponents
+// `c` is the c of the pixel, only used for coloring
+fn perturbation(params: FractalParams, reference_length: u32, c: vec2<fp>) -> FractalResult {
+    var dz = params.z;
+    let dc = params.c;
+    let p = u32(params.p.x);
+    let f = params.formula;
+    var out: FractalResult;
+    var trap_distance = fp(MAX_TRAP_DISTANCE);
+    var derivative = vec2<fp>(params.derivative_z, 0.0);
+    var average: OrbitAverage;
+
+    let r_squared = params.escape_radius * params.escape_radius;
+
+    // index into the reference orbit
+    var ref_i: u32 = 0;
+    var z = reference_orbit_point(0u) + dz;
+
+    var i: u32 = 0;
+    for (; i < params.iteration_count; i += 1u) {
+        if params.track_derivative {
+            derivative = next_derivative(derivative, z, complex_pow_pos_int(formula_pre(z, f), p), params);
+        }
+
+        let reference = formula_pre(reference_orbit_point(ref_i), f);
+        // the pre step of the supported formulas is linear, so it can be applied to dz separately
+        dz = perturb_pow(reference, formula_pre(dz, f), p) + dc;
+        ref_i += 1u;
+        z = reference_orbit_point(ref_i) + dz;
+        trap_distance = min(trap_distance, distance_to_trap(z, params));
+        add_to_orbit_average(&average, z, c, params);
+
+        if z.x * z.x + z.y * z.y > r_squared {
+            break;
+        }
+
+        // glitches happen when dz gets larger than z itself, since then dz stops being small
+        // compared to the values it's added to and loses precision,
+        // in that case, or if the reference orbit runs out,
+        // we continue from the start of the reference orbit instead
+        let dz_squared = dz.x * dz.x + dz.y * dz.y;
+        if z.x * z.x + z.y * z.y < dz_squared || ref_i + 1u >= reference_length {
+            dz = z - reference_orbit_point(0u);
+            ref_i = 0u;
+        }
+    }
+
+    out.exit_iteration = i;
+    out.final_z = z;
+    out.trap_distance = trap_distance;
+    out.orbit_average = average;
+    out.derivative = length(derivative);
+    return out;
+}
+
+// (reference + delta)^p - reference^p, without subtracting large values from each other
+fn perturb_pow(reference: vec2<fp>, delta: vec2<fp>, p: u32) -> vec2<fp> {
+    if p == 2u {
+        // 2 * reference * delta + delta^2
+        return complex_mult(2.0 * reference + delta, delta);
+    }
+
+    // (Z + d)^(k + 1) - Z^(k + 1) = (Z + d) * ((Z + d)^k - Z^k) + d * Z^k
+    var out = vec2<fp>(0.0, 0.0);
+    var reference_pow = vec2<fp>(1.0, 0.0);
+    for (var k: u32 = 0; k < p; k += 1u) {
+        out = complex_mult(reference + delta, out) + complex_mult(delta, reference_pow);
+        reference_pow = complex_mult(reference_pow, reference);
+    }
+
+    return out;
+}
+
+// the user-defined formula gets compiled to WGSL and inserted here
+fn custom_formula(z: vec2<fp>, c: vec2<fp>, p: vec2<fp>) -> vec2<fp> {
+    return complex_square(z) + c; // CUSTOM_FORMULA
+}
+
+fn newton(params: FractalParams) -> NewtonResult {
+    var z = params.z;
+    var roots = params.roots;
+    var out: NewtonResult;
+    out.root_index = NO_ROOT;
+
+    let tolerance_squared = params.tolerance * params.tolerance;
+
+    var i: u32 = 0;
+    for (; i < params.iteration_count; i += 1u) {
+        // f(z) = (z - r_0)(z - r_1)...(z - r_n)
+        // so f'(z) / f(z) = 1 / (z - r_0) + 1 / (z - r_1) + ... + 1 / (z - r_n)
+        var derivative_ratio = vec2<fp>(0.0, 0.0);
+
+        for (var k: u32 = 0; k < params.root_count; k += 1u) {
+            let diff = z - roots[k];
+            let dist_squared = diff.x * diff.x + diff.y * diff.y;
+
+            if dist_squared < tolerance_squared {
+                out.root_index = k;
+                out.iterations = i;
+                out.root_distance = sqrt(dist_squared);
+                return out;
+            }
+            derivative_ratio += complex_inv(diff);
+        }
+
+        z -= complex_inv(derivative_ratio);
+    }
+
+    out.iterations = i;
     return out;
 }
 
+// applied to z before it's raised to the power of p
+fn formula_pre(z: vec2<fp>, formula: u32) -> vec2<fp> {
+    switch formula {
+        case FORMULA_BURNING_SHIP {
+            return abs(z);
+        }
+        case FORMULA_TRICORN {
+            return vec2(z.x, -z.y);
+        }
+        default {
+            return z;
+        }
+    }
+}
+
+// applied to z^p before c is added
+fn formula_post(z: vec2<fp>, formula: u32) -> vec2<fp> {
+    switch formula {
+        case FORMULA_CELTIC {
+            return vec2(abs(z.x), z.y);
+        }
+        case FORMULA_BUFFALO {
+            return abs(z);
+        }
+        default {
+            return z;
+        }
+    }
+}
+
 fn fractal_res_to_color(res: FractalResult, params: FractalParams) -> vec3f {
-    // const escape_radius = 16.0;
-    // const curve_exp = 1.0;
-    const brightness_max_iter = fp(200.0);
+    if res.exit_iteration == params.iteration_count {
+        return interior_color(res, params);
+    }
+
+    if params.trap_shape != TRAP_NONE {
+        return trap_color(res.trap_distance);
+    }
+
+    let fraction = escape_fraction(res, params);
 
+    if params.exterior_coloring == EXTERIOR_STRIPE_AVERAGE
+        || params.exterior_coloring == EXTERIOR_TRIANGLE_INEQUALITY {
+        return palette_sample(orbit_average_value(res.orbit_average, fraction));
+    }
+
+    let value = f32(res.exit_iteration) + fraction;
+    let color = palette_color(value);
+
+    // the derivative isn't tracked for custom formulas
+    if params.exterior_coloring == EXTERIOR_DISTANCE_ESTIMATE && res.derivative > 0.0 {
+        // darkens the points closer to the boundary than its thickness
+        let boundary_distance = distance_estimate(res) / (params.pixel_size * fp(params.boundary_thickness));
+        return color * saturate(f32(boundary_distance));
+    }
+
+    return color;
+}
+
+// how far the last iteration went past the escape radius, for smoothing the iteration count
+fn escape_fraction(res: FractalResult, params: FractalParams) -> f32 {
     let x = res.final_z.x;
     let y = res.final_z.y;
     let dist = (sqrt(x * x + y * y) - params.escape_radius) / (params.escape_radius * params.escape_radius / 4.0);
-    let value = fp(res.exit_iteration) + 1.0 - saturate(dist);
-    let t = value / brightness_max_iter;
+    return f32(1.0 - saturate(dist));
+}
 
-    var brightness = fp(0.0);
-    if res.exit_iteration == params.iteration_count {
-        brightness = fp(0.0);
-    } else {
-        // let curved_t = pow(t, curve_exp);
-        let curved_t = t;
-        brightness = mix(fp(0.001), fp(1.0), curved_t);
+// the average with and without the last point of the orbit, interpolated to hide the bands
+// between points that escaped on different iterations
+fn orbit_average_value(average: OrbitAverage, fraction: f32) -> f32 {
+    if average.count < 2u {
+        return average.sum;
+    }
+
+    let current = average.sum / f32(average.count);
+    let previous = (average.sum - average.last) / f32(average.count - 1u);
+    return mix(previous, current, fraction);
+}
+
+// estimated distance from an escaped point to the boundary of the fractal
+fn distance_estimate(res: FractalResult) -> fp {
+    let z_length = length(res.final_z);
+    return 0.5 * z_length * fp_log(z_length) / res.derivative;
+}
+
+fn interior_color(res: FractalResult, params: FractalParams) -> vec3f {
+    switch params.interior_coloring {
+        case INTERIOR_FINAL_MAGNITUDE {
+            return palette_sample(f32(length(res.final_z) / params.escape_radius));
+        }
+        case INTERIOR_FINAL_ANGLE {
+            let angle = atan2(f32(res.final_z.y), f32(res.final_z.x));
+            return palette_sample(angle / TAU + 0.5);
+        }
+        case INTERIOR_PERIOD {
+            let period = orbit_period(res.final_z, params);
+            if period == 0u {
+                return vec3(0.0);
+            }
+            return palette_sample(f32(period - 1u) * GOLDEN_RATIO_CONJUGATE);
+        }
+        case INTERIOR_AVERAGE_DISTANCE {
+            // interior points went through every iteration
+            let average_distance = res.orbit_average.distance_sum / f32(max(res.exit_iteration, 1u));
+            return palette_sample(average_distance / f32(params.escape_radius));
+        }
+        case INTERIOR_ORBIT_TRAP {
+            if params.trap_shape == TRAP_NONE {
+                return vec3(0.0);
+            }
+            return trap_color(res.trap_distance);
+        }
+        default {
+            return vec3(0.0);
+        }
+    }
+}
+
+// a distance of 1 from the trap goes through the whole palette
+fn trap_color(trap_distance: fp) -> vec3f {
+    return palette_sample(f32(trap_distance));
+}
+
+// the period of the cycle the orbit gets attracted to,
+// found by continuing from the last point of the orbit until it comes back to it,
+// 0 if it doesn't come back within the iteration count
+fn orbit_period(final_z: vec2<fp>, params: FractalParams) -> u32 {
+    var z = final_z;
+
+    for (var i: u32 = 1; i <= params.iteration_count; i += 1u) {
+        z = fractal_step(z, params);
+
+        let diff = vec2<f32>(z - final_z);
+        if dot(diff, diff) < PERIOD_TOLERANCE_SQUARED {
+            return i;
+        }
+    }
+
+    return 0u;
+}
+
+// maps the smoothed iteration count to a color of the palette
+fn palette_color(value: f32) -> vec3f {
+    return palette_sample(value / encoded_material.palette.cycle_length);
+}
+
+// color at `t` along the gradient, after applying the offset
+fn palette_sample(t: f32) -> vec3f {
+    // read straight from the uniform, arrays can't be indexed dynamically once copied into a value
+    let count = encoded_material.palette.stop_count;
+    if count == 0u {
+        return vec3(0.0);
+    }
+
+    let position = fract(t + encoded_material.palette.offset);
+
+    var next_index = 0u;
+    while next_index < count && encoded_material.palette.stops[next_index].w <= position {
+        next_index += 1u;
+    }
+
+    // the stops before the first and after the last are in the neighbouring cycles
+    var previous = encoded_material.palette.stops[count - 1u];
+    previous.w -= 1.0;
+    if next_index > 0u {
+        previous = encoded_material.palette.stops[next_index - 1u];
+    }
+    var next = encoded_material.palette.stops[0];
+    next.w += 1.0;
+    if next_index < count {
+        next = encoded_material.palette.stops[next_index];
+    }
+
+    var fraction = saturate((position - previous.w) / max(next.w - previous.w, 1e-6));
+    switch encoded_material.palette.interpolation {
+        case INTERPOLATION_SMOOTH {
+            fraction = smoothstep(0.0, 1.0, fraction);
+        }
+        case INTERPOLATION_CONSTANT {
+            fraction = 0.0;
+        }
+        default {}
+    }
+
+    return mix(previous.rgb, next.rgb, fraction);
+}
+
+fn newton_res_to_color(res: NewtonResult, params: FractalParams) -> vec3f {
+    const brightness_max_iter = 50.0;
+
+    if res.root_index == NO_ROOT {
+        return vec3(0.0);
     }
 
-    var color = hsv2rgb(vec3(f32(value) * 0.01 + 0.6, 1.0, 1.0));
+    // newton's method converges quadratically, so the log of the distance to the root
+    // roughly doubles every iteration, we can use that to get a smooth value
+    let tolerance_log = log(f32(params.tolerance));
+    let distance_log = log(max(f32(res.root_distance), 1e-30));
+    let fraction = clamp(log2(tolerance_log / distance_log), -1.0, 0.0);
+    let value = f32(res.iterations) + fraction;
 
-    return color * f32(brightness);
+    let hue = f32(res.root_index) / f32(params.root_count);
+    let brightness = mix(1.0, 0.1, saturate(value / brightness_max_iter));
+
+    return hsv2rgb(vec3(hue, 0.75, brightness));
 }
 
 fn hsv2rgb(hsv: vec3f) -> vec3f {
@@ -423,15 +1451,250 @@ fn complex_mult(a: vec2<fp>, b: vec2<fp>) -> vec2<fp> {
     return vec2(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
 }
 
+fn complex_div(a: vec2<fp>, b: vec2<fp>) -> vec2<fp> {
+    return complex_mult(a, complex_inv(b));
+}
+
+fn complex_sqrt(z: vec2<fp>) -> vec2<fp> {
+    return complex_pow_real(z, fp(0.5));
+}
+
+fn complex_sin(z: vec2<fp>) -> vec2<fp> {
+    return vec2(fp_sin(z.x) * fp_cosh(z.y), fp_cos(z.x) * fp_sinh(z.y));
+}
+
+fn complex_cos(z: vec2<fp>) -> vec2<fp> {
+    return vec2(fp_cos(z.x) * fp_cosh(z.y), -fp_sin(z.x) * fp_sinh(z.y));
+}
+
+fn complex_tan(z: vec2<fp>) -> vec2<fp> {
+    return complex_div(complex_sin(z), complex_cos(z));
+}
+
+fn complex_sinh(z: vec2<fp>) -> vec2<fp> {
+    return vec2(fp_sinh(z.x) * fp_cos(z.y), fp_cosh(z.x) * fp_sin(z.y));
+}
+
+fn complex_cosh(z: vec2<fp>) -> vec2<fp> {
+    return vec2(fp_cosh(z.x) * fp_cos(z.y), fp_sinh(z.x) * fp_sin(z.y));
+}
+
+fn complex_tanh(z: vec2<fp>) -> vec2<fp> {
+    return complex_div(complex_sinh(z), complex_cosh(z));
+}
+
+fn complex_abs(z: vec2<fp>) -> vec2<fp> {
+    return vec2(length(z), 0.0);
+}
+
+fn complex_conj(z: vec2<fp>) -> vec2<fp> {
+    return vec2(z.x, -z.y);
+}
+
+fn complex_re(z: vec2<fp>) -> vec2<fp> {
+    return vec2(z.x, 0.0);
+}
+
+fn complex_im(z: vec2<fp>) -> vec2<fp> {
+    return vec2(z.y, 0.0);
+}
+
 fn complex_inv(z: vec2<fp>) -> vec2<fp> {
-    var norm_sqr = z.x * z.x + z.y * z.y;
-    // if norm_sqr == 0.0 {
-    //     return z;
-    // }
+    let norm_sqr = z.x * z.x + z.y * z.y;
+    if norm_sqr == 0.0 {
+        return vec2<fp>(fp(INVERSE_OF_ZERO), 0.0);
+    }
 
     return vec2(z.x / norm_sqr, -z.y / norm_sqr);
 }
 
+#ifdef DOUBLE_PRECISION
+// Vulkan only has f32 versions of the transcendental functions,
+// so the f64 ones are calculated from scratch with range reduction and polynomials
+
+// ln(2) and pi / 2 split into parts so that multiplying the first part by an integer is exact
+const LN2_HI: fp = 6.93147180369123816490e-01;
+const LN2_LO: fp = 1.90821492927058770002e-10;
+const INV_LN2: fp = 1.4426950408889634;
+const PIO2_1: fp = 1.57079632673412561417e+00;
+const PIO2_2: fp = 6.07710050630396597660e-11;
+const PIO2_3: fp = 2.02226624871116645580e-21;
+const TWO_OVER_PI: fp = 0.6366197723675814;
+const PI: fp = 3.141592653589793;
+const HALF_PI: fp = 1.5707963267948966;
+const SQRT_2: fp = 1.4142135623730951;
+const MIN_NORMAL_F64: fp = 2.2250738585072014e-308;
+const MAX_F64: fp = 1.7976931348623157e308;
+
+fn fp_sin(value: fp) -> fp {
+    return fp_sin_cos(value).x;
+}
+
+fn fp_cos(value: fp) -> fp {
+    return fp_sin_cos(value).y;
+}
+
+// returns both the sine and the cosine, since they're calculated together
+fn fp_sin_cos(value: fp) -> vec2<fp> {
+    // value = r + k * pi / 2, with |r| <= pi / 4
+    let k = floor(value * TWO_OVER_PI + 0.5);
+    let r = ((value - k * PIO2_1) - k * PIO2_2) - k * PIO2_3;
+    let r2 = r * r;
+
+    // taylor series, the error is below f64 epsilon for |r| <= pi / 4
+    var sin_r = fp(2.8114572543455206e-15);
+    sin_r = sin_r * r2 - 7.647163731819816e-13;
+    sin_r = sin_r * r2 + 1.6059043836821613e-10;
+    sin_r = sin_r * r2 - 2.505210838544172e-08;
+    sin_r = sin_r * r2 + 2.7557319223985893e-06;
+    sin_r = sin_r * r2 - 0.0001984126984126984;
+    sin_r = sin_r * r2 + 0.008333333333333333;
+    sin_r = sin_r * r2 - 0.16666666666666666;
+    sin_r = (sin_r * r2 + 1.0) * r;
+
+    var cos_r = fp(-1.5619206968586225e-16);
+    cos_r = cos_r * r2 + 4.779477332387385e-14;
+    cos_r = cos_r * r2 - 1.1470745597729725e-11;
+    cos_r = cos_r * r2 + 2.08767569878681e-09;
+    cos_r = cos_r * r2 - 2.755731922398589e-07;
+    cos_r = cos_r * r2 + 2.48015873015873e-05;
+    cos_r = cos_r * r2 - 0.001388888888888889;
+    cos_r = cos_r * r2 + 0.041666666666666664;
+    cos_r = cos_r * r2 - 0.5;
+    cos_r = cos_r * r2 + 1.0;
+
+    // k mod 4, without overflowing an integer for large values
+    let quadrant = u32(k - 4.0 * floor(k * 0.25));
+    switch quadrant {
+        case 0u {
+            return vec2(sin_r, cos_r);
+        }
+        case 1u {
+            return vec2(cos_r, -sin_r);
+        }
+        case 2u {
+            return vec2(-sin_r, -cos_r);
+        }
+        default {
+            return vec2(-cos_r, sin_r);
+        }
+    }
+}
+
+fn fp_atan2(y: fp, x: fp) -> fp {
+    let abs_x = abs(x);
+    let abs_y = abs(y);
+    let larger = max(abs_x, abs_y);
+    if larger == 0.0 {
+        return fp(0.0);
+    }
+
+    // atan of the smaller over the larger is in [0, pi / 4]
+    var t = min(abs_x, abs_y) / larger;
+    // atan(t) = 2 * atan(t / (1 + sqrt(1 + t^2))), applied twice brings t below tan(pi / 16)
+    t = t / (1.0 + sqrt(1.0 + t * t));
+    t = t / (1.0 + sqrt(1.0 + t * t));
+    let t2 = t * t;
+
+    var series = fp(-1.0 / 23.0);
+    series = series * t2 + 1.0 / 21.0;
+    series = series * t2 - 1.0 / 19.0;
+    series = series * t2 + 1.0 / 17.0;
+    series = series * t2 - 1.0 / 15.0;
+    series = series * t2 + 1.0 / 13.0;
+    series = series * t2 - 1.0 / 11.0;
+    series = series * t2 + 1.0 / 9.0;
+    series = series * t2 - 1.0 / 7.0;
+    series = series * t2 + 1.0 / 5.0;
+    series = series * t2 - 1.0 / 3.0;
+    series = series * t2 + 1.0;
+    var angle = 4.0 * t * series;
+
+    if abs_y > abs_x {
+        angle = HALF_PI - angle;
+    }
+    if x < 0.0 {
+        angle = PI - angle;
+    }
+    if y < 0.0 {
+        angle = -angle;
+    }
+
+    return angle;
+}
+
+fn fp_pow(x: fp, y: fp) -> fp {
+    // zero and negative bases are left to f32
+    if x <= 0.0 {
+        return fp(pow(f32(x), f32(y)));
+    }
+
+    return fp_exp(y * fp_log(x));
+}
+
+fn fp_log(x: fp) -> fp {
+    // zero, negative, subnormal and non-finite values are left to f32
+    if !(x >= MIN_NORMAL_F64 && x <= MAX_F64) {
+        return fp(log(f32(x)));
+    }
+
+    // x = m * 2^e, with m in [sqrt(2) / 2, sqrt(2)]
+    let bits = bitcast<u64>(x);
+    var e = fp(i32(u32(bits >> 52u)) - 1023);
+    let mantissa_mask = (u64(0x000fffffu) << 32u) | u64(0xffffffffu);
+    var m = bitcast<f64>((bits & mantissa_mask) | (u64(1023u) << 52u));
+    if m > SQRT_2 {
+        m *= fp(0.5);
+        e += fp(1.0);
+    }
+
+    // ln(m) = 2 * atanh(s), with |s| <= 0.172
+    let s = (m - 1.0) / (m + 1.0);
+    let s2 = s * s;
+
+    var series = fp(1.0 / 19.0);
+    series = series * s2 + 1.0 / 17.0;
+    series = series * s2 + 1.0 / 15.0;
+    series = series * s2 + 1.0 / 13.0;
+    series = series * s2 + 1.0 / 11.0;
+    series = series * s2 + 1.0 / 9.0;
+    series = series * s2 + 1.0 / 7.0;
+    series = series * s2 + 1.0 / 5.0;
+    series = series * s2 + 1.0 / 3.0;
+    series = series * s2 + 1.0;
+
+    return e * LN2_HI + (e * LN2_LO + 2.0 * s * series);
+}
+
+fn fp_exp(x: fp) -> fp {
+    // results outside of this range don't fit in a normal f64
+    let clamped = clamp(x, fp(-708.0), fp(709.0));
+
+    // x = r + k * ln(2), with |r| <= ln(2) / 2
+    let k = floor(clamped * INV_LN2 + 0.5);
+    let r = (clamped - k * LN2_HI) - k * LN2_LO;
+
+    // taylor series, the error is below f64 epsilon for |r| <= ln(2) / 2
+    var exp_r = fp(1.6059043836821613e-10);
+    exp_r = exp_r * r + 2.08767569878681e-09;
+    exp_r = exp_r * r + 2.505210838544172e-08;
+    exp_r = exp_r * r + 2.755731922398589e-07;
+    exp_r = exp_r * r + 2.7557319223985893e-06;
+    exp_r = exp_r * r + 2.48015873015873e-05;
+    exp_r = exp_r * r + 0.0001984126984126984;
+    exp_r = exp_r * r + 0.001388888888888889;
+    exp_r = exp_r * r + 0.008333333333333333;
+    exp_r = exp_r * r + 0.041666666666666664;
+    exp_r = exp_r * r + 0.16666666666666666;
+    exp_r = exp_r * r + 0.5;
+    exp_r = exp_r * r + 1.0;
+    exp_r = exp_r * r + 1.0;
+
+    // 2^k, built directly from the exponent bits
+    let scale = bitcast<f64>(u64(u32(i32(k) + 1023)) << 52u);
+    return exp_r * scale;
+}
+#else
 fn fp_sin(value: fp) -> fp {
     return fp(sin(f32(value)));
 }
@@ -454,4 +1717,228 @@ fn fp_log(x: fp) -> fp {
 
 fn fp_exp(x: fp) -> fp {
     return fp(exp(f32(x)));
-}
\ No newline at end of file
+}
+#endif
+
+fn fp_sinh(x: fp) -> fp {
+    return (fp_exp(x) - fp_exp(-x)) * 0.5;
+}
+
+fn fp_cosh(x: fp) -> fp {
+    return (fp_exp(x) + fp_exp(-x)) * 0.5;
+}
+
+#ifdef DOUBLE_SINGLE
+// Emulated f64 (df64), each value is a vec2f of two non-overlapping f32s whose sum is the value,
+// complex numbers are vec4f, with the real part in xy and the imaginary part in zw.
+// Based on "Extended-Precision Floating-Point Numbers for GPU Computation" by Andrew Thall
+
+// the same as fractal(), but with emulated f64,
+// only positive integer exponents are supported, the rest fall back to f32
+fn fractal_double_single(world_pos: vec2f, material: FractalMaterial, params: FractalParams) -> FractalResult {
+    let f = params.formula;
+    if f == FORMULA_CUSTOM || (params.exp_mode != EXP_2 && params.exp_mode != EXP_POS_INT) {
+        return fractal(params);
+    }
+
+    // the same as get_fractal_params, but only for z and c
+    let x = df_add(df_mul(vec2(world_pos.x, 0.0), encoded_material.scale), encoded_material.offset.xy);
+    let y = df_add(df_mul(vec2(world_pos.y, 0.0), encoded_material.scale), encoded_material.offset.zw);
+
+    var param_array: array<vec2f, PARAM_ARRAY_SIZE>;
+    param_array[Z_R_VALUE_INDEX] = encoded_material.initial_z.real_value;
+    param_array[Z_I_VALUE_INDEX] = encoded_material.initial_z.imag_value;
+    param_array[C_R_VALUE_INDEX] = encoded_material.c.real_value;
+    param_array[C_I_VALUE_INDEX] = encoded_material.c.imag_value;
+    param_array[PIXEL_X_INDEX] = x;
+    param_array[PIXEL_Y_INDEX] = y;
+    // the other parameters calculated from the pixel only get f32 precision
+    var derived = derived_pixel_params(x.x + x.y, y.x + y.y, material);
+    for (var i: u32 = 0; i < DERIVED_PIXEL_PARAM_COUNT; i += 1u) {
+        param_array[PIXEL_RADIUS_INDEX + i] = vec2(derived[i], 0.0);
+    }
+
+    var z = vec4(param_array[material.initial_z.real_index], param_array[material.initial_z.imag_index]);
+    let c = vec4(param_array[material.c.real_index], param_array[material.c.imag_index]);
+    let p = u32(params.p.x);
+    var out: FractalResult;
+    var trap_distance = MAX_TRAP_DISTANCE;
+    var derivative = vec2<fp>(params.derivative_z, 0.0);
+    var average: OrbitAverage;
+
+    let r_squared = params.escape_radius * params.escape_radius;
+
+    var i: u32 = 0;
+
+    // same dummy iteration as in fractal()
+    if all(z == vec4(0.0)) {
+        z = c;
+        trap_distance = distance_to_trap(dfc_to_f32(z), params);
+        derivative = vec2<fp>(params.derivative_c, 0.0);
+
+        if dfc_norm_squared(z) > r_squared {
+            out.exit_iteration = i;
+            out.final_z = dfc_to_f32(z);
+            out.trap_distance = trap_distance;
+            out.orbit_average = average;
+            out.derivative = length(derivative);
+            return out;
+        }
+        if params.iteration_count > 0 {
+            i += 1u;
+        }
+    }
+
+    for (; i < params.iteration_count; i += 1u) {
+        let powered = dfc_pow_pos_int(dfc_formula_pre(z, f), p);
+        derivative = next_derivative(derivative, dfc_to_f32(z), dfc_to_f32(powered), params);
+        z = dfc_add(dfc_formula_post(powered, f), c);
+        trap_distance = min(trap_distance, distance_to_trap(dfc_to_f32(z), params));
+        add_to_orbit_average(&average, dfc_to_f32(z), dfc_to_f32(c), params);
+
+        if dfc_norm_squared(z) > r_squared {
+            break;
+        }
+    }
+
+    out.exit_iteration = i;
+    out.final_z = dfc_to_f32(z);
+    out.trap_distance = trap_distance;
+    out.orbit_average = average;
+    out.derivative = length(derivative);
+    return out;
+}
+
+fn dfc_formula_pre(z: vec4f, formula: u32) -> vec4f {
+    switch formula {
+        case FORMULA_BURNING_SHIP {
+            return vec4(df_abs(z.xy), df_abs(z.zw));
+        }
+        case FORMULA_TRICORN {
+            return vec4(z.xy, -z.zw);
+        }
+        default {
+            return z;
+        }
+    }
+}
+
+fn dfc_formula_post(z: vec4f, formula: u32) -> vec4f {
+    switch formula {
+        case FORMULA_CELTIC {
+            return vec4(df_abs(z.xy), z.zw);
+        }
+        case FORMULA_BUFFALO {
+            return vec4(df_abs(z.xy), df_abs(z.zw));
+        }
+        default {
+            return z;
+        }
+    }
+}
+
+fn dfc_pow_pos_int(z: vec4f, p: u32) -> vec4f {
+    if p == 2u {
+        return dfc_square(z);
+    }
+
+    var x = z;
+    var n = p;
+
+    var y = vec4(1.0, 0.0, 0.0, 0.0);
+    while n > 1 {
+        if n % 2 == 1 {
+            y = dfc_mult(x, y);
+            n -= 1u;
+        }
+        x = dfc_square(x);
+        n /= 2u;
+    }
+
+    return dfc_mult(x, y);
+}
+
+fn dfc_add(a: vec4f, b: vec4f) -> vec4f {
+    return vec4(df_add(a.xy, b.xy), df_add(a.zw, b.zw));
+}
+
+fn dfc_mult(a: vec4f, b: vec4f) -> vec4f {
+    let re = df_sub(df_mul(a.xy, b.xy), df_mul(a.zw, b.zw));
+    let im = df_add(df_mul(a.xy, b.zw), df_mul(a.zw, b.xy));
+    return vec4(re, im);
+}
+
+fn dfc_square(z: vec4f) -> vec4f {
+    let re = df_sub(df_mul(z.xy, z.xy), df_mul(z.zw, z.zw));
+    // multiplying by 2 is exact
+    let im = 2.0 * df_mul(z.xy, z.zw);
+    return vec4(re, im);
+}
+
+fn dfc_norm_squared(z: vec4f) -> f32 {
+    // only used for the escape check, so f32 is good enough
+    return z.x * z.x + z.z * z.z;
+}
+
+fn dfc_to_f32(z: vec4f) -> vec2f {
+    return vec2(z.x + z.y, z.z + z.w);
+}
+
+fn df_abs(a: vec2f) -> vec2f {
+    return select(a, -a, a.x < 0.0);
+}
+
+fn df_add(a: vec2f, b: vec2f) -> vec2f {
+    var s = two_sum(a.x, b.x);
+    let t = two_sum(a.y, b.y);
+    s.y += t.x;
+    s = quick_two_sum(s.x, s.y);
+    s.y += t.y;
+    return quick_two_sum(s.x, s.y);
+}
+
+fn df_sub(a: vec2f, b: vec2f) -> vec2f {
+    return df_add(a, -b);
+}
+
+fn df_mul(a: vec2f, b: vec2f) -> vec2f {
+    var p = two_prod(a.x, b.x);
+    p.y += a.x * b.y + a.y * b.x;
+    return quick_two_sum(p.x, p.y);
+}
+
+// a + b as an exact sum of two f32s
+fn two_sum(a: f32, b: f32) -> vec2f {
+    let s = a + b;
+    let v = s - a;
+    let e = (a - (s - v)) + (b - v);
+    return vec2(s, e);
+}
+
+// the same as two_sum, but requires |a| >= |b|
+fn quick_two_sum(a: f32, b: f32) -> vec2f {
+    let s = a + b;
+    let e = b - (s - a);
+    return vec2(s, e);
+}
+
+// a * b as an exact sum of two f32s
+fn two_prod(a: f32, b: f32) -> vec2f {
+    let p = a * b;
+    let a_split = split(a);
+    let b_split = split(b);
+    let e = ((a_split.x * b_split.x - p) + a_split.x * b_split.y + a_split.y * b_split.x)
+        + a_split.y * b_split.y;
+    return vec2(p, e);
+}
+
+// splits an f32 into two halves with 12 bits of mantissa each,
+// so that multiplying them is exact
+fn split(a: f32) -> vec2f {
+    const SPLITTER = 4097.0; // 2^12 + 1
+    let t = SPLITTER * a;
+    let hi = t - (t - a);
+    let lo = a - hi;
+    return vec2(hi, lo);
+}
+#endif
@@ -0,0 +1,95 @@
+// builds the histogram of the values written by the fractal shader and its cumulative distribution,
+// which the fractal shader then maps the values through to color them
+
+const HISTOGRAM_BIN_COUNT: u32 = 4096;
+const CDF_LENGTH: u32 = 4097;
+const CDF_WORKGROUP_SIZE: u32 = 256;
+const BINS_PER_INVOCATION: u32 = HISTOGRAM_BIN_COUNT / CDF_WORKGROUP_SIZE;
+
+struct HistogramParams {
+    width: u32,
+    height: u32,
+    iteration_count: u32,
+}
+
+struct Histogram {
+    bins: array<atomic<u32>, HISTOGRAM_BIN_COUNT>,
+    // fraction of the values below the start of each bin, the last element is always 1
+    cdf: array<f32, CDF_LENGTH>,
+}
+
+@group(0) @binding(0) var<uniform> params: HistogramParams;
+// one value per pixel, negative for pixels that aren't colored by the histogram
+@group(0) @binding(1) var<storage, read> values: array<f32>;
+@group(0) @binding(2) var<storage, read_write> histogram: Histogram;
+
+var<workgroup> partial_sums: array<u32, CDF_WORKGROUP_SIZE>;
+
+@compute @workgroup_size(256)
+fn clear_histogram(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x < HISTOGRAM_BIN_COUNT {
+        atomicStore(&histogram.bins[id.x], 0u);
+    }
+}
+
+@compute @workgroup_size(16, 16)
+fn accumulate_histogram(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.width || id.y >= params.height {
+        return;
+    }
+
+    let index = id.y * params.width + id.x;
+    if index >= arrayLength(&values) {
+        return;
+    }
+
+    let value = values[index];
+    if value < 0.0 {
+        return;
+    }
+
+    let bin = min(u32(histogram_position(value, params.iteration_count)), HISTOGRAM_BIN_COUNT - 1u);
+    atomicAdd(&histogram.bins[bin], 1u);
+}
+
+// a single workgroup, every invocation sums a run of bins, then the sums are scanned
+@compute @workgroup_size(256)
+fn compute_cdf(@builtin(local_invocation_index) index: u32) {
+    let start = index * BINS_PER_INVOCATION;
+
+    var sum = 0u;
+    for (var i = 0u; i < BINS_PER_INVOCATION; i += 1u) {
+        sum += atomicLoad(&histogram.bins[start + i]);
+    }
+    partial_sums[index] = sum;
+    workgroupBarrier();
+
+    for (var offset = 1u; offset < CDF_WORKGROUP_SIZE; offset *= 2u) {
+        var scanned = partial_sums[index];
+        if index >= offset {
+            scanned += partial_sums[index - offset];
+        }
+        workgroupBarrier();
+        partial_sums[index] = scanned;
+        workgroupBarrier();
+    }
+
+    let total = f32(max(partial_sums[CDF_WORKGROUP_SIZE - 1u], 1u));
+    var below = partial_sums[index] - sum;
+    for (var i = 0u; i < BINS_PER_INVOCATION; i += 1u) {
+        histogram.cdf[start + i] = f32(below) / total;
+        below += atomicLoad(&histogram.bins[start + i]);
+    }
+
+    if index == CDF_WORKGROUP_SIZE - 1u {
+        histogram.cdf[HISTOGRAM_BIN_COUNT] = 1.0;
+    }
+}
+
+// position of a smoothed iteration count in the histogram, measured in bins,
+// the bins get wider logarithmically since most pixels escape after only a few iterations
+//
+// the same as in the fractal shader
+fn histogram_position(value: f32, iteration_count: u32) -> f32 {
+    return log2(1.0 + value) / log2(1.0 + f32(iteration_count)) * f32(HISTOGRAM_BIN_COUNT);
+}
@@ -0,0 +1,499 @@
+//! Named bookmarks of interesting views, with thumbnails.
+//!
+//! Every bookmark is saved as a small PNG in the [data directory](crate::utils::data_dir),
+//! with the fractal in its [`metadata`](crate::fractal::metadata) and the name in the standard
+//! `Title` chunk, so a bookmark can also be opened like an exported image.
+
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    input::mouse::AccumulatedMouseScroll,
+    prelude::*,
+    render::{
+        gpu_readback::ReadbackComplete,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        renderer::RenderDevice,
+        view::RenderLayers,
+    },
+};
+
+use crate::{
+    export::{is_ready_to_export, spawn_offscreen_camera, OFFSCREEN_PIXEL_SIZE, SETTLE_FRAMES},
+    fractal::{
+        material::FractalShaderSource,
+        metadata::{fractal_metadata, read_png_metadata, MetadataError, METADATA_KEYWORD},
+        parameters::CurrentParameterInputs,
+        perturbation::ReferenceOrbit,
+        Fractal, OffscreenFractal,
+    },
+    keyframes::interpolate_view,
+    ui::UiSystemSet,
+    utils::{data_dir, show_error_dialog},
+};
+
+/// Size of the thumbnails in pixels
+pub const THUMBNAIL_SIZE: UVec2 = UVec2::new(160, 90);
+/// Render layer of the fractals the thumbnails are rendered from, which the viewport doesn't see
+pub const THUMBNAIL_LAYER: usize = 1;
+
+// the standard keyword for the title of an image
+const TITLE_KEYWORD: &str = "Title";
+// seconds an animated jump to a bookmark takes
+const JUMP_DURATION: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BookmarksPlugin;
+
+impl Plugin for BookmarksPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AddBookmark>()
+            .add_event::<RemoveBookmark>()
+            .add_event::<JumpToBookmark>()
+            .init_resource::<Bookmarks>()
+            .add_systems(Startup, load_bookmarks)
+            .add_systems(
+                Update,
+                (
+                    add_bookmark,
+                    remove_bookmark,
+                    jump_to_bookmark,
+                    animate_jump,
+                    render_next_thumbnail,
+                )
+                    .chain()
+                    .after(UiSystemSet),
+            );
+    }
+}
+
+/// Bookmarks the current view under the given name
+#[derive(Debug, Clone, Event)]
+pub struct AddBookmark {
+    pub name: String,
+}
+
+/// Removes the bookmark with the given index, along with its file
+#[derive(Debug, Clone, Copy, Event)]
+pub struct RemoveBookmark {
+    pub index: usize,
+}
+
+/// Replaces the fractal with the one in the bookmark with the given index
+#[derive(Debug, Clone, Copy, Event)]
+pub struct JumpToBookmark {
+    pub index: usize,
+    /// Smoothly zoom and pan to the bookmark instead of jumping there at once
+    pub animated: bool,
+}
+
+/// All bookmarks, oldest first
+#[derive(Debug, Clone, Default, Resource, Deref)]
+pub struct Bookmarks {
+    #[deref]
+    list: Vec<Bookmark>,
+    next_id: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bookmark {
+    pub name: String,
+    pub fractal: Fractal,
+    /// `None` while the thumbnail is being rendered
+    pub thumbnail: Option<Handle<Image>>,
+    // identifies the bookmark while its thumbnail is rendered
+    id: u64,
+    // the file the bookmark is saved to, `None` if there is no data directory
+    path: Option<PathBuf>,
+}
+
+impl Bookmarks {
+    fn push(&mut self, bookmark: Bookmark) {
+        self.next_id = self.next_id.max(bookmark.id + 1);
+        self.list.push(bookmark);
+    }
+}
+
+/// The camera rendering the thumbnail of a bookmark
+#[derive(Debug, Clone, Component)]
+pub struct ThumbnailCamera {
+    id: u64,
+    frames: u32,
+}
+
+/// The thumbnail being rendered, exists until it's read back
+///
+/// The bookmarked fractal is rendered from an [`OffscreenFractal`] of its own
+/// on the [`THUMBNAIL_LAYER`], so the fractal the user sees is left alone.
+#[derive(Debug, Clone, Resource)]
+pub struct ThumbnailRender {
+    id: u64,
+    fractal: Entity,
+    camera: Option<Entity>,
+}
+
+/// The view moving to a bookmark, exists until it gets there
+#[derive(Debug, Clone, Resource)]
+pub struct BookmarkJump {
+    from: Fractal,
+    to: Fractal,
+    start_time: f64,
+}
+
+fn bookmarks_dir() -> Option<PathBuf> {
+    Some(data_dir()?.join("bookmarks"))
+}
+
+// a file name that isn't taken yet, ordered by creation time
+fn new_bookmark_path(dir: &Path) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis());
+
+    (millis..)
+        .map(|name| dir.join(format!("{name}.png")))
+        .find(|path| !path.exists())
+        .expect("there should be a free file name")
+}
+
+pub fn load_bookmarks(mut bookmarks: ResMut<Bookmarks>, mut images: ResMut<Assets<Image>>) {
+    let Some(dir) = bookmarks_dir() else {
+        warn!("There is no data directory, bookmarks won't be saved");
+        return;
+    };
+    // the directory doesn't exist until the first bookmark is added
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return;
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        match read_bookmark(&path) {
+            Ok((name, fractal, thumbnail)) => {
+                let id = bookmarks.next_id;
+                bookmarks.push(Bookmark {
+                    name,
+                    fractal,
+                    thumbnail: Some(images.add(thumbnail)),
+                    id,
+                    path: Some(path),
+                });
+            }
+            Err(e) => warn!(?path, "Failed to load bookmark: {e}"),
+        }
+    }
+    info!(count = bookmarks.len(), ?dir, "Loaded bookmarks");
+}
+
+// the name, the fractal and the thumbnail
+fn read_bookmark(path: &Path) -> Result<(String, Fractal, Image), MetadataError> {
+    let file = BufReader::new(File::open(path)?);
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+
+    let fractal = read_png_metadata(reader.info())?;
+    let name = reader
+        .info()
+        .utf8_text
+        .iter()
+        .find(|chunk| chunk.keyword == TITLE_KEYWORD)
+        .and_then(|chunk| chunk.get_text().ok())
+        .unwrap_or_else(|| {
+            path.file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into()
+        });
+
+    let mut data = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let output = reader.next_frame(&mut data)?;
+    data.truncate(output.buffer_size());
+
+    let pixels = match output.color_type {
+        png::ColorType::Rgba => data,
+        png::ColorType::Rgb => data
+            .chunks_exact(3)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], u8::MAX])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => data
+            .chunks_exact(2)
+            .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+            .collect(),
+        // indexed colors are expanded to rgb
+        png::ColorType::Grayscale | png::ColorType::Indexed => data
+            .iter()
+            .flat_map(|&value| [value, value, value, u8::MAX])
+            .collect(),
+    };
+    let size = UVec2::new(output.width, output.height);
+
+    Ok((name, fractal, thumbnail_image(size, pixels)))
+}
+
+fn write_bookmark(
+    path: &Path,
+    bookmark: &Bookmark,
+    pixels: &[u8],
+) -> Result<(), png::EncodingError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, THUMBNAIL_SIZE.x, THUMBNAIL_SIZE.y);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.add_itxt_chunk(TITLE_KEYWORD.to_string(), bookmark.name.clone())?;
+    encoder.add_text_chunk(
+        METADATA_KEYWORD.to_string(),
+        fractal_metadata(&bookmark.fractal),
+    )?;
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()
+}
+
+fn thumbnail_image(size: UVec2, pixels: Vec<u8>) -> Image {
+    Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        pixels,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+pub fn add_bookmark(
+    mut events: EventReader<AddBookmark>,
+    mut bookmarks: ResMut<Bookmarks>,
+    fractals: Query<&Fractal, Without<OffscreenFractal>>,
+    inputs: CurrentParameterInputs,
+) {
+    let Ok(fractal) = fractals.get_single() else {
+        return;
+    };
+    // animated and cursor parameters are kept at what they are now
+    let fractal = fractal.frozen(inputs.get());
+
+    for AddBookmark { name } in events.read() {
+        // bookmarks are still usable without a data directory, just not saved
+        let path = bookmarks_dir().map(|dir| new_bookmark_path(&dir));
+        let id = bookmarks.next_id;
+        info!(name, ?path, "Adding bookmark");
+        bookmarks.push(Bookmark {
+            name: name.clone(),
+            fractal: fractal.clone(),
+            thumbnail: None,
+            id,
+            path,
+        });
+    }
+}
+
+// renders the thumbnails of new bookmarks one at a time
+pub fn render_next_thumbnail(
+    mut commands: Commands,
+    render: Option<ResMut<ThumbnailRender>>,
+    bookmarks: Res<Bookmarks>,
+    mut images: ResMut<Assets<Image>>,
+    fractals: Query<(&Fractal, &ReferenceOrbit), With<OffscreenFractal>>,
+    shader_source: Res<FractalShaderSource>,
+) {
+    let Some(mut render) = render else {
+        let Some(bookmark) = bookmarks.iter().find(|b| b.thumbnail.is_none()) else {
+            return;
+        };
+        debug!(name = bookmark.name, "Rendering thumbnail");
+        // the custom formula is the one compiled for the viewport,
+        // which the bookmark was just taken from
+        let fractal = commands
+            .spawn((
+                bookmark.fractal.clone(),
+                OffscreenFractal,
+                RenderLayers::layer(THUMBNAIL_LAYER),
+            ))
+            .id();
+        commands.insert_resource(ThumbnailRender {
+            id: bookmark.id,
+            fractal,
+            camera: None,
+        });
+        return;
+    };
+
+    if render.camera.is_some() {
+        return;
+    }
+    // the new fractal needs its own reference orbit first
+    let Ok((fractal, orbit)) = fractals.get(render.fractal) else {
+        return;
+    };
+    if !is_ready_to_export(&shader_source, fractal, orbit) {
+        return;
+    }
+
+    let camera = spawn_offscreen_camera(
+        &mut commands,
+        &mut images,
+        THUMBNAIL_SIZE,
+        URect::from_corners(UVec2::ZERO, THUMBNAIL_SIZE),
+    )
+    .insert((
+        ThumbnailCamera {
+            id: render.id,
+            frames: 0,
+        },
+        RenderLayers::layer(THUMBNAIL_LAYER),
+    ))
+    .observe(receive_thumbnail)
+    .id();
+    render.camera = Some(camera);
+}
+
+pub fn receive_thumbnail(
+    trigger: Trigger<ReadbackComplete>,
+    mut commands: Commands,
+    mut cameras: Query<&mut ThumbnailCamera>,
+    render: Option<Res<ThumbnailRender>>,
+    mut bookmarks: ResMut<Bookmarks>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Ok(mut camera) = cameras.get_mut(trigger.entity()) else {
+        return;
+    };
+    camera.frames += 1;
+    if camera.frames < SETTLE_FRAMES {
+        return;
+    }
+    commands.entity(trigger.entity()).despawn();
+
+    if let Some(render) = render {
+        commands.entity(render.fractal).despawn_recursive();
+        commands.remove_resource::<ThumbnailRender>();
+    }
+
+    // the bookmark could have been removed while its thumbnail was rendered
+    let Some(bookmark) = bookmarks.list.iter_mut().find(|b| b.id == camera.id) else {
+        return;
+    };
+
+    let row_size = THUMBNAIL_SIZE.x as usize * OFFSCREEN_PIXEL_SIZE;
+    let pixels: Vec<u8> = trigger
+        .event()
+        .0
+        .chunks(RenderDevice::align_copy_bytes_per_row(row_size))
+        .flat_map(|row| &row[..row_size])
+        .copied()
+        .collect();
+    let Some(path) = bookmark.path.as_ref() else {
+        bookmark.thumbnail = Some(images.add(thumbnail_image(THUMBNAIL_SIZE, pixels)));
+        return;
+    };
+    if let Err(e) = write_bookmark(path, bookmark, &pixels) {
+        show_error_dialog(
+            "Failed to save bookmark",
+            format!("Failed to save {}: {e}", path.display()),
+        );
+    }
+    bookmark.thumbnail = Some(images.add(thumbnail_image(THUMBNAIL_SIZE, pixels)));
+}
+
+pub fn remove_bookmark(mut events: EventReader<RemoveBookmark>, mut bookmarks: ResMut<Bookmarks>) {
+    for &RemoveBookmark { index } in events.read() {
+        if index >= bookmarks.len() {
+            continue;
+        }
+
+        let bookmark = bookmarks.list.remove(index);
+        info!(name = bookmark.name, "Removing bookmark");
+        // the file doesn't exist until the thumbnail is rendered
+        let Some(path) = bookmark.path.filter(|path| path.exists()) else {
+            continue;
+        };
+        if let Err(e) = fs::remove_file(&path) {
+            show_error_dialog(
+                "Failed to remove bookmark",
+                format!("Failed to remove {}: {e}", path.display()),
+            );
+        }
+    }
+}
+
+pub fn jump_to_bookmark(
+    mut commands: Commands,
+    mut events: EventReader<JumpToBookmark>,
+    bookmarks: Res<Bookmarks>,
+    mut fractals: Query<&mut Fractal, Without<OffscreenFractal>>,
+    time: Res<Time<Real>>,
+) {
+    let Some(&JumpToBookmark { index, animated }) = events.read().last() else {
+        return;
+    };
+    let Some(bookmark) = bookmarks.get(index) else {
+        return;
+    };
+    let Ok(mut fractal) = fractals.get_single_mut() else {
+        return;
+    };
+
+    if animated {
+        commands.insert_resource(BookmarkJump {
+            from: fractal.clone(),
+            to: bookmark.fractal.clone(),
+            start_time: time.elapsed_secs_f64(),
+        });
+    } else {
+        commands.remove_resource::<BookmarkJump>();
+        *fractal = bookmark.fractal.clone();
+    }
+}
+
+pub fn animate_jump(
+    mut commands: Commands,
+    jump: Option<Res<BookmarkJump>>,
+    mut fractals: Query<&mut Fractal, Without<OffscreenFractal>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mouse_wheel: Res<AccumulatedMouseScroll>,
+    time: Res<Time<Real>>,
+) {
+    let Some(jump) = jump else {
+        return;
+    };
+    let Ok(mut fractal) = fractals.get_single_mut() else {
+        return;
+    };
+
+    // the user taking over the view stops the jump where it is
+    if mouse_buttons.get_just_pressed().next().is_some() || mouse_wheel.delta != Vec2::ZERO {
+        commands.remove_resource::<BookmarkJump>();
+        return;
+    }
+
+    let t = (time.elapsed_secs_f64() - jump.start_time) / JUMP_DURATION;
+    if t >= 1.0 {
+        *fractal = jump.to.clone();
+        commands.remove_resource::<BookmarkJump>();
+        return;
+    }
+
+    let eased = t * t * (3.0 - 2.0 * t);
+    let mut view = jump.to.clone();
+    (view.scale, view.offset) = interpolate_view(&jump.from, &jump.to, eased);
+    *fractal = view;
+}
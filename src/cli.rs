@@ -0,0 +1,400 @@
+//! Command line arguments.
+//!
+//! Fractality opens a window by default. The `render` command renders the fractal
+//! to a PNG file without opening one, see [`headless`](crate::headless).
+
+use std::{
+    error::Error,
+    ffi::OsString,
+    fmt::{self, Display},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    export::{ExportSettings, MAX_EXPORT_SIZE, MAX_SUPERSAMPLING},
+    fractal::{
+        add_fractal_to_world,
+        parameters::ParameterTime,
+        precise::{parse_decimal, PreciseVec2},
+        preset::{load_preset_file, PresetError},
+        Fractal, OffscreenFractal,
+    },
+    session::restore_session,
+};
+
+/// Shown for `--help` and after invalid arguments
+pub const USAGE: &str = "\
+Usage: fractality [OPTIONS]
+       fractality render --output <FILE> [OPTIONS] [RENDER OPTIONS]
+
+Commands:
+  render                 Render the fractal to a PNG file without opening a window
+
+Options:
+  --preset <FILE>        Start with the fractal in a .ron or .toml preset
+  --center <X,Y>         Center of the view
+  --scale <SCALE>        Distance from the center to the edge of the longer side of the view
+  --iterations <COUNT>   Number of iterations
+  --time <SECONDS>       Time animated parameters start at
+  --fresh                Start with the default fractal instead of restoring the last session
+  -h, --help             Print this message
+
+Render options:
+  -o, --output <FILE>    PNG file to render to
+  --width <PIXELS>       Width of the image, 3840 by default
+  --height <PIXELS>      Height of the image, 2160 by default
+  --supersampling <N>    Samples per pixel along each axis, from 1 to 4, 2 by default";
+
+/// The arguments Fractality was started with
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CliArgs {
+    pub command: Command,
+    pub fractal: FractalArgs,
+    /// Start with the default fractal instead of restoring the last session
+    pub fresh: bool,
+    pub help: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Command {
+    /// Open the window
+    #[default]
+    Run,
+    /// Render the fractal to a PNG file and exit
+    Render {
+        path: PathBuf,
+        settings: ExportSettings,
+    },
+}
+
+/// Changes to the fractal Fractality starts with
+#[derive(Debug, Clone, Default, PartialEq, Resource)]
+pub struct FractalArgs {
+    /// Replaces the fractal before the other changes are made
+    pub preset: Option<Fractal>,
+    pub center: Option<PreciseVec2>,
+    pub scale: Option<f64>,
+    pub iterations: Option<u32>,
+    /// Time animated parameters start at, see [`ParameterTime`]
+    pub time: Option<f64>,
+}
+
+impl FractalArgs {
+    pub fn apply(&self, fractal: &mut Fractal) {
+        if let Some(preset) = &self.preset {
+            *fractal = preset.clone();
+        }
+        if let Some(center) = &self.center {
+            fractal.offset = center.clone();
+        }
+        if let Some(scale) = self.scale {
+            fractal.scale = scale;
+        }
+        if let Some(iterations) = self.iterations {
+            fractal.iteration_count = iterations;
+        }
+    }
+}
+
+impl CliArgs {
+    /// Parses the arguments, not including the name of the executable
+    ///
+    /// The preset is read from its file right away, so that a missing or invalid preset
+    /// is reported before anything starts. Paths are taken as they are, every other value
+    /// has to be valid unicode.
+    pub fn parse(args: impl IntoIterator<Item = OsString>) -> Result<Self, CliError> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter().peekable();
+
+        let render = args.next_if(|arg| arg == "render").is_some();
+        let mut output = None;
+        let mut settings = ExportSettings::default();
+
+        while let Some(arg) = args.next() {
+            let arg = arg
+                .into_string()
+                .map_err(|arg| CliError::UnknownArgument(arg.to_string_lossy().into_owned()))?;
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| CliError::MissingValue(arg.clone()))
+            };
+            let text = |value: OsString| {
+                value.into_string().map_err(|value| {
+                    CliError::InvalidValue(arg.clone(), value.to_string_lossy().into_owned())
+                })
+            };
+
+            match arg.as_str() {
+                "--preset" => {
+                    let path = PathBuf::from(value()?);
+                    let preset = load_preset_file(&path).map_err(|e| CliError::Preset(path, e))?;
+                    parsed.fractal.preset = Some(preset);
+                }
+                "--center" => parsed.fractal.center = Some(parse_center(&arg, text(value()?)?)?),
+                "--scale" => {
+                    let value = text(value()?)?;
+                    let scale: f64 = parse_value(&arg, value.clone())?;
+                    if !(scale.is_finite() && scale > 0.0) {
+                        return Err(CliError::InvalidValue(arg, value));
+                    }
+                    parsed.fractal.scale = Some(scale);
+                }
+                "--iterations" => {
+                    parsed.fractal.iterations = Some(parse_value(&arg, text(value()?)?)?);
+                }
+                "--time" => {
+                    let value = text(value()?)?;
+                    let time: f64 = parse_value(&arg, value.clone())?;
+                    if !time.is_finite() {
+                        return Err(CliError::InvalidValue(arg, value));
+                    }
+                    parsed.fractal.time = Some(time);
+                }
+                "--fresh" => parsed.fresh = true,
+                "-h" | "--help" => parsed.help = true,
+                "-o" | "--output" if render => output = Some(PathBuf::from(value()?)),
+                "--width" if render => {
+                    settings.width = parse_in_range(&arg, text(value()?)?, 1..=MAX_EXPORT_SIZE)?;
+                }
+                "--height" if render => {
+                    settings.height = parse_in_range(&arg, text(value()?)?, 1..=MAX_EXPORT_SIZE)?;
+                }
+                "--supersampling" if render => {
+                    settings.supersampling =
+                        parse_in_range(&arg, text(value()?)?, 1..=MAX_SUPERSAMPLING)?;
+                }
+                _ => return Err(CliError::UnknownArgument(arg)),
+            }
+        }
+
+        if render {
+            // `--help` doesn't need the rest of the arguments
+            let path = match output {
+                Some(path) => path,
+                None if parsed.help => PathBuf::new(),
+                None => return Err(CliError::MissingOutput),
+            };
+            parsed.command = Command::Render { path, settings };
+        }
+
+        Ok(parsed)
+    }
+}
+
+fn parse_value<T: FromStr>(arg: &str, value: String) -> Result<T, CliError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| CliError::InvalidValue(arg.to_string(), value))
+}
+
+fn parse_in_range(
+    arg: &str,
+    value: String,
+    range: std::ops::RangeInclusive<u32>,
+) -> Result<u32, CliError> {
+    let parsed = parse_value(arg, value.clone())?;
+    if !range.contains(&parsed) {
+        return Err(CliError::InvalidValue(arg.to_string(), value));
+    }
+    Ok(parsed)
+}
+
+// the components are parsed as decimals, so deep zoom centers keep all of their digits
+fn parse_center(arg: &str, value: String) -> Result<PreciseVec2, CliError> {
+    let invalid = || CliError::InvalidValue(arg.to_string(), value.clone());
+    let (x, y) = value.split_once(',').ok_or_else(invalid)?;
+
+    Ok(PreciseVec2 {
+        x: parse_decimal(x.trim()).map_err(|_| invalid())?,
+        y: parse_decimal(y.trim()).map_err(|_| invalid())?,
+    })
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    UnknownArgument(String),
+    /// The argument needs a value, but it was the last one
+    MissingValue(String),
+    /// The argument and its value
+    InvalidValue(String, String),
+    /// `render` was used without `--output`
+    MissingOutput,
+    Preset(PathBuf, PresetError),
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::UnknownArgument(arg) => write!(f, "unknown argument '{arg}'"),
+            CliError::MissingValue(arg) => write!(f, "'{arg}' needs a value"),
+            CliError::InvalidValue(arg, value) => {
+                write!(f, "invalid value '{value}' for '{arg}'")
+            }
+            CliError::MissingOutput => {
+                write!(f, "'render' needs an output file, set with '--output'")
+            }
+            CliError::Preset(path, e) => write!(f, "failed to open {}: {e}", path.display()),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct CliPlugin {
+    pub fractal: FractalArgs,
+}
+
+impl Plugin for CliPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.fractal.clone()).add_systems(
+            Startup,
+            apply_fractal_args
+                .after(add_fractal_to_world)
+                .after(restore_session),
+        );
+    }
+}
+
+// the arguments take priority over the restored session
+pub fn apply_fractal_args(
+    args: Res<FractalArgs>,
+    mut fractals: Query<&mut Fractal, Without<OffscreenFractal>>,
+    mut parameter_time: ResMut<ParameterTime>,
+) {
+    for mut fractal in fractals.iter_mut() {
+        args.apply(&mut fractal);
+    }
+    if let Some(time) = args.time {
+        parameter_time.seconds = time;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliArgs, CliError> {
+        CliArgs::parse(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn unknown_arguments_are_errors() {
+        assert!(matches!(
+            parse(&["--zoom", "2"]),
+            Err(CliError::UnknownArgument(arg)) if arg == "--zoom"
+        ));
+        // render options only exist for the render command
+        assert!(matches!(
+            parse(&["--width", "100"]),
+            Err(CliError::UnknownArgument(arg)) if arg == "--width"
+        ));
+    }
+
+    #[test]
+    fn missing_values_are_errors() {
+        assert!(matches!(
+            parse(&["--scale"]),
+            Err(CliError::MissingValue(arg)) if arg == "--scale"
+        ));
+        assert!(matches!(
+            parse(&["render", "-o"]),
+            Err(CliError::MissingValue(arg)) if arg == "-o"
+        ));
+    }
+
+    #[test]
+    fn sizes_have_to_be_in_range() {
+        let too_large = (MAX_EXPORT_SIZE + 1).to_string();
+        for width in ["0", too_large.as_str(), "-1", "wide"] {
+            assert!(
+                matches!(
+                    parse(&["render", "-o", "out.png", "--width", width]),
+                    Err(CliError::InvalidValue(arg, value)) if arg == "--width" && value == width
+                ),
+                "{width}"
+            );
+        }
+
+        let largest = MAX_EXPORT_SIZE.to_string();
+        let args = parse(&[
+            "render", "-o", "out.png", "--width", "1", "--height", &largest,
+        ]);
+        let Ok(CliArgs {
+            command: Command::Render { settings, .. },
+            ..
+        }) = args
+        else {
+            panic!("{args:?}");
+        };
+        assert_eq!((settings.width, settings.height), (1, MAX_EXPORT_SIZE));
+    }
+
+    #[test]
+    fn render_needs_an_output() {
+        assert!(matches!(
+            parse(&["render", "--width", "100"]),
+            Err(CliError::MissingOutput)
+        ));
+
+        let args = parse(&["render", "--output", "out.png"]).unwrap();
+        assert_eq!(
+            args.command,
+            Command::Render {
+                path: PathBuf::from("out.png"),
+                settings: ExportSettings::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn render_help_needs_no_output() {
+        let args = parse(&["render", "--help"]).unwrap();
+        assert!(args.help);
+        assert!(matches!(args.command, Command::Render { .. }));
+    }
+
+    #[test]
+    fn centers_keep_all_digits() {
+        let x = "-1.74902630720926325417563012345678901234";
+        let y = "0.00000000000000000000000000000000000123456789";
+        let args = parse(&["--center", &format!("{x}, {y}")]).unwrap();
+
+        let center = args.fractal.center.unwrap();
+        assert_eq!(center.x, parse_decimal(x).unwrap());
+        assert_eq!(center.y, parse_decimal(y).unwrap());
+        // more precise than the closest f64
+        let rounded = center.x.to_f64().value().to_string();
+        assert_ne!(center.x, parse_decimal(&rounded).unwrap());
+
+        assert!(matches!(
+            parse(&["--center", "0.5"]),
+            Err(CliError::InvalidValue(arg, _)) if arg == "--center"
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_paths_can_be_invalid_unicode() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let invalid = || OsString::from_vec(vec![b'o', 0xff, b'.', b'p', b'n', b'g']);
+
+        let args =
+            CliArgs::parse([OsString::from("render"), OsString::from("-o"), invalid()]).unwrap();
+        assert!(matches!(args.command, Command::Render { path, .. } if path == invalid()));
+
+        assert!(matches!(
+            CliArgs::parse([OsString::from("--scale"), invalid()]),
+            Err(CliError::InvalidValue(arg, _)) if arg == "--scale"
+        ));
+        assert!(matches!(
+            CliArgs::parse([invalid()]),
+            Err(CliError::UnknownArgument(_))
+        ));
+    }
+}
@@ -6,7 +6,7 @@ use bevy::{
     window::{PrimaryWindow, WindowResized},
 };
 
-use crate::ui::NonUiArea;
+use crate::{fractal::histogram::HistogramSource, ui::NonUiArea};
 
 /// Plugin responsible for managing different viewports of the app
 pub struct CompositingPlugin;
@@ -27,7 +27,11 @@ impl Plugin for CompositingPlugin {
 
 /// Camera responsible for compositing the final image of the fractal to the screen
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Component)]
-#[require(Camera2d, OrthographicProjection(fractal_camera_projection))]
+#[require(
+    Camera2d,
+    OrthographicProjection(fractal_camera_projection),
+    HistogramSource
+)]
 pub struct ViewportCamera;
 
 pub fn fractal_camera_projection() -> OrthographicProjection {
@@ -0,0 +1,563 @@
+//! Exporting the fractal to a PNG file of any size.
+//!
+//! The image is rendered by an offscreen camera one tile at a time, so it can be much larger
+//! than the GPU allows for a single texture, and it doesn't depend on the size of the window.
+//! Each tile is read back and downsampled on the CPU. The tiles are rendered a row at a time,
+//! and every finished row is written to the file in the background, so only a couple of rows
+//! have to be kept in memory even for the largest images.
+//!
+//! Before the tiles, the whole image is rendered once at a size that fits in a tile,
+//! for the [histogram](crate::fractal::histogram) every tile is colored with.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    math::{uvec2, vec2},
+    prelude::*,
+    render::{
+        camera::{RenderTarget, ScalingMode},
+        gpu_readback::{Readback, ReadbackComplete},
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        renderer::RenderDevice,
+    },
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fractal::{
+        histogram::HistogramSource,
+        material::FractalShaderSource,
+        metadata::{fractal_metadata, METADATA_KEYWORD},
+        parameters::{CurrentParameterInputs, ParameterInputSystemSet},
+        perturbation::ReferenceOrbit,
+        Fractal, OffscreenFractal,
+    },
+    ui::UiSystemSet,
+};
+
+/// Largest width or height of an exported image
+pub const MAX_EXPORT_SIZE: u32 = 32768;
+/// Largest number of samples per pixel along each axis
+pub const MAX_SUPERSAMPLING: u32 = 4;
+
+// size of the tiles the image is rendered in, before downsampling and including the apron,
+// small enough for the buffers of the histogram and the lighting to fit in a storage binding
+const TILE_SIZE: u32 = 4096;
+// pixels rendered around every tile and then thrown away,
+// so that the lighting at the edges of the tile can use the heights of the pixels next to it
+const TILE_APRON: u32 = 1;
+/// Number of times an offscreen camera is read back before the result is used,
+/// the first frames can be rendered before everything the camera draws is prepared
+pub const SETTLE_FRAMES: u32 = 3;
+/// Bytes per pixel of the images rendered by offscreen cameras, which are RGBA in sRGB
+pub const OFFSCREEN_PIXEL_SIZE: usize = 4;
+
+const OFFSCREEN_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+// bytes per pixel of the exported image, which is RGB
+const EXPORT_PIXEL_SIZE: usize = 3;
+// higher than the viewport camera, so the histogram is taken from the whole image instead
+const HISTOGRAM_CAMERA_ORDER: isize = 1;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportPlugin;
+
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ExportImage>()
+            .add_event::<CancelExport>()
+            .add_event::<ExportFinished>()
+            .init_resource::<ExportSettings>()
+            // every tile has to show animated parameters at the same time
+            .configure_sets(
+                PreUpdate,
+                ParameterInputSystemSet.run_if(not(resource_exists::<ImageExport>)),
+            )
+            .add_systems(
+                Update,
+                (cancel_export, start_export, render_next_tile, finish_export)
+                    .chain()
+                    .after(UiSystemSet),
+            );
+    }
+}
+
+/// Size and quality of exported images
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportSettings {
+    pub width: u32,
+    pub height: u32,
+    /// Every pixel is the average of `supersampling * supersampling` samples
+    pub supersampling: u32,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            width: 3840,
+            height: 2160,
+            supersampling: 2,
+        }
+    }
+}
+
+impl ExportSettings {
+    // size of the image before downsampling
+    fn render_size(&self) -> UVec2 {
+        uvec2(self.width, self.height) * self.supersampling
+    }
+}
+
+/// Exports the current view of the fractal to a PNG file
+///
+/// The longer side of the image covers the same area as the longer side of the viewport.
+#[derive(Debug, Clone, Event)]
+pub struct ExportImage {
+    pub path: PathBuf,
+    pub settings: ExportSettings,
+}
+
+/// Stops the export in progress, the file is left unfinished if it's already being written
+#[derive(Debug, Clone, Copy, Event)]
+pub struct CancelExport;
+
+/// Sent once the image is written to the file, or the export failed
+#[derive(Debug, Clone, Event)]
+pub struct ExportFinished {
+    pub path: PathBuf,
+    /// Why the export failed, `None` if it succeeded
+    pub error: Option<String>,
+}
+
+type PngWriter = png::StreamWriter<'static, BufWriter<File>>;
+
+/// The export in progress, exists until the file is written
+#[derive(Resource)]
+pub struct ImageExport {
+    path: PathBuf,
+    settings: ExportSettings,
+    // renders the whole image for the histogram, kept until the export ends
+    histogram_camera: Option<Entity>,
+    // in the pixels of the image before downsampling
+    tiles: Vec<URect>,
+    // index of the tile being rendered
+    tile_index: usize,
+    tile_camera: Option<Entity>,
+    // number of frames the current tile has been read back
+    tile_frames: u32,
+    // the downsampled rows of the current row of tiles, empty until its first tile is rendered
+    band: Vec<u8>,
+    // a finished row of tiles, the next one isn't rendered until this one is being written
+    finished_band: Option<Vec<u8>>,
+    // `None` while a band is being written, and once the file is finished
+    writer: Option<PngWriter>,
+    // gives the writer back once the band is written, unless it was the last one
+    writing: Option<Task<Result<Option<PngWriter>, png::EncodingError>>>,
+}
+
+impl ImageExport {
+    /// Fraction of the tiles that are rendered
+    pub fn progress(&self) -> f32 {
+        self.tile_index as f32 / self.tiles.len() as f32
+    }
+
+    /// Whether all the tiles are rendered and the rest of the image is being written to the file
+    pub fn is_saving(&self) -> bool {
+        self.tile_index >= self.tiles.len()
+    }
+
+    // the area rendered for the tile, with the apron around it
+    fn rendered_area(&self, tile: URect) -> URect {
+        URect::from_corners(
+            tile.min.saturating_sub(UVec2::splat(TILE_APRON)),
+            (tile.max + TILE_APRON).min(self.settings.render_size()),
+        )
+    }
+
+    // copies the tile to the band, averaging the samples in linear space,
+    // `data` is the whole rendered area of the tile
+    fn copy_tile(&mut self, tile: URect, data: &[u8]) {
+        let area = self.rendered_area(tile);
+        let supersampling = self.settings.supersampling;
+        let band_start = tile.min.y / supersampling;
+        let sample_count = (supersampling * supersampling) as f32;
+        let row_size =
+            RenderDevice::align_copy_bytes_per_row(area.width() as usize * OFFSCREEN_PIXEL_SIZE);
+        let to_linear: [f32; 256] =
+            std::array::from_fn(|i| Srgba::gamma_function(i as f32 / 255.0));
+
+        for y in tile.min.y / supersampling..tile.max.y / supersampling {
+            for x in tile.min.x / supersampling..tile.max.x / supersampling {
+                let mut sum = Vec3::ZERO;
+                for sample_y in y * supersampling..(y + 1) * supersampling {
+                    for sample_x in x * supersampling..(x + 1) * supersampling {
+                        let row = (sample_y - area.min.y) as usize * row_size;
+                        let i = row + (sample_x - area.min.x) as usize * OFFSCREEN_PIXEL_SIZE;
+                        sum += Vec3::from_array(std::array::from_fn(|c| {
+                            to_linear[data[i + c] as usize]
+                        }));
+                    }
+                }
+
+                let color = (sum / sample_count).to_array();
+                let i = ((y - band_start) * self.settings.width + x) as usize * EXPORT_PIXEL_SIZE;
+                for (pixel, value) in self.band[i..i + EXPORT_PIXEL_SIZE].iter_mut().zip(color) {
+                    *pixel = (Srgba::gamma_function_inverse(value) * 255.0).round() as u8;
+                }
+            }
+        }
+    }
+}
+
+/// Whether the fractal is ready to be exported as it is now
+///
+/// It isn't while its shader is being loaded or its reference orbit is being calculated,
+/// the export would be blank or use an outdated orbit otherwise
+pub fn is_ready_to_export(
+    shader_source: &FractalShaderSource,
+    fractal: &Fractal,
+    orbit: &ReferenceOrbit,
+) -> bool {
+    shader_source.0.is_some() && orbit.is_ready_for(fractal)
+}
+
+pub fn cancel_export(
+    mut commands: Commands,
+    mut events: EventReader<CancelExport>,
+    export: Option<Res<ImageExport>>,
+) {
+    let Some(CancelExport) = events.read().last() else {
+        return;
+    };
+    let Some(export) = export else {
+        return;
+    };
+
+    info!(path = ?export.path, "Cancelled export");
+    if let Some(camera) = export.histogram_camera {
+        commands.entity(camera).despawn();
+    }
+    // the histogram camera is the tile camera until the histogram is done
+    if let Some(camera) = export
+        .tile_camera
+        .filter(|&c| Some(c) != export.histogram_camera)
+    {
+        commands.entity(camera).despawn();
+    }
+    commands.remove_resource::<ImageExport>();
+}
+
+pub fn start_export(
+    mut commands: Commands,
+    mut events: EventReader<ExportImage>,
+    export: Option<Res<ImageExport>>,
+    fractals: Query<&Fractal, Without<OffscreenFractal>>,
+    inputs: CurrentParameterInputs,
+    mut finished: EventWriter<ExportFinished>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+    let Ok(fractal) = fractals.get_single() else {
+        return;
+    };
+    if export.is_some() {
+        warn!("An image is already being exported");
+        return;
+    }
+
+    let settings = event.settings;
+    info!(path = ?event.path, ?settings, "Exporting image");
+
+    // the fractal is frozen at the moment the export starts, see [`metadata`](crate::fractal::metadata)
+    let metadata = fractal_metadata(&fractal.frozen(inputs.get()));
+    let writer = match create_png(&event.path, settings, metadata) {
+        Ok(writer) => writer,
+        Err(e) => {
+            finished.send(ExportFinished {
+                path: event.path.clone(),
+                error: Some(e.to_string()),
+            });
+            return;
+        }
+    };
+
+    commands.insert_resource(ImageExport {
+        path: event.path.clone(),
+        settings,
+        histogram_camera: None,
+        tiles: tiles(
+            settings.render_size(),
+            (TILE_SIZE - 2 * TILE_APRON) / settings.supersampling * settings.supersampling,
+        ),
+        tile_index: 0,
+        tile_camera: None,
+        tile_frames: 0,
+        band: Vec::new(),
+        finished_band: None,
+        writer: Some(writer),
+        writing: None,
+    });
+}
+
+// writes everything up to the pixels, which are streamed in as the rows of tiles are finished
+fn create_png(
+    path: &Path,
+    settings: ExportSettings,
+    metadata: String,
+) -> Result<PngWriter, png::EncodingError> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, settings.width, settings.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.add_text_chunk(METADATA_KEYWORD.to_string(), metadata)?;
+
+    encoder.write_header()?.into_stream_writer()
+}
+
+// splits the image into rows of tiles, aligned to the size of a downsampled pixel,
+// the rows are in order so they can be written to the file as they are finished
+fn tiles(size: UVec2, tile_size: u32) -> Vec<URect> {
+    let mut tiles = Vec::new();
+    for y in (0..size.y).step_by(tile_size as usize) {
+        for x in (0..size.x).step_by(tile_size as usize) {
+            let min = uvec2(x, y);
+            tiles.push(URect::from_corners(min, (min + tile_size).min(size)));
+        }
+    }
+    tiles
+}
+
+pub fn render_next_tile(
+    mut commands: Commands,
+    export: Option<ResMut<ImageExport>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Some(mut export) = export else {
+        return;
+    };
+    if export.tile_camera.is_some()
+        || export.finished_band.is_some()
+        || export.tile_index >= export.tiles.len()
+    {
+        return;
+    }
+
+    if export.histogram_camera.is_none() {
+        // the whole image, scaled down to fit in a tile
+        let render_size = export.settings.render_size();
+        let scale = (TILE_SIZE as f32 / render_size.max_element() as f32).min(1.0);
+        let size = (render_size.as_vec2() * scale).as_uvec2().max(UVec2::ONE);
+        debug!(?size, "Rendering histogram");
+
+        let mut camera = spawn_offscreen_camera(
+            &mut commands,
+            &mut images,
+            size,
+            URect::from_corners(UVec2::ZERO, size),
+        );
+        camera
+            .insert(HistogramSource)
+            .observe(receive_histogram)
+            .entry::<Camera>()
+            .and_modify(|mut camera| camera.order = HISTOGRAM_CAMERA_ORDER);
+        let camera = camera.id();
+
+        export.histogram_camera = Some(camera);
+        export.tile_camera = Some(camera);
+        export.tile_frames = 0;
+        return;
+    }
+
+    let tile = export.tiles[export.tile_index];
+    debug!(index = export.tile_index, ?tile, "Rendering tile");
+    if export.band.is_empty() {
+        let rows = tile.height() / export.settings.supersampling;
+        export.band = vec![0; (export.settings.width * rows) as usize * EXPORT_PIXEL_SIZE];
+    }
+
+    let camera = spawn_offscreen_camera(
+        &mut commands,
+        &mut images,
+        export.settings.render_size(),
+        export.rendered_area(tile),
+    )
+    .observe(receive_tile)
+    .id();
+
+    export.tile_camera = Some(camera);
+    export.tile_frames = 0;
+}
+
+/// Spawns a camera rendering the fractal to a new image, which is read back every frame
+///
+/// The camera renders `area` of an image of the given size, covering the same area
+/// as the viewport along the longer side.
+pub fn spawn_offscreen_camera<'a>(
+    commands: &'a mut Commands,
+    images: &mut Assets<Image>,
+    size: UVec2,
+    area: URect,
+) -> EntityCommands<'a> {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: area.width(),
+            height: area.height(),
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0; OFFSCREEN_PIXEL_SIZE],
+        OFFSCREEN_FORMAT,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.texture_descriptor.usage |= TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC;
+    let image = images.add(image);
+
+    // the longer side of the image is 2 units wide, like the viewport
+    let size = size.as_vec2();
+    let world_pixel_size = 2.0 / size.max_element();
+    let center = (area.as_rect().center() - size / 2.0) * vec2(1.0, -1.0) * world_pixel_size;
+
+    commands.spawn((
+        Camera2d,
+        Camera {
+            target: RenderTarget::Image(image.clone()),
+            ..default()
+        },
+        OrthographicProjection {
+            scaling_mode: ScalingMode::Fixed {
+                width: area.width() as f32 * world_pixel_size,
+                height: area.height() as f32 * world_pixel_size,
+            },
+            ..OrthographicProjection::default_2d()
+        },
+        Transform::from_translation(center.extend(0.0)),
+        Msaa::Off,
+        Readback::texture(image),
+    ))
+}
+
+// the histogram is built by now, the camera is kept so it stays the source of the histogram
+pub fn receive_histogram(
+    trigger: Trigger<ReadbackComplete>,
+    mut commands: Commands,
+    export: Option<ResMut<ImageExport>>,
+    mut cameras: Query<&mut Camera>,
+) {
+    let Some(mut export) = export else {
+        return;
+    };
+    if export.tile_camera != Some(trigger.entity()) {
+        return;
+    }
+
+    export.tile_frames += 1;
+    if export.tile_frames < SETTLE_FRAMES {
+        return;
+    }
+
+    commands.entity(trigger.entity()).remove::<Readback>();
+    if let Ok(mut camera) = cameras.get_mut(trigger.entity()) {
+        camera.is_active = false;
+    }
+    export.tile_camera = None;
+}
+
+pub fn receive_tile(
+    trigger: Trigger<ReadbackComplete>,
+    mut commands: Commands,
+    export: Option<ResMut<ImageExport>>,
+) {
+    let Some(mut export) = export else {
+        return;
+    };
+    if export.tile_camera != Some(trigger.entity()) {
+        return;
+    }
+
+    export.tile_frames += 1;
+    if export.tile_frames < SETTLE_FRAMES {
+        return;
+    }
+
+    let tile = export.tiles[export.tile_index];
+    export.copy_tile(tile, &trigger.event().0);
+    commands.entity(trigger.entity()).despawn();
+    export.tile_camera = None;
+    export.tile_index += 1;
+
+    let next_tile = export.tiles.get(export.tile_index);
+    if next_tile.is_none_or(|next| next.min.y != tile.min.y) {
+        export.finished_band = Some(std::mem::take(&mut export.band));
+    }
+
+    if export.tile_index < export.tiles.len() {
+        return;
+    }
+
+    if let Some(camera) = export.histogram_camera.take() {
+        commands.entity(camera).despawn();
+    }
+    debug!(path = ?export.path, "Saving image");
+}
+
+// writes the finished rows of tiles to the file, and ends the export once they all are
+pub fn finish_export(
+    mut commands: Commands,
+    export: Option<ResMut<ImageExport>>,
+    mut finished: EventWriter<ExportFinished>,
+) {
+    let Some(mut export) = export else {
+        return;
+    };
+
+    if let Some(task) = export.writing.as_mut() {
+        let Some(result) = block_on(future::poll_once(task)) else {
+            return;
+        };
+        export.writing = None;
+
+        match result {
+            Ok(writer) => export.writer = writer,
+            Err(e) => {
+                // failures are reported by whoever listens for the event
+                commands.remove_resource::<ImageExport>();
+                finished.send(ExportFinished {
+                    path: export.path.clone(),
+                    error: Some(e.to_string()),
+                });
+                return;
+            }
+        }
+    }
+
+    if let Some(mut writer) = export.writer.take() {
+        let Some(band) = export.finished_band.take() else {
+            export.writer = Some(writer);
+            return;
+        };
+        let last = export.tile_index >= export.tiles.len();
+        export.writing = Some(AsyncComputeTaskPool::get().spawn(async move {
+            writer.write_all(&band)?;
+            if last {
+                writer.finish()?;
+                return Ok(None);
+            }
+            Ok(Some(writer))
+        }));
+        return;
+    }
+
+    commands.remove_resource::<ImageExport>();
+    info!(path = ?export.path, "Exported image");
+    finished.send(ExportFinished {
+        path: export.path.clone(),
+        error: None,
+    });
+}
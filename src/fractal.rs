@@ -1,57 +1,237 @@
-use bevy::{math::DVec2, prelude::*};
+use bevy::prelude::*;
 
+pub mod coloring;
+pub mod expression;
+pub mod formula;
+pub mod histogram;
+pub mod lighting;
 pub mod material;
+pub mod metadata;
+pub mod palette;
 pub mod parameters;
+pub mod perturbation;
+pub mod precise;
+pub mod precision;
+pub mod preset;
 pub mod render;
 
+use coloring::{ExteriorColoring, InteriorColoring, OrbitTrap};
+use formula::Formula;
+use histogram::HistogramPlugin;
+use lighting::Lighting;
 use material::FractalMaterialPlugin;
-use parameters::{ComplexParameter, Parameter};
+use metadata::MetadataPlugin;
+use palette::{Palette, MAX_COLOR_STOPS};
+use parameters::{
+    ComplexParameter, ComplexPart, Parameter, ParameterInputs, ParametersPlugin, PixelTransform,
+};
+use perturbation::{PerturbationPlugin, ReferenceOrbit, ReferenceOrbitTask};
+use precise::PreciseVec2;
+use precision::Precision;
+use preset::PresetPlugin;
 use render::{FractalRenderPlugin, FractalRenderer};
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of roots of the polynomial used by [`Formula::Newton`]
+pub const MAX_ROOT_COUNT: usize = 6;
+/// Smallest tolerance of [`Formula::Newton`], a tolerance of 0 would never converge
+pub const MIN_TOLERANCE: f64 = 1e-12;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct FractalPlugin;
 
 impl Plugin for FractalPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((FractalMaterialPlugin, FractalRenderPlugin));
+        app.add_plugins((
+            FractalMaterialPlugin,
+            FractalRenderPlugin,
+            PerturbationPlugin,
+            HistogramPlugin,
+            MetadataPlugin,
+            PresetPlugin,
+            ParametersPlugin,
+        ));
         app.add_systems(Startup, add_fractal_to_world);
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Component)]
-#[require(FractalRenderer)]
+#[derive(Debug, Clone, PartialEq, Component, Serialize, Deserialize)]
+#[serde(default)]
+#[require(FractalRenderer, ReferenceOrbit, ReferenceOrbitTask)]
 pub struct Fractal {
+    pub formula: Formula,
     pub iteration_count: u32,
     pub scale: f64,
     pub escape_radius: f64,
-    pub offset: DVec2,
+    /// Center of the screen, stored with arbitrary precision for deep zooms
+    pub offset: PreciseVec2,
     pub initial_z: ComplexParameter,
     pub c: ComplexParameter,
     pub p: ComplexParameter,
-    pub use_f64: bool,
+    /// Used by [`Parameter::TransformedPixel`]
+    pub pixel_transform: PixelTransform,
+    pub roots: [ComplexParameter; MAX_ROOT_COUNT],
+    pub root_count: usize,
+    pub tolerance: f64,
+    /// The formula used by [`Formula::Custom`], see [`expression`] for the syntax
+    pub custom_formula: String,
+    pub precision: Precision,
+    /// Render using perturbation, see [`perturbation`]
+    pub use_perturbation: bool,
+    pub palette: Palette,
+    pub exterior_coloring: ExteriorColoring,
+    /// Width of the darkened boundary in pixels, see [`ExteriorColoring::DistanceEstimate`]
+    pub boundary_thickness: f32,
+    /// Number of stripes in a full turn, see [`ExteriorColoring::StripeAverage`]
+    pub stripe_density: u32,
+    pub interior_coloring: InteriorColoring,
+    pub orbit_trap: OrbitTrap,
+    /// Shading of the fractal as a lit surface, see [`lighting`]
+    pub lighting: Lighting,
+}
+
+/// Marks a fractal that is only drawn by offscreen cameras, on a render layer of its own,
+/// such as the thumbnail of a bookmark
+///
+/// Everything that works with the fractal the user sees and edits leaves these out.
+#[derive(Debug, Clone, Copy, Default, Component)]
+pub struct OffscreenFractal;
+
+impl Fractal {
+    /// Whether any of the parameters in use change with time, see [`parameters::ParameterTime`]
+    pub fn is_animated(&self) -> bool {
+        self.parameters_in_use().any(Parameter::is_animated)
+    }
+
+    /// Whether any of the parameters in use follow the cursor, see [`parameters::ParameterCursor`]
+    pub fn follows_cursor(&self) -> bool {
+        self.parameters_in_use().any(Parameter::follows_cursor)
+    }
+
+    /// Whether any of the parameters in use are transformed by [`Fractal::pixel_transform`]
+    pub fn uses_pixel_transform(&self) -> bool {
+        self.parameters_in_use()
+            .any(|param| param == Parameter::TransformedPixel)
+    }
+
+    /// The fractal with every parameter that doesn't depend on the pixel replaced by its value
+    /// for the inputs, so it looks the same wherever it's saved to and opened later
+    pub fn frozen(&self, inputs: ParameterInputs) -> Fractal {
+        let mut fractal = self.clone();
+        let trap = &mut fractal.orbit_trap;
+        for param in [
+            &mut fractal.initial_z,
+            &mut fractal.c,
+            &mut fractal.p,
+            &mut trap.center,
+        ]
+        .into_iter()
+        .chain(&mut fractal.roots)
+        {
+            *param = param.frozen(inputs);
+        }
+        // neither of them is part of a complex number
+        trap.radius = trap.radius.frozen(ComplexPart::Real, inputs);
+        trap.angle = trap.angle.frozen(ComplexPart::Real, inputs);
+        fractal
+    }
+
+    /// The precision the shader uses, given whether the device supports f64
+    ///
+    /// Usually the selected one, but deep zooms with perturbation switch to double precision
+    /// if they can, see [`perturbation::needs_double_precision`]
+    pub fn shader_precision(&self, f64_supported: bool) -> Precision {
+        if f64_supported && perturbation::needs_double_precision(self) {
+            Precision::Double
+        } else {
+            self.precision.supported(f64_supported)
+        }
+    }
+
+    /// Replaces values the UI can't produce, which a loaded file could still have,
+    /// with ones that are safe to render and edit
+    pub fn sanitize(&mut self) {
+        let default = Fractal::default();
+        let positive_or = |value: f64, default: f64| {
+            if value.is_finite() && value > 0.0 {
+                value
+            } else {
+                default
+            }
+        };
+
+        self.scale = positive_or(self.scale, default.scale);
+        self.escape_radius = positive_or(self.escape_radius, default.escape_radius);
+        self.tolerance = positive_or(self.tolerance, default.tolerance).max(MIN_TOLERANCE);
+        self.root_count = self.root_count.clamp(1, MAX_ROOT_COUNT);
+        self.palette.stops.truncate(MAX_COLOR_STOPS);
+    }
+
+    fn parameters_in_use(&self) -> impl Iterator<Item = Parameter> + '_ {
+        let roots = &self.roots[..self.root_count.min(MAX_ROOT_COUNT)];
+        let trap = &self.orbit_trap;
+
+        [self.initial_z, self.c, self.p, trap.center]
+            .into_iter()
+            .chain(roots.iter().copied())
+            .flat_map(|param| [param.real, param.imaginary])
+            .chain([trap.radius, trap.angle])
+    }
 }
 
 impl Default for Fractal {
     fn default() -> Self {
         Self {
+            formula: Formula::Mandelbrot,
             iteration_count: 100,
             scale: 2.0,
             escape_radius: 2.0,
-            offset: DVec2::ZERO,
+            offset: PreciseVec2::ZERO,
             initial_z: default(),
-            c: ComplexParameter {
-                real: Parameter::PixelX,
-                imaginary: Parameter::PixelY,
-            },
+            c: ComplexParameter::PIXEL,
             p: ComplexParameter {
                 real: Parameter::Value(2.0),
                 imaginary: Parameter::Value(0.0),
             },
-            use_f64: false,
+            pixel_transform: default(),
+            roots: default_roots(),
+            root_count: 3,
+            tolerance: 0.001,
+            custom_formula: "z^2 + c".into(),
+            precision: Precision::Single,
+            use_perturbation: false,
+            palette: default(),
+            exterior_coloring: ExteriorColoring::Iterations,
+            boundary_thickness: 1.0,
+            stripe_density: 5,
+            interior_coloring: InteriorColoring::Black,
+            orbit_trap: default(),
+            lighting: default(),
         }
     }
 }
 
+// the cube roots of unity, the classic newton fractal
+fn default_roots() -> [ComplexParameter; MAX_ROOT_COUNT] {
+    let mut roots = [ComplexParameter::default(); MAX_ROOT_COUNT];
+    let half_sqrt_3 = f64::sqrt(3.0) / 2.0;
+
+    roots[0] = ComplexParameter {
+        real: Parameter::Value(1.0),
+        imaginary: Parameter::Value(0.0),
+    };
+    roots[1] = ComplexParameter {
+        real: Parameter::Value(-0.5),
+        imaginary: Parameter::Value(half_sqrt_3),
+    };
+    roots[2] = ComplexParameter {
+        real: Parameter::Value(-0.5),
+        imaginary: Parameter::Value(-half_sqrt_3),
+    };
+
+    roots
+}
+
 pub fn add_fractal_to_world(mut commands: Commands) {
     commands.spawn(Fractal::default());
 }
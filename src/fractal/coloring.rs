@@ -0,0 +1,143 @@
+use super::parameters::{ComplexParameter, Parameter};
+use bevy::prelude::default;
+use serde::{Deserialize, Serialize};
+
+/// How the points that escape are colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ExteriorColoring {
+    /// A color from the palette based on the smoothed iteration count
+    #[default]
+    Iterations,
+    /// The same as [`ExteriorColoring::Iterations`], but darkened near the boundary of the fractal,
+    /// using an estimate of the distance to it
+    ///
+    /// Not supported by [`Formula::Custom`](super::formula::Formula::Custom)
+    DistanceEstimate,
+    /// The average of `sin(k * arg z)` over the orbit, where `k` is the stripe density
+    StripeAverage,
+    /// The average of where `|z|` falls between the smallest and largest values
+    /// the triangle inequality allows for it
+    TriangleInequality,
+    /// A color from the palette based on the fraction of the pixels on the screen
+    /// that took fewer iterations to escape, see [`histogram`](super::histogram)
+    Histogram,
+}
+
+impl ExteriorColoring {
+    pub const ALL: [ExteriorColoring; 5] = [
+        ExteriorColoring::Iterations,
+        ExteriorColoring::DistanceEstimate,
+        ExteriorColoring::StripeAverage,
+        ExteriorColoring::TriangleInequality,
+        ExteriorColoring::Histogram,
+    ];
+
+    pub fn variant_str(self) -> &'static str {
+        match self {
+            ExteriorColoring::Iterations => "Iteration count",
+            ExteriorColoring::DistanceEstimate => "Distance estimate",
+            ExteriorColoring::StripeAverage => "Stripe average",
+            ExteriorColoring::TriangleInequality => "Triangle inequality average",
+            ExteriorColoring::Histogram => "Histogram equalized",
+        }
+    }
+}
+
+/// How the points that never escape are colored
+///
+/// Every mode except [`InteriorColoring::Black`] picks a color from the palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum InteriorColoring {
+    #[default]
+    Black,
+    /// Distance of the last point of the orbit from the origin
+    FinalMagnitude,
+    /// Angle of the last point of the orbit
+    FinalAngle,
+    /// Period of the cycle the orbit gets attracted to
+    Period,
+    /// Average distance of the points of the orbit from the origin
+    AverageDistance,
+    /// Distance to the [`OrbitTrap`] of the closest point of the orbit
+    OrbitTrap,
+}
+
+impl InteriorColoring {
+    pub const ALL: [InteriorColoring; 6] = [
+        InteriorColoring::Black,
+        InteriorColoring::FinalMagnitude,
+        InteriorColoring::FinalAngle,
+        InteriorColoring::Period,
+        InteriorColoring::AverageDistance,
+        InteriorColoring::OrbitTrap,
+    ];
+
+    pub fn variant_str(self) -> &'static str {
+        match self {
+            InteriorColoring::Black => "Black",
+            InteriorColoring::FinalMagnitude => "Final |z|",
+            InteriorColoring::FinalAngle => "Final angle",
+            InteriorColoring::Period => "Period",
+            InteriorColoring::AverageDistance => "Average distance",
+            InteriorColoring::OrbitTrap => "Orbit trap",
+        }
+    }
+}
+
+/// Colors points by how close their orbit gets to a shape
+///
+/// The geometry is made of parameters, so it can be tied to the pixel coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrbitTrap {
+    pub shape: TrapShape,
+    /// Center of the point, the cross and the circle, or a point the line goes through
+    pub center: ComplexParameter,
+    /// Radius of the circle
+    pub radius: Parameter,
+    /// Rotation of the line and the cross in radians
+    pub angle: Parameter,
+}
+
+impl Default for OrbitTrap {
+    fn default() -> Self {
+        Self {
+            shape: TrapShape::None,
+            center: default(),
+            radius: Parameter::Value(1.0),
+            angle: Parameter::Value(0.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TrapShape {
+    /// Orbit traps are disabled
+    #[default]
+    None,
+    Point,
+    Line,
+    /// Two perpendicular lines
+    Cross,
+    Circle,
+}
+
+impl TrapShape {
+    pub const ALL: [TrapShape; 5] = [
+        TrapShape::None,
+        TrapShape::Point,
+        TrapShape::Line,
+        TrapShape::Cross,
+        TrapShape::Circle,
+    ];
+
+    pub fn variant_str(self) -> &'static str {
+        match self {
+            TrapShape::None => "None",
+            TrapShape::Point => "Point",
+            TrapShape::Line => "Line",
+            TrapShape::Cross => "Cross",
+            TrapShape::Circle => "Circle",
+        }
+    }
+}
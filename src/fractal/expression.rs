@@ -0,0 +1,701 @@
+//! Parsing of user-defined formulas and compiling them into WGSL
+//!
+//! The formulas are regular math expressions over complex numbers, e.g. `z^3 + c*sin(z) + 0.2`
+
+use std::{
+    error::Error,
+    f64::consts::{E, PI},
+    fmt::{self, Display, Write},
+};
+
+/// How deeply expressions can be nested, to keep the recursive parser from overflowing the stack
+pub const MAX_NESTING_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variable {
+    Z,
+    C,
+    P,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Exp,
+    Log,
+    Sqrt,
+    Abs,
+    Conj,
+    Re,
+    Im,
+}
+
+impl Function {
+    pub const ALL: [Function; 13] = [
+        Function::Sin,
+        Function::Cos,
+        Function::Tan,
+        Function::Sinh,
+        Function::Cosh,
+        Function::Tanh,
+        Function::Exp,
+        Function::Log,
+        Function::Sqrt,
+        Function::Abs,
+        Function::Conj,
+        Function::Re,
+        Function::Im,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Function::Sin => "sin",
+            Function::Cos => "cos",
+            Function::Tan => "tan",
+            Function::Sinh => "sinh",
+            Function::Cosh => "cosh",
+            Function::Tanh => "tanh",
+            Function::Exp => "exp",
+            Function::Log => "log",
+            Function::Sqrt => "sqrt",
+            Function::Abs => "abs",
+            Function::Conj => "conj",
+            Function::Re => "re",
+            Function::Im => "im",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        // ln is a common enough alias to support it
+        if name == "ln" {
+            return Some(Function::Log);
+        }
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    fn wgsl_name(self) -> &'static str {
+        match self {
+            Function::Sin => "complex_sin",
+            Function::Cos => "complex_cos",
+            Function::Tan => "complex_tan",
+            Function::Sinh => "complex_sinh",
+            Function::Cosh => "complex_cosh",
+            Function::Tanh => "complex_tanh",
+            Function::Exp => "complex_exp",
+            Function::Log => "complex_ln",
+            Function::Sqrt => "complex_sqrt",
+            Function::Abs => "complex_abs",
+            Function::Conj => "complex_conj",
+            Function::Re => "complex_re",
+            Function::Im => "complex_im",
+        }
+    }
+}
+
+/// An expression over complex numbers
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    /// A real number literal
+    Number(f64),
+    ImaginaryUnit,
+    Variable(Variable),
+    Negate(Box<Expression>),
+    Binary(BinaryOp, Box<Expression>, Box<Expression>),
+    Function(Function, Box<Expression>),
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            current: 0,
+            depth: 0,
+        };
+
+        let expression = parser.parse_sum()?;
+        let token = parser.peek();
+        if token.kind != TokenKind::End {
+            return Err(ParseError::new(
+                format!("unexpected {}", token.kind),
+                token.position,
+            ));
+        }
+
+        Ok(expression)
+    }
+
+    /// Compiles the expression into a WGSL expression of type `vec2<fp>`
+    ///
+    /// The resulting code expects `z`, `c` and `p` to be in scope,
+    /// as well as the complex number functions from the fractal shader
+    pub fn to_wgsl(&self) -> String {
+        let mut out = String::new();
+        self.write_wgsl(&mut out);
+        out
+    }
+
+    /// Returns the value of the expression if it is a constant real number
+    fn as_real_constant(&self) -> Option<f64> {
+        match self {
+            Expression::Number(value) => Some(*value),
+            Expression::Negate(inner) => inner.as_real_constant().map(|v| -v),
+            _ => None,
+        }
+    }
+
+    fn write_wgsl(&self, out: &mut String) {
+        match self {
+            Expression::Number(value) => {
+                let _ = write!(out, "vec2<fp>({value:?}, 0.0)");
+            }
+            Expression::ImaginaryUnit => out.push_str("vec2<fp>(0.0, 1.0)"),
+            Expression::Variable(variable) => out.push_str(match variable {
+                Variable::Z => "z",
+                Variable::C => "c",
+                Variable::P => "p",
+            }),
+            Expression::Negate(inner) => {
+                // naga can't negate constant f64 vectors, so subtracting from zero instead
+                out.push_str("(vec2<fp>(0.0, 0.0) - ");
+                inner.write_wgsl(out);
+                out.push(')');
+            }
+            Expression::Binary(BinaryOp::Add, lhs, rhs) => write_infix(out, lhs, " + ", rhs),
+            Expression::Binary(BinaryOp::Sub, lhs, rhs) => write_infix(out, lhs, " - ", rhs),
+            Expression::Binary(BinaryOp::Mul, lhs, rhs) => {
+                write_call(out, "complex_mult", lhs, rhs)
+            }
+            Expression::Binary(BinaryOp::Div, lhs, rhs) => write_call(out, "complex_div", lhs, rhs),
+            Expression::Binary(BinaryOp::Pow, base, exponent) => {
+                write_pow(out, base, exponent);
+            }
+            Expression::Function(function, argument) => {
+                out.push_str(function.wgsl_name());
+                out.push('(');
+                argument.write_wgsl(out);
+                out.push(')');
+            }
+        }
+    }
+}
+
+fn write_infix(out: &mut String, lhs: &Expression, operator: &str, rhs: &Expression) {
+    out.push('(');
+    lhs.write_wgsl(out);
+    out.push_str(operator);
+    rhs.write_wgsl(out);
+    out.push(')');
+}
+
+fn write_call(out: &mut String, function: &str, lhs: &Expression, rhs: &Expression) {
+    out.push_str(function);
+    out.push('(');
+    lhs.write_wgsl(out);
+    out.push_str(", ");
+    rhs.write_wgsl(out);
+    out.push(')');
+}
+
+// picking the cheapest way to exponentiate, same as the exponent modes in the shader
+fn write_pow(out: &mut String, base: &Expression, exponent: &Expression) {
+    let Some(p) = exponent.as_real_constant() else {
+        write_call(out, "complex_pow_complex", base, exponent);
+        return;
+    };
+
+    if p == 0.0 {
+        out.push_str("vec2<fp>(1.0, 0.0)");
+    } else if p == 2.0 {
+        out.push_str("complex_square(");
+        base.write_wgsl(out);
+        out.push(')');
+    } else if p.fract() == 0.0 && p.abs() <= i32::MAX as f64 {
+        if p > 0.0 {
+            out.push_str("complex_pow_pos_int(");
+            base.write_wgsl(out);
+            let _ = write!(out, ", {}u)", p as u32);
+        } else {
+            out.push_str("complex_pow_neg_int(");
+            base.write_wgsl(out);
+            let _ = write!(out, ", {}i)", p as i32);
+        }
+    } else {
+        out.push_str("complex_pow_real(");
+        base.write_wgsl(out);
+        let _ = write!(out, ", fp({p:?}))");
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    /// Byte offset into the source where the error occurred
+    pub position: usize,
+}
+
+impl ParseError {
+    fn new(message: impl Into<String>, position: usize) -> Self {
+        Self {
+            message: message.into(),
+            position,
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind<'a> {
+    Number(f64),
+    Identifier(&'a str),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    LeftParen,
+    RightParen,
+    End,
+}
+
+impl Display for TokenKind<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Number(value) => write!(f, "number {value}"),
+            TokenKind::Identifier(name) => write!(f, "'{name}'"),
+            TokenKind::Plus => write!(f, "'+'"),
+            TokenKind::Minus => write!(f, "'-'"),
+            TokenKind::Star => write!(f, "'*'"),
+            TokenKind::Slash => write!(f, "'/'"),
+            TokenKind::Caret => write!(f, "'^'"),
+            TokenKind::LeftParen => write!(f, "'('"),
+            TokenKind::RightParen => write!(f, "')'"),
+            TokenKind::End => write!(f, "end of formula"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token<'a> {
+    kind: TokenKind<'a>,
+    position: usize,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token<'_>>, ParseError> {
+    let mut tokens = Vec::new();
+    let bytes = source.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        let kind = match bytes[i] {
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                i += 1;
+                continue;
+            }
+            b'+' => TokenKind::Plus,
+            b'-' => TokenKind::Minus,
+            b'*' => TokenKind::Star,
+            b'/' => TokenKind::Slash,
+            b'^' => TokenKind::Caret,
+            b'(' => TokenKind::LeftParen,
+            b')' => TokenKind::RightParen,
+            b'0'..=b'9' | b'.' => {
+                i = number_end(bytes, i);
+                let text = &source[start..i];
+                let value = text
+                    .parse::<f64>()
+                    .map_err(|_| ParseError::new(format!("invalid number '{text}'"), start))?;
+
+                // the shader might be single precision
+                if value > f32::MAX as f64 {
+                    return Err(ParseError::new(
+                        format!("number {text} is too large"),
+                        start,
+                    ));
+                }
+
+                tokens.push(Token {
+                    kind: TokenKind::Number(value),
+                    position: start,
+                });
+                continue;
+            }
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Identifier(&source[start..i]),
+                    position: start,
+                });
+                continue;
+            }
+            _ => {
+                // the source might not be ASCII, so we can't just index into it
+                let c = source[start..].chars().next().unwrap_or_default();
+                return Err(ParseError::new(
+                    format!("unexpected character '{c}'"),
+                    start,
+                ));
+            }
+        };
+
+        tokens.push(Token {
+            kind,
+            position: start,
+        });
+        i += 1;
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::End,
+        position: source.len(),
+    });
+    Ok(tokens)
+}
+
+fn number_end(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+        i += 1;
+    }
+
+    // only treat 'e' as an exponent if a number follows it,
+    // so that things like `2e` aren't ambiguous with the constant e
+    if i < bytes.len() && matches!(bytes[i], b'e' | b'E') {
+        let mut exponent_start = i + 1;
+        if exponent_start < bytes.len() && matches!(bytes[exponent_start], b'+' | b'-') {
+            exponent_start += 1;
+        }
+        if exponent_start < bytes.len() && bytes[exponent_start].is_ascii_digit() {
+            i = exponent_start;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+        }
+    }
+
+    i
+}
+
+struct Parser<'t, 'a> {
+    tokens: &'t [Token<'a>],
+    current: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'_, 'a> {
+    fn peek(&self) -> &Token<'a> {
+        // the last token is always End, and we never advance past it
+        &self.tokens[self.current]
+    }
+
+    fn advance(&mut self) -> &Token<'a> {
+        let token = &self.tokens[self.current];
+        if token.kind != TokenKind::End {
+            self.current += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, kind: TokenKind<'_>) -> Result<(), ParseError> {
+        let token = self.advance();
+        if token.kind != kind {
+            return Err(ParseError::new(
+                format!("expected {kind}, found {}", token.kind),
+                token.position,
+            ));
+        }
+        Ok(())
+    }
+
+    // sum := product (('+' | '-') product)*
+    fn parse_sum(&mut self) -> Result<Expression, ParseError> {
+        let mut lhs = self.parse_product()?;
+
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Plus => BinaryOp::Add,
+                TokenKind::Minus => BinaryOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            let rhs = self.parse_product()?;
+            lhs = Expression::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    // product := unary (('*' | '/') unary)*
+    fn parse_product(&mut self) -> Result<Expression, ParseError> {
+        let mut lhs = self.parse_unary()?;
+
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Star => BinaryOp::Mul,
+                TokenKind::Slash => BinaryOp::Div,
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            let rhs = self.parse_unary()?;
+            lhs = Expression::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    // unary := ('-' | '+') unary | power
+    // every kind of nesting goes through here, so this is where the depth is limited
+    fn parse_unary(&mut self) -> Result<Expression, ParseError> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(ParseError::new(
+                "formula is nested too deeply",
+                self.peek().position,
+            ));
+        }
+
+        self.depth += 1;
+        let expression = self.parse_unary_inner();
+        self.depth -= 1;
+        expression
+    }
+
+    fn parse_unary_inner(&mut self) -> Result<Expression, ParseError> {
+        match self.peek().kind {
+            TokenKind::Minus => {
+                self.advance();
+                Ok(Expression::Negate(Box::new(self.parse_unary()?)))
+            }
+            TokenKind::Plus => {
+                self.advance();
+                self.parse_unary()
+            }
+            _ => self.parse_power(),
+        }
+    }
+
+    // power := atom ('^' unary)?
+    // the exponent is parsed as unary to make it right associative and to allow z^-2
+    fn parse_power(&mut self) -> Result<Expression, ParseError> {
+        let base = self.parse_atom()?;
+
+        if self.peek().kind != TokenKind::Caret {
+            return Ok(base);
+        }
+        self.advance();
+        let exponent = self.parse_unary()?;
+
+        Ok(Expression::Binary(
+            BinaryOp::Pow,
+            Box::new(base),
+            Box::new(exponent),
+        ))
+    }
+
+    // atom := number | variable | constant | function '(' sum ')' | '(' sum ')'
+    fn parse_atom(&mut self) -> Result<Expression, ParseError> {
+        let token = self.advance().clone();
+
+        match token.kind {
+            TokenKind::Number(value) => Ok(Expression::Number(value)),
+            TokenKind::LeftParen => {
+                let inner = self.parse_sum()?;
+                self.expect(TokenKind::RightParen)?;
+                Ok(inner)
+            }
+            TokenKind::Identifier(name) => self.parse_identifier(name, token.position),
+            TokenKind::End => Err(ParseError::new("expected an expression", token.position)),
+            kind => Err(ParseError::new(
+                format!("expected an expression, found {kind}"),
+                token.position,
+            )),
+        }
+    }
+
+    fn parse_identifier(&mut self, name: &str, position: usize) -> Result<Expression, ParseError> {
+        let expression = match name {
+            "z" => Expression::Variable(Variable::Z),
+            "c" => Expression::Variable(Variable::C),
+            "p" => Expression::Variable(Variable::P),
+            "i" => Expression::ImaginaryUnit,
+            "pi" => Expression::Number(PI),
+            "e" => Expression::Number(E),
+            _ => {
+                let Some(function) = Function::from_name(name) else {
+                    return Err(ParseError::new(format!("unknown name '{name}'"), position));
+                };
+
+                self.expect(TokenKind::LeftParen)?;
+                let argument = self.parse_sum()?;
+                self.expect(TokenKind::RightParen)?;
+
+                Expression::Function(function, Box::new(argument))
+            }
+        };
+
+        Ok(expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Expression {
+        Expression::parse(source).unwrap()
+    }
+
+    fn binary(op: BinaryOp, lhs: Expression, rhs: Expression) -> Expression {
+        Expression::Binary(op, Box::new(lhs), Box::new(rhs))
+    }
+
+    const Z: Expression = Expression::Variable(Variable::Z);
+    const C: Expression = Expression::Variable(Variable::C);
+
+    #[test]
+    fn precedence() {
+        assert_eq!(
+            parse("z + c * 2"),
+            binary(
+                BinaryOp::Add,
+                Z,
+                binary(BinaryOp::Mul, C, Expression::Number(2.0))
+            )
+        );
+        assert_eq!(
+            parse("(z + c) * 2"),
+            binary(
+                BinaryOp::Mul,
+                binary(BinaryOp::Add, Z, C),
+                Expression::Number(2.0)
+            )
+        );
+        assert_eq!(
+            parse("z - c - 1"),
+            binary(
+                BinaryOp::Sub,
+                binary(BinaryOp::Sub, Z, C),
+                Expression::Number(1.0)
+            )
+        );
+    }
+
+    #[test]
+    fn power_is_right_associative_and_binds_tighter_than_negation() {
+        assert_eq!(
+            parse("z^2^3"),
+            binary(
+                BinaryOp::Pow,
+                Z,
+                binary(
+                    BinaryOp::Pow,
+                    Expression::Number(2.0),
+                    Expression::Number(3.0)
+                )
+            )
+        );
+        assert_eq!(
+            parse("-z^2"),
+            Expression::Negate(Box::new(binary(BinaryOp::Pow, Z, Expression::Number(2.0))))
+        );
+        assert_eq!(
+            parse("z^-2"),
+            binary(
+                BinaryOp::Pow,
+                Z,
+                Expression::Negate(Box::new(Expression::Number(2.0)))
+            )
+        );
+    }
+
+    #[test]
+    fn functions_and_constants() {
+        assert_eq!(
+            parse("ln(z) + pi"),
+            binary(
+                BinaryOp::Add,
+                Expression::Function(Function::Log, Box::new(Z)),
+                Expression::Number(PI)
+            )
+        );
+        assert_eq!(parse("2e3"), Expression::Number(2000.0));
+        assert_eq!(
+            parse("2*e"),
+            binary(
+                BinaryOp::Mul,
+                Expression::Number(2.0),
+                Expression::Number(E)
+            )
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = |source| Expression::parse(source).unwrap_err();
+
+        assert_eq!(error("z +"), ParseError::new("expected an expression", 3));
+        assert_eq!(error("foo(z)"), ParseError::new("unknown name 'foo'", 0));
+        assert_eq!(
+            error("(z + c"),
+            ParseError::new("expected ')', found end of formula", 6)
+        );
+        assert_eq!(
+            error("z $ c"),
+            ParseError::new("unexpected character '$'", 2)
+        );
+        assert_eq!(error("z c"), ParseError::new("unexpected 'c'", 2));
+        assert_eq!(
+            error("sin z"),
+            ParseError::new("expected '(', found 'z'", 4)
+        );
+        assert_eq!(
+            error("1e39"),
+            ParseError::new("number 1e39 is too large", 0)
+        );
+    }
+
+    #[test]
+    fn nesting_depth_is_limited() {
+        let nested = |depth| format!("{}z{}", "(".repeat(depth), ")".repeat(depth));
+
+        assert!(Expression::parse(&nested(MAX_NESTING_DEPTH - 1)).is_ok());
+        assert!(Expression::parse(&nested(MAX_NESTING_DEPTH)).is_err());
+        assert!(Expression::parse(&nested(100_000)).is_err());
+        assert!(Expression::parse(&"-".repeat(100_000)).is_err());
+    }
+
+    #[test]
+    fn codegen() {
+        let wgsl = |source| parse(source).to_wgsl();
+
+        assert_eq!(wgsl("z^2 + c"), "(complex_square(z) + c)");
+        assert_eq!(wgsl("z^3"), "complex_pow_pos_int(z, 3u)");
+        assert_eq!(wgsl("z^-2"), "complex_pow_neg_int(z, -2i)");
+        assert_eq!(wgsl("z^1.5"), "complex_pow_real(z, fp(1.5))");
+        assert_eq!(wgsl("z^0"), "vec2<fp>(1.0, 0.0)");
+        assert_eq!(wgsl("z^c"), "complex_pow_complex(z, c)");
+        assert_eq!(
+            wgsl("-p * sin(z) / (2*i)"),
+            "complex_div(complex_mult((vec2<fp>(0.0, 0.0) - p), complex_sin(z)), \
+             complex_mult(vec2<fp>(2.0, 0.0), vec2<fp>(0.0, 1.0)))"
+        );
+    }
+}
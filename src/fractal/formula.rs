@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// The equation that is iterated for every pixel
+///
+/// All of these are variations of `z^p + c` that take the absolute value
+/// or the conjugate of `z` at some point during the iteration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Formula {
+    /// `z^p + c`
+    #[default]
+    Mandelbrot,
+    /// `(|re(z)| + i|im(z)|)^p + c`
+    BurningShip,
+    /// `conj(z)^p + c`
+    Tricorn,
+    /// `|re(z^p)| + i*im(z^p) + c`
+    Celtic,
+    /// `|re(z^p)| + i|im(z^p)| + c`
+    Buffalo,
+    /// Newton's method for a polynomial with the given roots, `z - f(z) / f'(z)`
+    ///
+    /// Unlike the other formulas, this one doesn't escape,
+    /// it converges to one of the roots instead
+    Newton,
+    /// A user-defined formula
+    Custom,
+}
+
+impl Formula {
+    pub const ALL: [Formula; 7] = [
+        Formula::Mandelbrot,
+        Formula::BurningShip,
+        Formula::Tricorn,
+        Formula::Celtic,
+        Formula::Buffalo,
+        Formula::Newton,
+        Formula::Custom,
+    ];
+
+    pub fn variant_str(self) -> &'static str {
+        match self {
+            Formula::Mandelbrot => "Mandelbrot",
+            Formula::BurningShip => "Burning Ship",
+            Formula::Tricorn => "Tricorn",
+            Formula::Celtic => "Celtic",
+            Formula::Buffalo => "Buffalo",
+            Formula::Newton => "Newton",
+            Formula::Custom => "Custom",
+        }
+    }
+
+    pub fn is_escape_time(self) -> bool {
+        self != Formula::Newton
+    }
+}
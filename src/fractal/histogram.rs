@@ -0,0 +1,282 @@
+//! Histogram equalized coloring.
+//!
+//! Takes two passes over the screen. The first one draws the fractal as usual and also writes
+//! the smoothed iteration count of every pixel to a buffer. A compute pass then builds
+//! a histogram of those values and its cumulative distribution, and the second pass maps
+//! the values through it, so every color of the palette covers about the same number of pixels
+//! regardless of the view or the iteration count.
+//!
+//! The histogram is only built from the view of the [`HistogramSource`] camera, the other views
+//! reuse it, so that the tiles of an exported image aren't equalized separately.
+
+use super::{
+    coloring::ExteriorColoring,
+    render::{PassBufferSystemSet, PassBuffers},
+    Fractal, OffscreenFractal,
+};
+use bevy::{
+    asset::load_internal_asset,
+    core_pipeline::core_2d::graph::{Core2d, Node2d},
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_asset::RenderAssets,
+        render_graph::{
+            NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner,
+        },
+        render_resource::{
+            binding_types::{storage_buffer_read_only_sized, storage_buffer_sized, uniform_buffer},
+            encase, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries,
+            CachedComputePipelineId, ComputePassDescriptor, ComputePipelineDescriptor,
+            PipelineCache, ShaderStages, ShaderType,
+        },
+        renderer::{RenderContext, RenderDevice},
+        storage::{GpuShaderStorageBuffer, ShaderStorageBuffer},
+        sync_world::MainEntity,
+        view::ExtractedView,
+        RenderApp,
+    },
+};
+use wgpu::{util::BufferInitDescriptor, BufferUsages};
+
+const HISTOGRAM_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x5d2b8e61_94c7_4f3a_a0d5_7e18c43b92f6);
+
+/// Number of bins in the histogram, the same as in the shaders
+pub const HISTOGRAM_BIN_COUNT: usize = 4096;
+/// Size of the buffer with the histogram, followed by its cumulative distribution,
+/// which has one more element
+pub const HISTOGRAM_BUFFER_SIZE: usize = (2 * HISTOGRAM_BIN_COUNT + 1) * size_of::<u32>();
+
+// sizes of the workgroups in the histogram shader
+const CLEAR_WORKGROUP_SIZE: u32 = 256;
+const ACCUMULATE_WORKGROUP_SIZE: u32 = 16;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HistogramPlugin;
+
+impl Plugin for HistogramPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            HISTOGRAM_SHADER_HANDLE,
+            "../../assets/shaders/histogram.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.init_resource::<HistogramSettings>()
+            .add_plugins(ExtractResourcePlugin::<HistogramSettings>::default())
+            .add_systems(
+                PostUpdate,
+                update_histogram_settings.after(PassBufferSystemSet),
+            );
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .add_render_graph_node::<ViewNodeRunner<HistogramNode>>(Core2d, HistogramLabel)
+            // the fractal is drawn in the opaque pass and the colors in the transparent one
+            .add_render_graph_edges(
+                Core2d,
+                (
+                    Node2d::MainOpaquePass,
+                    HistogramLabel,
+                    Node2d::MainTransparentPass,
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<HistogramPipeline>();
+    }
+}
+
+/// Marks the camera the histogram is built from
+///
+/// If several cameras have it, the one with the highest [`Camera::order`] is used,
+/// even if it isn't active, in which case the last histogram it built is kept.
+#[derive(Debug, Clone, Copy, Default, Component)]
+pub struct HistogramSource;
+
+/// Whether the fractal is colored with [`ExteriorColoring::Histogram`]
+pub fn histogram_equalized(fractal: &Fractal) -> bool {
+    fractal.formula.is_escape_time() && fractal.exterior_coloring == ExteriorColoring::Histogram
+}
+
+// everything the compute pass needs
+#[derive(Debug, Clone, PartialEq, Default, Resource, ExtractResource)]
+struct HistogramSettings {
+    enabled: bool,
+    iteration_count: u32,
+    // the main world entity of the camera the histogram is built from
+    source: Option<Entity>,
+    values: Handle<ShaderStorageBuffer>,
+    histogram: Handle<ShaderStorageBuffer>,
+}
+
+fn update_histogram_settings(
+    fractals: Query<&Fractal, Without<OffscreenFractal>>,
+    sources: Query<(Entity, &Camera), With<HistogramSource>>,
+    buffers: Res<PassBuffers>,
+    mut settings: ResMut<HistogramSettings>,
+) {
+    let Ok(fractal) = fractals.get_single() else {
+        return;
+    };
+
+    settings.set_if_neq(HistogramSettings {
+        enabled: histogram_equalized(fractal),
+        iteration_count: fractal.iteration_count,
+        source: sources
+            .iter()
+            .max_by_key(|(_, camera)| camera.order)
+            .map(|(entity, _)| entity),
+        values: buffers.histogram_values.handle.clone(),
+        histogram: buffers.histogram.clone(),
+    });
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, RenderLabel)]
+struct HistogramLabel;
+
+#[derive(Debug, Clone, Copy, ShaderType)]
+struct HistogramParams {
+    width: u32,
+    height: u32,
+    iteration_count: u32,
+}
+
+#[derive(Debug, Resource)]
+struct HistogramPipeline {
+    layout: BindGroupLayout,
+    clear: CachedComputePipelineId,
+    accumulate: CachedComputePipelineId,
+    compute_cdf: CachedComputePipelineId,
+}
+
+impl FromWorld for HistogramPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let layout = world.resource::<RenderDevice>().create_bind_group_layout(
+            "histogram_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    uniform_buffer::<HistogramParams>(false),
+                    storage_buffer_read_only_sized(false, None),
+                    storage_buffer_sized(false, None),
+                ),
+            ),
+        );
+
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let queue_pipeline = |entry_point: &'static str| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(entry_point.into()),
+                layout: vec![layout.clone()],
+                push_constant_ranges: Vec::new(),
+                shader: HISTOGRAM_SHADER_HANDLE,
+                shader_defs: Vec::new(),
+                entry_point: entry_point.into(),
+                zero_initialize_workgroup_memory: false,
+            })
+        };
+
+        Self {
+            clear: queue_pipeline("clear_histogram"),
+            accumulate: queue_pipeline("accumulate_histogram"),
+            compute_cdf: queue_pipeline("compute_cdf"),
+            layout,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct HistogramNode;
+
+impl ViewNode for HistogramNode {
+    type ViewQuery = (&'static ExtractedView, MainEntity);
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (view, camera): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let settings = world.resource::<HistogramSettings>();
+        if !settings.enabled || settings.source != Some(camera) {
+            return Ok(());
+        }
+
+        let pipeline = world.resource::<HistogramPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let (Some(clear), Some(accumulate), Some(compute_cdf)) = (
+            pipeline_cache.get_compute_pipeline(pipeline.clear),
+            pipeline_cache.get_compute_pipeline(pipeline.accumulate),
+            pipeline_cache.get_compute_pipeline(pipeline.compute_cdf),
+        ) else {
+            return Ok(());
+        };
+
+        let storage_buffers = world.resource::<RenderAssets<GpuShaderStorageBuffer>>();
+        let (Some(values), Some(histogram)) = (
+            storage_buffers.get(&settings.values),
+            storage_buffers.get(&settings.histogram),
+        ) else {
+            return Ok(());
+        };
+
+        let params = HistogramParams {
+            width: view.viewport.z,
+            height: view.viewport.w,
+            iteration_count: settings.iteration_count,
+        };
+        let mut params_bytes = encase::UniformBuffer::new(Vec::new());
+        params_bytes.write(&params).unwrap();
+
+        let render_device = render_context.render_device();
+        let params_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("histogram_params"),
+            contents: params_bytes.as_ref(),
+            usage: BufferUsages::UNIFORM,
+        });
+        let bind_group = render_device.create_bind_group(
+            "histogram_bind_group",
+            &pipeline.layout,
+            &BindGroupEntries::sequential((
+                params_buffer.as_entire_binding(),
+                values.buffer.as_entire_binding(),
+                histogram.buffer.as_entire_binding(),
+            )),
+        );
+
+        let mut pass =
+            render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("histogram_pass"),
+                    timestamp_writes: None,
+                });
+        pass.set_bind_group(0, &bind_group, &[]);
+
+        pass.set_pipeline(clear);
+        pass.dispatch_workgroups(HISTOGRAM_BIN_COUNT as u32 / CLEAR_WORKGROUP_SIZE, 1, 1);
+
+        pass.set_pipeline(accumulate);
+        pass.dispatch_workgroups(
+            params.width.div_ceil(ACCUMULATE_WORKGROUP_SIZE),
+            params.height.div_ceil(ACCUMULATE_WORKGROUP_SIZE),
+            1,
+        );
+
+        pass.set_pipeline(compute_cdf);
+        pass.dispatch_workgroups(1, 1, 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+//! Shading the fractal as if it was a lit surface.
+//!
+//! The fractal writes the height of every pixel to a buffer, and then a second pass
+//! calculates the normals of the surface from the heights around each pixel and shades
+//! the colors underneath with diffuse and specular lighting.
+
+use super::Fractal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lighting {
+    pub enabled: bool,
+    pub height: HeightSource,
+    /// Direction the light comes from, counterclockwise from the right of the screen, in degrees
+    pub azimuth: f32,
+    /// Angle between the light and the screen, in degrees
+    pub elevation: f32,
+    /// Scale of the heights, makes the surface steeper
+    pub depth: f32,
+    /// Brightness of the highlights
+    pub specular: f32,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            height: HeightSource::Iterations,
+            azimuth: 45.0,
+            elevation: 45.0,
+            depth: 10.0,
+            specular: 0.5,
+        }
+    }
+}
+
+/// What the height of each point of the surface is based on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum HeightSource {
+    /// The smoothed iteration count, the interior is the highest
+    #[default]
+    Iterations,
+    /// The estimated distance to the boundary, the boundary is the highest
+    ///
+    /// Not supported by [`Formula::Custom`](super::formula::Formula::Custom)
+    DistanceEstimate,
+}
+
+impl HeightSource {
+    pub const ALL: [HeightSource; 2] = [HeightSource::Iterations, HeightSource::DistanceEstimate];
+
+    pub fn variant_str(self) -> &'static str {
+        match self {
+            HeightSource::Iterations => "Iteration count",
+            HeightSource::DistanceEstimate => "Distance estimate",
+        }
+    }
+}
+
+/// Whether the fractal is drawn with [`Lighting`]
+pub fn lighting_enabled(fractal: &Fractal) -> bool {
+    fractal.formula.is_escape_time() && fractal.lighting.enabled
+}
@@ -1,31 +1,52 @@
-use super::{parameters::ComplexParameter, Fractal};
-use crate::fractal::parameters::Parameter;
+use super::{
+    coloring::{ExteriorColoring, InteriorColoring, OrbitTrap, TrapShape},
+    formula::Formula,
+    histogram::histogram_equalized,
+    lighting::{lighting_enabled, HeightSource},
+    palette::{Interpolation, Palette, MAX_COLOR_STOPS},
+    parameters::{
+        ComplexParameter, ComplexPart, CurrentParameterInputs, Parameter, ParameterInputs,
+    },
+    perturbation::{perturbation_supported, ReferenceOrbit},
+    precision::Precision,
+    render::{PassBufferSystemSet, PassBuffers},
+    Fractal, MAX_ROOT_COUNT,
+};
 use bevy::{
-    ecs::query::QuerySingleError,
+    math::DVec2,
     prelude::*,
     render::{
         mesh::{MeshVertexBufferLayoutRef, VertexBufferLayout},
         render_resource::{
-            AsBindGroup, RenderPipelineDescriptor, ShaderRef, ShaderType,
-            SpecializedMeshPipelineError, VertexAttribute, VertexFormat, VertexStepMode,
+            AsBindGroup, BlendComponent, BlendFactor, BlendOperation, BlendState,
+            RenderPipelineDescriptor, ShaderRef, ShaderType, SpecializedMeshPipelineError,
+            VertexAttribute, VertexFormat, VertexStepMode,
         },
+        storage::ShaderStorageBuffer,
     },
     sprite::{AlphaMode2d, Material2d, Material2dKey, Material2dPlugin},
 };
-use shader_float::EncodeShaderFloat;
+use fractal_shader::FractalShaderPlugin;
 
+mod fractal_shader;
 mod shader_float;
 #[cfg(debug_assertions)]
 mod shader_hot_reload;
 #[cfg(debug_assertions)]
 use shader_hot_reload::ShaderHotReloadPlugin;
 
+pub use fractal_shader::{CustomFormulaError, FractalShaderSource};
+pub use shader_float::{DoubleSingle, EncodeShaderFloat};
+
 const FRACTAL_SHADER_HANDLE: Handle<Shader> =
     Handle::weak_from_u128(0xca66eb26_69e9_4e00_8760_ba2d0019c452);
 
 const FRACTAL_SHADER_F64_HANDLE: Handle<Shader> =
     Handle::weak_from_u128(0xb6eee0d8_4663_4c6c_8e23_db6d30527739);
 
+const FRACTAL_SHADER_DF64_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x3f0b5c1e_8a2d_4d6b_9e47_c2a1d90f6b38);
+
 const Z_R_VALUE_INDEX: u32 = 0;
 const Z_I_VALUE_INDEX: u32 = 1;
 const C_R_VALUE_INDEX: u32 = 2;
@@ -34,6 +55,61 @@ const P_R_VALUE_INDEX: u32 = 4;
 const P_I_VALUE_INDEX: u32 = 5;
 const PIXEL_X_INDEX: u32 = 6;
 const PIXEL_Y_INDEX: u32 = 7;
+const PIXEL_RADIUS_INDEX: u32 = 8;
+const PIXEL_ANGLE_INDEX: u32 = 9;
+const INVERSE_PIXEL_R_INDEX: u32 = 10;
+const INVERSE_PIXEL_I_INDEX: u32 = 11;
+const TRANSFORMED_PIXEL_X_INDEX: u32 = 12;
+const TRANSFORMED_PIXEL_Y_INDEX: u32 = 13;
+// the values of the roots of a newton fractal go after this index,
+// real and imaginary parts of each root are next to each other
+const ROOT_VALUE_INDEX_START: u32 = 14;
+const TRAP_CENTER_R_VALUE_INDEX: u32 = ROOT_VALUE_INDEX_START + 2 * MAX_ROOT_COUNT as u32;
+const TRAP_CENTER_I_VALUE_INDEX: u32 = TRAP_CENTER_R_VALUE_INDEX + 1;
+const TRAP_RADIUS_VALUE_INDEX: u32 = TRAP_CENTER_R_VALUE_INDEX + 2;
+const TRAP_ANGLE_VALUE_INDEX: u32 = TRAP_CENTER_R_VALUE_INDEX + 3;
+
+const FORMULA_MANDELBROT: u32 = 0;
+const FORMULA_BURNING_SHIP: u32 = 1;
+const FORMULA_TRICORN: u32 = 2;
+const FORMULA_CELTIC: u32 = 3;
+const FORMULA_BUFFALO: u32 = 4;
+const FORMULA_NEWTON: u32 = 5;
+const FORMULA_CUSTOM: u32 = 6;
+
+const INTERPOLATION_LINEAR: u32 = 0;
+const INTERPOLATION_SMOOTH: u32 = 1;
+const INTERPOLATION_CONSTANT: u32 = 2;
+
+const INTERIOR_BLACK: u32 = 0;
+const INTERIOR_FINAL_MAGNITUDE: u32 = 1;
+const INTERIOR_FINAL_ANGLE: u32 = 2;
+const INTERIOR_PERIOD: u32 = 3;
+const INTERIOR_AVERAGE_DISTANCE: u32 = 4;
+const INTERIOR_ORBIT_TRAP: u32 = 5;
+
+const TRAP_NONE: u32 = 0;
+const TRAP_POINT: u32 = 1;
+const TRAP_LINE: u32 = 2;
+const TRAP_CROSS: u32 = 3;
+const TRAP_CIRCLE: u32 = 4;
+
+const EXTERIOR_ITERATIONS: u32 = 0;
+const EXTERIOR_DISTANCE_ESTIMATE: u32 = 1;
+const EXTERIOR_STRIPE_AVERAGE: u32 = 2;
+const EXTERIOR_TRIANGLE_INEQUALITY: u32 = 3;
+const EXTERIOR_HISTOGRAM: u32 = 4;
+
+const HISTOGRAM_PASS_NONE: u32 = 0;
+const HISTOGRAM_PASS_VALUES: u32 = 1;
+const HISTOGRAM_PASS_COLORS: u32 = 2;
+
+const LIGHTING_PASS_NONE: u32 = 0;
+const LIGHTING_PASS_HEIGHTS: u32 = 1;
+const LIGHTING_PASS_SHADING: u32 = 2;
+
+const HEIGHT_ITERATIONS: u32 = 0;
+const HEIGHT_DISTANCE_ESTIMATE: u32 = 1;
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct FractalMaterialPlugin;
@@ -43,46 +119,80 @@ impl Plugin for FractalMaterialPlugin {
         app.add_plugins((
             Material2dPlugin::<FractalMaterial<f32>>::default(),
             Material2dPlugin::<FractalMaterial<f64>>::default(),
+            Material2dPlugin::<FractalMaterial<DoubleSingle>>::default(),
+            FractalShaderPlugin,
         ));
         app.add_systems(
             PostUpdate,
             (
                 update_fractal_material::<f32>,
                 update_fractal_material::<f64>,
+                update_fractal_material::<DoubleSingle>,
+                update_pass_materials,
             ),
         );
+        app.add_systems(
+            PostUpdate,
+            (
+                update_pass_buffers::<f32>,
+                update_pass_buffers::<f64>,
+                update_pass_buffers::<DoubleSingle>,
+            )
+                .after(PassBufferSystemSet)
+                .run_if(resource_changed::<PassBuffers>),
+        );
 
         #[cfg(debug_assertions)]
         app.add_plugins(ShaderHotReloadPlugin);
-
-        #[cfg(not(debug_assertions))]
-        {
-            use bevy::render::render_resource::ShaderDefVal;
-
-            let mut shaders = app.world_mut().resource_mut::<Assets<Shader>>();
-            shaders.insert(
-                &FRACTAL_SHADER_HANDLE,
-                Shader::from_wgsl(
-                    include_str!("../../assets/shaders/fractal.wgsl"),
-                    "shaders/fractal.wgsl",
-                ),
-            );
-            shaders.insert(
-                &FRACTAL_SHADER_F64_HANDLE,
-                Shader::from_wgsl_with_defs(
-                    include_str!("../../assets/shaders/fractal.wgsl"),
-                    "shaders/fractal.wgsl",
-                    vec![ShaderDefVal::Bool("DOUBLE_PRECISION".into(), true)],
-                ),
-            );
-        }
     }
 }
 
-#[derive(Debug, Clone, Copy, Asset, TypePath, AsBindGroup, ShaderType)]
+#[derive(Debug, Clone, Asset, TypePath, AsBindGroup)]
+#[bind_group_data(MaterialPass)]
 pub struct FractalMaterial<FP: EncodeShaderFloat> {
     #[uniform(0)]
     uniform: MaterialUniform<FP>,
+    #[storage(1, read_only, visibility(fragment))]
+    reference_orbit: Handle<ShaderStorageBuffer>,
+    #[storage(2, visibility(fragment))]
+    histogram_values: Handle<ShaderStorageBuffer>,
+    #[storage(3, read_only, visibility(fragment))]
+    histogram: Handle<ShaderStorageBuffer>,
+    #[storage(4, visibility(fragment))]
+    heights: Handle<ShaderStorageBuffer>,
+    // generation of the reference orbit that is in the buffer
+    orbit_generation: u64,
+    pass: MaterialPass,
+}
+
+/// What a [`FractalMaterial`] draws
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Component)]
+pub enum MaterialPass {
+    /// The fractal, which also writes the values for the passes on top of it when they're used
+    #[default]
+    Fractal,
+    /// Only the colors of the histogram equalized coloring, on top of the fractal,
+    /// see [`histogram`](super::histogram)
+    HistogramColors,
+    /// The shading of the fractal, on top of its colors, see [`lighting`](super::lighting)
+    Lighting,
+}
+
+impl MaterialPass {
+    /// Whether the pass has anything to draw for the fractal
+    pub fn is_drawn(self, fractal: &Fractal) -> bool {
+        match self {
+            MaterialPass::Fractal => true,
+            MaterialPass::HistogramColors => histogram_equalized(fractal),
+            MaterialPass::Lighting => lighting_enabled(fractal),
+        }
+    }
+}
+
+impl<FP: EncodeShaderFloat> From<&FractalMaterial<FP>> for MaterialPass {
+    fn from(material: &FractalMaterial<FP>) -> Self {
+        material.pass
+    }
 }
 
 #[derive(Debug, Clone, Copy, ShaderType)]
@@ -93,45 +203,126 @@ struct MaterialUniform<FP: EncodeShaderFloat> {
     initial_z: EncodedComplexParameter<FP>,
     c: EncodedComplexParameter<FP>,
     p: EncodedComplexParameter<FP>,
+    // arrays in uniforms have to be aligned to 16 bytes,
+    // this position happens to be aligned for both f32 and f64
+    roots: [EncodedComplexParameter<FP>; MAX_ROOT_COUNT],
     escape_radius: FP::EncodedFp,
+    formula: u32,
+    root_count: u32,
+    tolerance: FP::EncodedFp,
+    // offset of the center of the screen from the center of the reference orbit
+    reference_offset: FP::EncodedVec2,
+    reference_length: u32,
+    perturbation: u32,
+    palette: EncodedPalette,
+    // structs have to be aligned to 16 bytes too, so these go before the u32s
+    trap_center: EncodedComplexParameter<FP>,
+    // the radius in the real part and the angle in the imaginary part
+    trap_geometry: EncodedComplexParameter<FP>,
+    interior_coloring: u32,
+    trap_shape: u32,
+    exterior_coloring: u32,
+    boundary_thickness: f32,
+    stripe_density: f32,
+    histogram_pass: u32,
+    lighting_pass: u32,
+    height_source: u32,
+    // in radians
+    light_azimuth: f32,
+    light_elevation: f32,
+    height_scale: f32,
+    specular: f32,
+    // doesn't need to be precise, see `Parameter::TransformedPixel`
+    pixel_transform: Mat2,
+    pixel_transform_offset: Vec2,
+}
+
+// colors don't need more precision than f32, so this is the same for every precision
+#[derive(Debug, Clone, Copy, ShaderType)]
+struct EncodedPalette {
+    // rgb is the color, w is the position
+    stops: [Vec4; MAX_COLOR_STOPS],
+    stop_count: u32,
+    interpolation: u32,
+    cycle_length: f32,
+    offset: f32,
 }
 
-impl<FP: EncodeShaderFloat> Default for FractalMaterial<FP> {
-    fn default() -> Self {
-        Fractal::default().into()
+impl<FP: EncodeShaderFloat> FractalMaterial<FP> {
+    pub fn new(
+        fractal: &Fractal,
+        orbit: &ReferenceOrbit,
+        buffers: &mut Assets<ShaderStorageBuffer>,
+        pass_buffers: &PassBuffers,
+        pass: MaterialPass,
+    ) -> Self {
+        Self {
+            // animated and cursor parameters get their values once the fractal is first updated
+            uniform: MaterialUniform::new(fractal, orbit, pass, ParameterInputs::default()),
+            reference_orbit: buffers.add(encode_reference_orbit::<FP>(orbit)),
+            histogram_values: pass_buffers.histogram_values.handle.clone(),
+            histogram: pass_buffers.histogram.clone(),
+            heights: pass_buffers.heights.handle.clone(),
+            orbit_generation: orbit.generation,
+            pass,
+        }
     }
 }
 
 impl<FP: EncodeShaderFloat + Clone> Material2d for FractalMaterial<FP> {
     fn vertex_shader() -> ShaderRef {
-        if FP::IS_DOUBLE_PRECISION {
-            ShaderRef::Handle(FRACTAL_SHADER_F64_HANDLE)
-        } else {
-            ShaderRef::Handle(FRACTAL_SHADER_HANDLE)
-        }
+        ShaderRef::Handle(shader_handle(FP::PRECISION))
     }
 
     fn fragment_shader() -> ShaderRef {
-        if FP::IS_DOUBLE_PRECISION {
-            ShaderRef::Handle(FRACTAL_SHADER_F64_HANDLE)
-        } else {
-            ShaderRef::Handle(FRACTAL_SHADER_HANDLE)
-        }
+        ShaderRef::Handle(shader_handle(FP::PRECISION))
     }
 
     fn depth_bias(&self) -> f32 {
-        0.0
+        // the lighting shades the histogram colors too
+        match self.pass {
+            MaterialPass::Lighting => 1.0,
+            _ => 0.0,
+        }
     }
 
     fn alpha_mode(&self) -> AlphaMode2d {
-        AlphaMode2d::Opaque
+        // drawn after the opaque fractal, the pixels that the histogram doesn't color are transparent
+        match self.pass {
+            MaterialPass::Fractal => AlphaMode2d::Opaque,
+            MaterialPass::HistogramColors | MaterialPass::Lighting => AlphaMode2d::Blend,
+        }
     }
 
     fn specialize(
         descriptor: &mut RenderPipelineDescriptor,
         _layout: &MeshVertexBufferLayoutRef,
-        _key: Material2dKey<Self>,
+        key: Material2dKey<Self>,
     ) -> Result<(), SpecializedMeshPipelineError> {
+        // the shading multiplies the colors underneath by the diffuse light in alpha
+        // and adds the specular highlights in rgb
+        if key.bind_group_data == MaterialPass::Lighting {
+            if let Some(target) = descriptor
+                .fragment
+                .as_mut()
+                .and_then(|fragment| fragment.targets.first_mut())
+                .and_then(Option::as_mut)
+            {
+                target.blend = Some(BlendState {
+                    color: BlendComponent {
+                        src_factor: BlendFactor::One,
+                        dst_factor: BlendFactor::SrcAlpha,
+                        operation: BlendOperation::Add,
+                    },
+                    alpha: BlendComponent {
+                        src_factor: BlendFactor::Zero,
+                        dst_factor: BlendFactor::One,
+                        operation: BlendOperation::Add,
+                    },
+                });
+            }
+        }
+
         let vertex_buf_layout = &mut descriptor.vertex.buffers;
         vertex_buf_layout.clear();
         vertex_buf_layout.push(VertexBufferLayout {
@@ -148,50 +339,177 @@ impl<FP: EncodeShaderFloat + Clone> Material2d for FractalMaterial<FP> {
     }
 }
 
-// This is needed for shader type derives
-impl<FP: EncodeShaderFloat> From<&FractalMaterial<FP>> for FractalMaterial<FP> {
-    fn from(value: &FractalMaterial<FP>) -> Self {
-        *value
+fn shader_handle(precision: Precision) -> Handle<Shader> {
+    match precision {
+        Precision::Single => FRACTAL_SHADER_HANDLE,
+        Precision::DoubleSingle => FRACTAL_SHADER_DF64_HANDLE,
+        Precision::Double => FRACTAL_SHADER_F64_HANDLE,
     }
 }
 
-impl<FP: EncodeShaderFloat> From<Fractal> for FractalMaterial<FP> {
-    fn from(fractal: Fractal) -> Self {
+impl<FP: EncodeShaderFloat> MaterialUniform<FP> {
+    fn new(
+        fractal: &Fractal,
+        orbit: &ReferenceOrbit,
+        pass: MaterialPass,
+        inputs: ParameterInputs,
+    ) -> Self {
+        let perturbation = fractal.use_perturbation
+            && perturbation_supported(fractal)
+            && orbit.is_usable_for(fractal);
+        let histogram_pass = match pass {
+            MaterialPass::Fractal if histogram_equalized(fractal) => HISTOGRAM_PASS_VALUES,
+            MaterialPass::Fractal => HISTOGRAM_PASS_NONE,
+            MaterialPass::HistogramColors => HISTOGRAM_PASS_COLORS,
+            MaterialPass::Lighting => HISTOGRAM_PASS_NONE,
+        };
+        let lighting_pass = match pass {
+            MaterialPass::Fractal if lighting_enabled(fractal) => LIGHTING_PASS_HEIGHTS,
+            MaterialPass::Fractal | MaterialPass::HistogramColors => LIGHTING_PASS_NONE,
+            MaterialPass::Lighting => LIGHTING_PASS_SHADING,
+        };
+        let lighting = &fractal.lighting;
+
         Self {
-            uniform: MaterialUniform {
-                iteration_count: fractal.iteration_count,
-                scale: FP::encode_f64(fractal.scale),
-                escape_radius: FP::encode_f64(fractal.escape_radius),
-                offset: FP::encode_vec2(fractal.offset),
-                initial_z: encode_complex_parameter(
-                    fractal.initial_z,
-                    Z_R_VALUE_INDEX,
-                    Z_I_VALUE_INDEX,
-                ),
-                c: encode_complex_parameter(fractal.c, C_R_VALUE_INDEX, C_I_VALUE_INDEX),
-                p: encode_complex_parameter(fractal.p, P_R_VALUE_INDEX, P_I_VALUE_INDEX),
+            iteration_count: fractal.iteration_count,
+            scale: FP::encode_f64(fractal.scale),
+            escape_radius: FP::encode_f64(fractal.escape_radius),
+            offset: FP::encode_vec2(fractal.offset.as_dvec2()),
+            initial_z: encode_complex_parameter(
+                fractal.initial_z,
+                Z_R_VALUE_INDEX,
+                Z_I_VALUE_INDEX,
+                inputs,
+            ),
+            c: encode_complex_parameter(fractal.c, C_R_VALUE_INDEX, C_I_VALUE_INDEX, inputs),
+            p: encode_complex_parameter(fractal.p, P_R_VALUE_INDEX, P_I_VALUE_INDEX, inputs),
+            formula: encode_formula(fractal.formula),
+            roots: std::array::from_fn(|i| {
+                let real_index = ROOT_VALUE_INDEX_START + 2 * i as u32;
+                encode_complex_parameter(fractal.roots[i], real_index, real_index + 1, inputs)
+            }),
+            root_count: fractal.root_count.min(MAX_ROOT_COUNT) as u32,
+            tolerance: FP::encode_f64(fractal.tolerance),
+            reference_offset: FP::encode_vec2(fractal.offset.difference(&orbit.center)),
+            reference_length: orbit.points.len() as u32,
+            perturbation: perturbation as u32,
+            palette: encode_palette(&fractal.palette),
+            trap_center: encode_complex_parameter(
+                fractal.orbit_trap.center,
+                TRAP_CENTER_R_VALUE_INDEX,
+                TRAP_CENTER_I_VALUE_INDEX,
+                inputs,
+            ),
+            trap_geometry: encode_trap_geometry(&fractal.orbit_trap, inputs),
+            interior_coloring: encode_interior_coloring(fractal.interior_coloring),
+            trap_shape: encode_trap_shape(fractal.orbit_trap.shape),
+            exterior_coloring: encode_exterior_coloring(fractal.exterior_coloring),
+            boundary_thickness: fractal.boundary_thickness,
+            stripe_density: fractal.stripe_density as f32,
+            histogram_pass,
+            lighting_pass,
+            height_source: match lighting.height {
+                HeightSource::Iterations => HEIGHT_ITERATIONS,
+                HeightSource::DistanceEstimate => HEIGHT_DISTANCE_ESTIMATE,
             },
+            light_azimuth: lighting.azimuth.to_radians(),
+            light_elevation: lighting.elevation.to_radians(),
+            height_scale: lighting.depth,
+            specular: lighting.specular,
+            pixel_transform: Mat2::from_cols(
+                fractal.pixel_transform.x_axis.as_vec2(),
+                fractal.pixel_transform.y_axis.as_vec2(),
+            ),
+            pixel_transform_offset: fractal.pixel_transform.offset.as_vec2(),
         }
     }
 }
 
+#[allow(clippy::type_complexity)]
 pub fn update_fractal_material<FP: EncodeShaderFloat>(
-    query: Query<(&Fractal, &MeshMaterial2d<FractalMaterial<FP>>), Changed<Fractal>>,
+    query: Query<(
+        Ref<Fractal>,
+        Ref<ReferenceOrbit>,
+        &MeshMaterial2d<FractalMaterial<FP>>,
+    )>,
     mut materials: ResMut<Assets<FractalMaterial<FP>>>,
+    mut buffers: ResMut<Assets<ShaderStorageBuffer>>,
+    parameter_inputs: CurrentParameterInputs,
 ) {
-    let (fractal, material) = match query.get_single() {
-        Ok(value) => value,
-        Err(QuerySingleError::NoEntities(_)) => return,
-        Err(e) => panic!("{e}"),
-    };
-    debug!(?fractal, "Updating fractal");
+    for (fractal, orbit, material) in query.iter() {
+        // animated and cursor parameters change every frame even if the fractal doesn't
+        let inputs_changed = parameter_inputs.changed_for(&fractal);
+        if !fractal.is_changed() && !orbit.is_changed() && !inputs_changed {
+            continue;
+        }
+        if !inputs_changed {
+            debug!(?fractal, "Updating fractal");
+        }
 
-    let Some(material) = materials.get_mut(material.0.id()) else {
-        warn!("Failed to find the fractal material asset");
-        return;
-    };
+        let Some(material) = materials.get_mut(material.0.id()) else {
+            warn!("Failed to find the fractal material asset");
+            continue;
+        };
+
+        material.uniform =
+            MaterialUniform::new(&fractal, &orbit, material.pass, parameter_inputs.get());
+
+        // the orbit might have changed while the other material was in use
+        if material.orbit_generation != orbit.generation {
+            debug!(
+                generation = orbit.generation,
+                "Updating reference orbit buffer"
+            );
+
+            material.orbit_generation = orbit.generation;
+            buffers.insert(
+                &material.reference_orbit,
+                encode_reference_orbit::<FP>(&orbit),
+            );
+        }
+    }
+}
+
+pub fn update_pass_materials(
+    fractals: Query<(Ref<Fractal>, &ReferenceOrbit, &Children)>,
+    passes: Query<&MeshMaterial2d<FractalMaterial<f32>>, With<MaterialPass>>,
+    mut materials: ResMut<Assets<FractalMaterial<f32>>>,
+    parameter_inputs: CurrentParameterInputs,
+) {
+    for (fractal, orbit, children) in fractals.iter() {
+        if !fractal.is_changed() && !parameter_inputs.changed_for(&fractal) {
+            continue;
+        }
 
-    *material = (*fractal).into();
+        for material in passes.iter_many(children) {
+            if let Some(material) = materials.get_mut(material.0.id()) {
+                material.uniform =
+                    MaterialUniform::new(&fractal, orbit, material.pass, parameter_inputs.get());
+            }
+        }
+    }
+}
+
+// every material has to be updated when a buffer is replaced, including the ones not in use
+pub fn update_pass_buffers<FP: EncodeShaderFloat>(
+    pass_buffers: Res<PassBuffers>,
+    mut materials: ResMut<Assets<FractalMaterial<FP>>>,
+) {
+    for (_, material) in materials.iter_mut() {
+        material.histogram_values = pass_buffers.histogram_values.handle.clone();
+        material.histogram = pass_buffers.histogram.clone();
+        material.heights = pass_buffers.heights.handle.clone();
+    }
+}
+
+fn encode_reference_orbit<FP: EncodeShaderFloat>(orbit: &ReferenceOrbit) -> ShaderStorageBuffer {
+    let mut points: Vec<_> = orbit.points.iter().map(|&p| FP::encode_vec2(p)).collect();
+    // empty buffers can't be bound
+    if points.is_empty() {
+        points.push(FP::encode_vec2(DVec2::ZERO));
+    }
+
+    ShaderStorageBuffer::from(points)
 }
 
 #[derive(Debug, Clone, Copy, ShaderType)]
@@ -212,9 +530,11 @@ fn encode_complex_parameter<FP: EncodeShaderFloat>(
     param: ComplexParameter,
     real_index: u32,
     imag_index: u32,
+    inputs: ParameterInputs,
 ) -> EncodedComplexParameter<FP> {
-    let real = encode_parameter::<FP>(param.real, real_index);
-    let imaginary = encode_parameter::<FP>(param.imaginary, imag_index);
+    let real = encode_parameter::<FP>(param.real, real_index, ComplexPart::Real, inputs);
+    let imaginary =
+        encode_parameter::<FP>(param.imaginary, imag_index, ComplexPart::Imaginary, inputs);
     EncodedComplexParameter {
         real_value: real.value,
         real_index: real.index,
@@ -223,22 +543,120 @@ fn encode_complex_parameter<FP: EncodeShaderFloat>(
     }
 }
 
+// animated and cursor parameters are sent as their value for the given inputs
 fn encode_parameter<FP: EncodeShaderFloat>(
     param: Parameter,
     value_index: u32,
+    part: ComplexPart,
+    inputs: ParameterInputs,
 ) -> EncodedParameter<FP> {
-    match param {
-        Parameter::Value(c) => EncodedParameter {
-            value: FP::encode_f64(c),
-            index: value_index,
+    let index = match param {
+        Parameter::PixelX => PIXEL_X_INDEX,
+        Parameter::PixelY => PIXEL_Y_INDEX,
+        Parameter::PixelRadius => PIXEL_RADIUS_INDEX,
+        Parameter::PixelAngle => PIXEL_ANGLE_INDEX,
+        Parameter::InversePixel => match part {
+            ComplexPart::Real => INVERSE_PIXEL_R_INDEX,
+            ComplexPart::Imaginary => INVERSE_PIXEL_I_INDEX,
         },
-        Parameter::PixelX => EncodedParameter {
-            value: FP::encode_f64(0.0),
-            index: PIXEL_X_INDEX,
+        Parameter::TransformedPixel => match part {
+            ComplexPart::Real => TRANSFORMED_PIXEL_X_INDEX,
+            ComplexPart::Imaginary => TRANSFORMED_PIXEL_Y_INDEX,
         },
-        Parameter::PixelY => EncodedParameter {
-            value: FP::encode_f64(0.0),
-            index: PIXEL_Y_INDEX,
+        _ => value_index,
+    };
+
+    EncodedParameter {
+        value: FP::encode_f64(param.value_at(part, inputs).unwrap_or(0.0)),
+        index,
+    }
+}
+
+fn encode_palette(palette: &Palette) -> EncodedPalette {
+    let sorted_stops = palette.sorted_stops();
+    let mut stops = [Vec4::ZERO; MAX_COLOR_STOPS];
+    for (encoded, stop) in stops.iter_mut().zip(&sorted_stops) {
+        *encoded = stop.color.to_vec3().extend(stop.position);
+    }
+
+    EncodedPalette {
+        stops,
+        stop_count: sorted_stops.len() as u32,
+        interpolation: match palette.interpolation {
+            Interpolation::Linear => INTERPOLATION_LINEAR,
+            Interpolation::Smooth => INTERPOLATION_SMOOTH,
+            Interpolation::Constant => INTERPOLATION_CONSTANT,
         },
+        // a zero would divide by zero in the shader
+        cycle_length: palette.cycle_length.max(f32::EPSILON),
+        offset: palette.offset,
+    }
+}
+
+fn encode_exterior_coloring(coloring: ExteriorColoring) -> u32 {
+    match coloring {
+        ExteriorColoring::Iterations => EXTERIOR_ITERATIONS,
+        ExteriorColoring::DistanceEstimate => EXTERIOR_DISTANCE_ESTIMATE,
+        ExteriorColoring::StripeAverage => EXTERIOR_STRIPE_AVERAGE,
+        ExteriorColoring::TriangleInequality => EXTERIOR_TRIANGLE_INEQUALITY,
+        ExteriorColoring::Histogram => EXTERIOR_HISTOGRAM,
+    }
+}
+
+fn encode_interior_coloring(coloring: InteriorColoring) -> u32 {
+    match coloring {
+        InteriorColoring::Black => INTERIOR_BLACK,
+        InteriorColoring::FinalMagnitude => INTERIOR_FINAL_MAGNITUDE,
+        InteriorColoring::FinalAngle => INTERIOR_FINAL_ANGLE,
+        InteriorColoring::Period => INTERIOR_PERIOD,
+        InteriorColoring::AverageDistance => INTERIOR_AVERAGE_DISTANCE,
+        InteriorColoring::OrbitTrap => INTERIOR_ORBIT_TRAP,
+    }
+}
+
+fn encode_trap_geometry<FP: EncodeShaderFloat>(
+    trap: &OrbitTrap,
+    inputs: ParameterInputs,
+) -> EncodedComplexParameter<FP> {
+    // neither of them is part of a complex number, so both read the real part of the pixel
+    let radius = encode_parameter::<FP>(
+        trap.radius,
+        TRAP_RADIUS_VALUE_INDEX,
+        ComplexPart::Real,
+        inputs,
+    );
+    let angle = encode_parameter::<FP>(
+        trap.angle,
+        TRAP_ANGLE_VALUE_INDEX,
+        ComplexPart::Real,
+        inputs,
+    );
+    EncodedComplexParameter {
+        real_value: radius.value,
+        real_index: radius.index,
+        imag_value: angle.value,
+        imag_index: angle.index,
+    }
+}
+
+fn encode_trap_shape(shape: TrapShape) -> u32 {
+    match shape {
+        TrapShape::None => TRAP_NONE,
+        TrapShape::Point => TRAP_POINT,
+        TrapShape::Line => TRAP_LINE,
+        TrapShape::Cross => TRAP_CROSS,
+        TrapShape::Circle => TRAP_CIRCLE,
+    }
+}
+
+fn encode_formula(formula: Formula) -> u32 {
+    match formula {
+        Formula::Mandelbrot => FORMULA_MANDELBROT,
+        Formula::BurningShip => FORMULA_BURNING_SHIP,
+        Formula::Tricorn => FORMULA_TRICORN,
+        Formula::Celtic => FORMULA_CELTIC,
+        Formula::Buffalo => FORMULA_BUFFALO,
+        Formula::Newton => FORMULA_NEWTON,
+        Formula::Custom => FORMULA_CUSTOM,
     }
 }
@@ -0,0 +1,147 @@
+use super::{FRACTAL_SHADER_DF64_HANDLE, FRACTAL_SHADER_F64_HANDLE, FRACTAL_SHADER_HANDLE};
+use crate::fractal::{expression::Expression, Fractal, OffscreenFractal};
+use bevy::{prelude::*, render::render_resource::ShaderDefVal};
+use std::borrow::Cow;
+
+pub const FRACTAL_SHADER_PATH: &str = "shaders/fractal.wgsl";
+
+// the line of the shader containing this gets replaced with the custom formula
+const CUSTOM_FORMULA_MARKER: &str = "// CUSTOM_FORMULA";
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FractalShaderPlugin;
+
+impl Plugin for FractalShaderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FractalShaderSource>()
+            .init_resource::<CustomFormulaWgsl>()
+            .init_resource::<CustomFormulaError>();
+
+        app.add_systems(
+            PostUpdate,
+            (
+                compile_custom_formula,
+                build_fractal_shaders.run_if(
+                    resource_changed::<FractalShaderSource>
+                        .or(resource_changed::<CustomFormulaWgsl>),
+                ),
+            )
+                .chain(),
+        );
+
+        #[cfg(not(debug_assertions))]
+        app.insert_resource(FractalShaderSource(Some(Cow::Borrowed(include_str!(
+            "../../../assets/shaders/fractal.wgsl"
+        )))));
+    }
+}
+
+/// Source code of the fractal shader, before the custom formula is inserted into it
+///
+/// Is `None` until the shader is loaded
+#[derive(Debug, Clone, Default, Resource)]
+pub struct FractalShaderSource(pub Option<Cow<'static, str>>);
+
+/// The custom formula compiled to WGSL
+///
+/// Is `None` until a formula compiles successfully, in which case the shader's default is used
+#[derive(Debug, Clone, Default, PartialEq, Eq, Resource)]
+pub struct CustomFormulaWgsl(pub Option<String>);
+
+/// Error from the last attempt to compile the custom formula
+#[derive(Debug, Clone, Default, PartialEq, Eq, Resource)]
+pub struct CustomFormulaError(pub Option<String>);
+
+pub fn compile_custom_formula(
+    fractals: Query<&Fractal, (Changed<Fractal>, Without<OffscreenFractal>)>,
+    mut wgsl: ResMut<CustomFormulaWgsl>,
+    mut error: ResMut<CustomFormulaError>,
+    mut last_formula: Local<Option<String>>,
+) {
+    for fractal in fractals.iter() {
+        // most changes to the fractal don't touch the formula
+        if last_formula.as_deref() == Some(fractal.custom_formula.as_str()) {
+            continue;
+        }
+        *last_formula = Some(fractal.custom_formula.clone());
+
+        match Expression::parse(&fractal.custom_formula) {
+            Ok(expression) => {
+                let compiled = expression.to_wgsl();
+                debug!(
+                    formula = fractal.custom_formula,
+                    compiled, "Compiled custom formula"
+                );
+
+                wgsl.set_if_neq(CustomFormulaWgsl(Some(compiled)));
+                error.set_if_neq(CustomFormulaError(None));
+            }
+            Err(e) => {
+                debug!(formula = fractal.custom_formula, error = %e, "Invalid custom formula");
+                error.0 = Some(e.to_string());
+            }
+        }
+    }
+}
+
+// there's no way to add defs to a shader in Material2d,
+// so we build every precision's version of the shader ourselves
+pub fn build_fractal_shaders(
+    source: Res<FractalShaderSource>,
+    custom_formula: Res<CustomFormulaWgsl>,
+    mut shaders: ResMut<Assets<Shader>>,
+) {
+    let Some(source) = source.0.as_deref() else {
+        return;
+    };
+
+    let source = match custom_formula.0.as_deref() {
+        Some(formula) => insert_custom_formula(source, formula),
+        None => source.to_owned(),
+    };
+    debug!("Building fractal shaders");
+
+    shaders.insert(
+        &FRACTAL_SHADER_HANDLE,
+        Shader::from_wgsl(source.clone(), FRACTAL_SHADER_PATH),
+    );
+    shaders.insert(
+        &FRACTAL_SHADER_F64_HANDLE,
+        Shader::from_wgsl_with_defs(
+            source.clone(),
+            FRACTAL_SHADER_PATH,
+            vec![
+                ShaderDefVal::Bool("DOUBLE_PRECISION".into(), true),
+                ShaderDefVal::Bool("ENCODED_FLOATS".into(), true),
+            ],
+        ),
+    );
+    shaders.insert(
+        &FRACTAL_SHADER_DF64_HANDLE,
+        Shader::from_wgsl_with_defs(
+            source,
+            FRACTAL_SHADER_PATH,
+            vec![
+                ShaderDefVal::Bool("DOUBLE_SINGLE".into(), true),
+                ShaderDefVal::Bool("ENCODED_FLOATS".into(), true),
+            ],
+        ),
+    );
+}
+
+fn insert_custom_formula(source: &str, formula: &str) -> String {
+    let mut out = String::with_capacity(source.len() + formula.len());
+
+    for line in source.lines() {
+        if line.contains(CUSTOM_FORMULA_MARKER) {
+            out.push_str("    return ");
+            out.push_str(formula);
+            out.push(';');
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+
+    out
+}
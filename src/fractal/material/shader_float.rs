@@ -1,5 +1,6 @@
+use crate::fractal::precision::Precision;
 use bevy::{
-    math::{uvec2, uvec4, vec2, DVec2, UVec2, UVec4, Vec2},
+    math::{uvec2, uvec4, vec2, vec4, DVec2, UVec2, UVec4, Vec2, Vec4},
     reflect::TypePath,
     render::render_resource::{encase::internal::WriteInto, ShaderSize, ShaderType},
 };
@@ -13,9 +14,9 @@ use std::fmt::Debug;
 
 /// A trait for encoding floating point value into a supported shader type
 ///
-/// This can either truncate to f32 or turn f64 into bit representation
+/// This can either truncate to f32, split f64 into two f32s or turn f64 into bit representation
 pub trait EncodeShaderFloat: TypePath + Copy {
-    const IS_DOUBLE_PRECISION: bool = false;
+    const PRECISION: Precision = Precision::Single;
     type EncodedFp: ShaderType + ShaderSize + Send + Sync + Copy + Debug + WriteInto;
     type EncodedVec2: ShaderType + ShaderSize + Send + Sync + Copy + Debug + WriteInto;
 
@@ -38,7 +39,7 @@ impl EncodeShaderFloat for f32 {
 }
 
 impl EncodeShaderFloat for f64 {
-    const IS_DOUBLE_PRECISION: bool = true;
+    const PRECISION: Precision = Precision::Double;
 
     // we're gonna turn the f64 into bits
     // pack them into a vec2<u32> and in the shader we will
@@ -61,3 +62,30 @@ impl EncodeShaderFloat for f64 {
         uvec4(x.x, x.y, y.x, y.y)
     }
 }
+
+/// Marker type for f64 emulated with a pair of f32s in the shader
+#[derive(Debug, Clone, Copy, TypePath)]
+pub struct DoubleSingle;
+
+impl EncodeShaderFloat for DoubleSingle {
+    const PRECISION: Precision = Precision::DoubleSingle;
+
+    // the value is the sum of the two f32s,
+    // the first is the f64 rounded to f32, the second is what was lost by rounding
+    type EncodedFp = Vec2;
+    type EncodedVec2 = Vec4;
+
+    fn encode_f64(value: f64) -> Self::EncodedFp {
+        let hi = value as f32;
+        let lo = (value - hi as f64) as f32;
+
+        vec2(hi, lo)
+    }
+
+    fn encode_vec2(value: DVec2) -> Self::EncodedVec2 {
+        let x = Self::encode_f64(value.x);
+        let y = Self::encode_f64(value.y);
+
+        vec4(x.x, x.y, y.x, y.y)
+    }
+}
@@ -1,5 +1,5 @@
-use crate::fractal::material::{FRACTAL_SHADER_F64_HANDLE, FRACTAL_SHADER_HANDLE};
-use bevy::{prelude::*, render::render_resource::ShaderDefVal};
+use super::fractal_shader::{FractalShaderSource, FRACTAL_SHADER_PATH};
+use bevy::{prelude::*, render::render_resource::Source};
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct ShaderHotReloadPlugin;
@@ -21,18 +21,19 @@ pub struct TempShaderAsset(Handle<Shader>);
 
 // load the temporary shader from the asset server for hot reloading
 pub fn load_temp_shader(asset_server: Res<AssetServer>, mut temp_asset: ResMut<TempShaderAsset>) {
-    let shader = asset_server.load::<Shader>("shaders/fractal.wgsl");
+    let shader = asset_server.load::<Shader>(FRACTAL_SHADER_PATH);
     temp_asset.0 = shader;
 }
 
-// add deps to the loaded shader and store it in another handle
-// this allows us to add defs to the shader since you can't do that
+// take the source of the loaded shader, the actual shaders are then built from it
+// this allows us to add defs and the custom formula to the shader since you can't do that
 // in Material2d
 // it also allows us to update both f32 and f64 versions of the shader at the same time
 pub fn finalize_shader(
     mut asset_events: EventReader<AssetEvent<Shader>>,
-    mut shaders: ResMut<Assets<Shader>>,
+    shaders: Res<Assets<Shader>>,
     temp_asset: Res<TempShaderAsset>,
+    mut shader_source: ResMut<FractalShaderSource>,
 ) {
     for event in asset_events.read().copied() {
         use AssetEvent as E;
@@ -42,16 +43,15 @@ pub fn finalize_shader(
         if id != temp_asset.id() {
             continue;
         }
-        let Some(mut temp_shader) = shaders.get(&temp_asset.0).cloned() else {
+        let Some(temp_shader) = shaders.get(&temp_asset.0) else {
             warn!("Shader change detected but no shader in assets");
             continue;
         };
+        let Source::Wgsl(ref source) = temp_shader.source else {
+            warn!("Fractal shader is not WGSL");
+            continue;
+        };
 
-        shaders.insert(&FRACTAL_SHADER_HANDLE, temp_shader.clone());
-        temp_shader
-            .shader_defs
-            .push(ShaderDefVal::Bool("DOUBLE_PRECISION".into(), true));
-
-        shaders.insert(&FRACTAL_SHADER_F64_HANDLE, temp_shader);
+        shader_source.0 = Some(source.clone());
     }
 }
@@ -0,0 +1,149 @@
+//! Storing fractals in the metadata of exported images.
+//!
+//! Exported PNGs get a tEXt chunk with the whole [`Fractal`] written as a RON [`preset`](super::preset),
+//! and opening such an image, or dropping it onto the window, restores the exact view.
+//! Parameters that change with time or follow the cursor are stored with the values they had
+//! when the image was exported, see [`Fractal::frozen`].
+
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+
+use super::{
+    preset::{read_preset, write_preset, PresetError, PresetFormat},
+    Fractal, OffscreenFractal,
+};
+use crate::{ui::UiSystemSet, utils::show_error_dialog};
+
+/// Keyword of the tEXt chunk with the fractal
+pub const METADATA_KEYWORD: &str = "Fractality";
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetadataPlugin;
+
+impl Plugin for MetadataPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<OpenImage>()
+            .add_systems(Update, open_image.after(UiSystemSet));
+    }
+}
+
+/// Restores the fractal from the metadata of an exported image
+#[derive(Debug, Clone, Event)]
+pub struct OpenImage {
+    pub path: PathBuf,
+}
+
+/// The fractal as text to put into the metadata
+pub fn fractal_metadata(fractal: &Fractal) -> String {
+    let text =
+        write_preset(fractal, PresetFormat::Ron).expect("fractals should always be serializable");
+
+    // tEXt chunks are latin-1, anything else can only be in strings, where it can be escaped
+    text.chars()
+        .map(|c| {
+            if c.is_ascii() {
+                c.to_string()
+            } else {
+                c.escape_unicode().to_string()
+            }
+        })
+        .collect()
+}
+
+/// Reads the fractal from the metadata of an image
+pub fn read_image_metadata(path: &Path) -> Result<Fractal, MetadataError> {
+    let file = BufReader::new(File::open(path)?);
+    let reader = png::Decoder::new(file).read_info()?;
+    read_png_metadata(reader.info())
+}
+
+/// Reads the fractal from the metadata of a PNG that is already being decoded
+pub fn read_png_metadata(info: &png::Info) -> Result<Fractal, MetadataError> {
+    let text = info
+        .uncompressed_latin1_text
+        .iter()
+        .find(|chunk| chunk.keyword == METADATA_KEYWORD)
+        .ok_or(MetadataError::Missing)?;
+
+    Ok(read_preset(&text.text, PresetFormat::Ron)?)
+}
+
+#[derive(Debug)]
+pub enum MetadataError {
+    Io(io::Error),
+    Png(png::DecodingError),
+    /// The image wasn't exported by Fractality
+    Missing,
+    Invalid(PresetError),
+}
+
+impl Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataError::Io(e) => write!(f, "{e}"),
+            MetadataError::Png(e) => write!(f, "not a valid PNG image: {e}"),
+            MetadataError::Missing => write!(f, "the image has no fractal parameters"),
+            MetadataError::Invalid(e) => write!(f, "invalid fractal parameters: {e}"),
+        }
+    }
+}
+
+impl Error for MetadataError {}
+
+impl From<io::Error> for MetadataError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<png::DecodingError> for MetadataError {
+    fn from(value: png::DecodingError) -> Self {
+        Self::Png(value)
+    }
+}
+
+impl From<PresetError> for MetadataError {
+    fn from(value: PresetError) -> Self {
+        Self::Invalid(value)
+    }
+}
+
+pub fn open_image(
+    mut events: EventReader<OpenImage>,
+    mut dropped: EventReader<FileDragAndDrop>,
+    mut fractals: Query<&mut Fractal, Without<OffscreenFractal>>,
+) {
+    let dropped_paths = dropped.read().filter_map(|event| match event {
+        FileDragAndDrop::DroppedFile { path_buf, .. } => Some(path_buf),
+        _ => None,
+    });
+    let Some(path) = events
+        .read()
+        .map(|event| &event.path)
+        .chain(dropped_paths)
+        .last()
+    else {
+        return;
+    };
+    let Ok(mut fractal) = fractals.get_single_mut() else {
+        return;
+    };
+
+    match read_image_metadata(path) {
+        Ok(restored) => {
+            info!(?path, "Restored fractal from image");
+            *fractal = restored;
+        }
+        Err(e) => show_error_dialog(
+            "Failed to open image",
+            format!("Failed to open {}: {e}", path.display()),
+        ),
+    }
+}
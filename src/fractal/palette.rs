@@ -0,0 +1,250 @@
+//! Colors of the points that escape.
+//!
+//! The smoothed iteration count of every escaped point is mapped onto a gradient
+//! made of color stops, which repeats every `cycle_length` iterations.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of color stops in a [`Palette`]
+pub const MAX_COLOR_STOPS: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Palette {
+    /// Positions don't have to be sorted, they're sorted before being sent to the shader
+    pub stops: Vec<ColorStop>,
+    pub interpolation: Interpolation,
+    /// Number of iterations it takes to go through the whole gradient once
+    pub cycle_length: f32,
+    /// Shift of the gradient as a fraction of the cycle
+    pub offset: f32,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        PalettePreset::Classic.palette()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ColorStop {
+    /// Position of the stop in the gradient, from 0 to 1
+    pub position: f32,
+    pub color: LinearRgba,
+}
+
+impl ColorStop {
+    fn srgb(position: f32, r: u8, g: u8, b: u8) -> Self {
+        Self {
+            position,
+            color: Color::srgb_u8(r, g, b).to_linear(),
+        }
+    }
+}
+
+impl Palette {
+    /// The stops sorted by position, at most [`MAX_COLOR_STOPS`] of them
+    pub fn sorted_stops(&self) -> Vec<ColorStop> {
+        let mut stops: Vec<_> = self.stops.iter().take(MAX_COLOR_STOPS).copied().collect();
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+        stops
+    }
+
+    /// Color at `t` along the gradient, the gradient wraps around from the last stop to the first
+    ///
+    /// Does the same thing as the shader
+    pub fn sample(&self, t: f32) -> LinearRgba {
+        let stops = self.sorted_stops();
+        let (Some(first), Some(last)) = (stops.first(), stops.last()) else {
+            return LinearRgba::BLACK;
+        };
+
+        let t = t.rem_euclid(1.0);
+        let next_index = stops.partition_point(|stop| stop.position <= t);
+
+        // the stops before the first and after the last are in the neighbouring cycles
+        let (previous_position, previous) = match next_index {
+            0 => (last.position - 1.0, last),
+            i => (stops[i - 1].position, &stops[i - 1]),
+        };
+        let (next_position, next) = match stops.get(next_index) {
+            Some(stop) => (stop.position, stop),
+            None => (first.position + 1.0, first),
+        };
+
+        let fraction = (t - previous_position) / (next_position - previous_position).max(1e-6);
+        previous
+            .color
+            .mix(&next.color, self.interpolation.apply(fraction))
+    }
+
+    /// Adds a stop in the middle of the largest gap between two stops
+    pub fn add_stop(&mut self) {
+        if self.stops.len() >= MAX_COLOR_STOPS {
+            return;
+        }
+
+        let stops = self.sorted_stops();
+        let position = match stops.as_slice() {
+            [] => 0.0,
+            [stop] => (stop.position + 0.5).fract(),
+            _ => {
+                // the gap that wraps around from the last stop to the first
+                let wrapping_gap = (stops[stops.len() - 1].position, stops[0].position + 1.0);
+
+                let (start, end) = stops
+                    .windows(2)
+                    .map(|pair| (pair[0].position, pair[1].position))
+                    .chain([wrapping_gap])
+                    .max_by(|a, b| (a.1 - a.0).total_cmp(&(b.1 - b.0)))
+                    .unwrap_or(wrapping_gap);
+
+                ((start + end) / 2.0).fract()
+            }
+        };
+
+        self.stops.push(ColorStop {
+            position,
+            color: self.sample(position),
+        });
+    }
+}
+
+/// How the color changes between two stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Eases in and out of every stop
+    Smooth,
+    /// No interpolation, every stop keeps its color until the next one
+    Constant,
+}
+
+impl Interpolation {
+    pub const ALL: [Interpolation; 3] = [
+        Interpolation::Linear,
+        Interpolation::Smooth,
+        Interpolation::Constant,
+    ];
+
+    pub fn variant_str(self) -> &'static str {
+        match self {
+            Interpolation::Linear => "Linear",
+            Interpolation::Smooth => "Smooth",
+            Interpolation::Constant => "Constant",
+        }
+    }
+
+    // maps the fraction of the way between two stops to how much of the next color is used
+    fn apply(self, fraction: f32) -> f32 {
+        let fraction = fraction.clamp(0.0, 1.0);
+
+        match self {
+            Interpolation::Linear => fraction,
+            Interpolation::Smooth => fraction * fraction * (3.0 - 2.0 * fraction),
+            Interpolation::Constant => 0.0,
+        }
+    }
+}
+
+/// Built-in palettes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PalettePreset {
+    /// The hue wheel the fractal was colored with before palettes existed
+    Classic,
+    Ocean,
+    Fire,
+    Ice,
+    Grayscale,
+}
+
+impl PalettePreset {
+    pub const ALL: [PalettePreset; 5] = [
+        PalettePreset::Classic,
+        PalettePreset::Ocean,
+        PalettePreset::Fire,
+        PalettePreset::Ice,
+        PalettePreset::Grayscale,
+    ];
+
+    pub fn variant_str(self) -> &'static str {
+        match self {
+            PalettePreset::Classic => "Classic",
+            PalettePreset::Ocean => "Ocean",
+            PalettePreset::Fire => "Fire",
+            PalettePreset::Ice => "Ice",
+            PalettePreset::Grayscale => "Grayscale",
+        }
+    }
+
+    /// The preset the palette is identical to, if any
+    pub fn matching(palette: &Palette) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.palette() == *palette)
+    }
+
+    pub fn palette(self) -> Palette {
+        match self {
+            PalettePreset::Classic => Palette {
+                stops: vec![
+                    ColorStop::srgb(0.0, 255, 0, 0),
+                    ColorStop::srgb(1.0 / 6.0, 255, 255, 0),
+                    ColorStop::srgb(2.0 / 6.0, 0, 255, 0),
+                    ColorStop::srgb(3.0 / 6.0, 0, 255, 255),
+                    ColorStop::srgb(4.0 / 6.0, 0, 0, 255),
+                    ColorStop::srgb(5.0 / 6.0, 255, 0, 255),
+                ],
+                interpolation: Interpolation::Linear,
+                cycle_length: 100.0,
+                offset: 0.6,
+            },
+            PalettePreset::Ocean => Palette {
+                stops: vec![
+                    ColorStop::srgb(0.0, 0, 7, 100),
+                    ColorStop::srgb(0.16, 32, 107, 203),
+                    ColorStop::srgb(0.42, 237, 255, 255),
+                    ColorStop::srgb(0.6425, 255, 170, 0),
+                    ColorStop::srgb(0.8575, 0, 2, 0),
+                ],
+                interpolation: Interpolation::Smooth,
+                cycle_length: 64.0,
+                offset: 0.0,
+            },
+            PalettePreset::Fire => Palette {
+                stops: vec![
+                    ColorStop::srgb(0.0, 0, 0, 0),
+                    ColorStop::srgb(0.3, 160, 16, 0),
+                    ColorStop::srgb(0.55, 255, 120, 0),
+                    ColorStop::srgb(0.75, 255, 220, 60),
+                    ColorStop::srgb(0.9, 255, 255, 230),
+                ],
+                interpolation: Interpolation::Linear,
+                cycle_length: 80.0,
+                offset: 0.0,
+            },
+            PalettePreset::Ice => Palette {
+                stops: vec![
+                    ColorStop::srgb(0.0, 2, 8, 32),
+                    ColorStop::srgb(0.35, 20, 70, 160),
+                    ColorStop::srgb(0.65, 110, 200, 240),
+                    ColorStop::srgb(0.85, 240, 250, 255),
+                ],
+                interpolation: Interpolation::Smooth,
+                cycle_length: 80.0,
+                offset: 0.0,
+            },
+            PalettePreset::Grayscale => Palette {
+                stops: vec![
+                    ColorStop::srgb(0.0, 0, 0, 0),
+                    ColorStop::srgb(0.5, 255, 255, 255),
+                ],
+                interpolation: Interpolation::Smooth,
+                cycle_length: 50.0,
+                offset: 0.0,
+            },
+        }
+    }
+}
@@ -1,18 +1,176 @@
-#[derive(Debug, Clone, Copy, PartialEq)]
+use std::f64::consts::TAU;
+
+use bevy::{ecs::system::SystemParam, math::DVec2, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use super::Fractal;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParametersPlugin;
+
+impl Plugin for ParametersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ParameterTime>()
+            .init_resource::<ParameterCursor>()
+            .add_systems(
+                PreUpdate,
+                advance_parameter_time.in_set(ParameterInputSystemSet),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Parameter {
     Value(f64),
     PixelX,
     PixelY,
+    /// Distance of the pixel from the origin
+    PixelRadius,
+    /// Angle of the pixel around the origin in radians, between -π and π
+    PixelAngle,
+    /// One part of 1 divided by the pixel, see [`ComplexPart`]
+    InversePixel,
+    /// One part of the pixel transformed by [`PixelTransform`], see [`ComplexPart`]
+    TransformedPixel,
+    /// Position of the cursor over the fractal, see [`ParameterCursor`]
+    CursorX,
+    CursorY,
+    /// Grows linearly with time, starting at `phase`
+    Time {
+        speed: f64,
+        phase: f64,
+    },
+    /// Oscillates around `center`, `frequency` is in cycles per second and `phase` in radians
+    Sine {
+        amplitude: f64,
+        frequency: f64,
+        phase: f64,
+        center: f64,
+    },
+    /// One part of a point moving around a circle, the real part is the cosine
+    /// and the imaginary part the sine, see [`ComplexPart`]
+    Circle {
+        center: f64,
+        radius: f64,
+        frequency: f64,
+        phase: f64,
+    },
 }
 
 impl Parameter {
+    /// Every kind of parameter, with its default settings
+    pub const ALL: [Parameter; 12] = [
+        Parameter::Value(0.0),
+        Parameter::PixelX,
+        Parameter::PixelY,
+        Parameter::PixelRadius,
+        Parameter::PixelAngle,
+        Parameter::InversePixel,
+        Parameter::TransformedPixel,
+        Parameter::CursorX,
+        Parameter::CursorY,
+        Parameter::Time {
+            speed: 0.1,
+            phase: 0.0,
+        },
+        Parameter::Sine {
+            amplitude: 0.5,
+            frequency: 0.1,
+            phase: 0.0,
+            center: 0.0,
+        },
+        Parameter::Circle {
+            center: 0.0,
+            radius: 0.7885,
+            frequency: 0.05,
+            phase: 0.0,
+        },
+    ];
+
     pub fn variant_str(self) -> &'static str {
         match self {
             Parameter::Value(_) => "Constant",
             Parameter::PixelX => "X coordinate",
             Parameter::PixelY => "Y coordinate",
+            Parameter::PixelRadius => "Pixel radius",
+            Parameter::PixelAngle => "Pixel angle",
+            Parameter::InversePixel => "Inverse pixel",
+            Parameter::TransformedPixel => "Transformed pixel",
+            Parameter::CursorX => "Cursor X",
+            Parameter::CursorY => "Cursor Y",
+            Parameter::Time { .. } => "Time",
+            Parameter::Sine { .. } => "Sine wave",
+            Parameter::Circle { .. } => "Circle",
         }
     }
+
+    /// Whether the parameter changes with time
+    pub fn is_animated(self) -> bool {
+        matches!(
+            self,
+            Parameter::Time { .. } | Parameter::Sine { .. } | Parameter::Circle { .. }
+        )
+    }
+
+    /// Whether the parameter is different for every pixel
+    pub fn depends_on_pixel(self) -> bool {
+        matches!(
+            self,
+            Parameter::PixelX
+                | Parameter::PixelY
+                | Parameter::PixelRadius
+                | Parameter::PixelAngle
+                | Parameter::InversePixel
+                | Parameter::TransformedPixel
+        )
+    }
+
+    /// Whether the parameter changes with the cursor position
+    pub fn follows_cursor(self) -> bool {
+        matches!(self, Parameter::CursorX | Parameter::CursorY)
+    }
+
+    /// The value of the parameter for the given inputs,
+    /// `None` if it depends on the pixel instead
+    pub fn value_at(self, part: ComplexPart, inputs: ParameterInputs) -> Option<f64> {
+        let time = inputs.time;
+        match self {
+            Parameter::Value(value) => Some(value),
+            Parameter::PixelX
+            | Parameter::PixelY
+            | Parameter::PixelRadius
+            | Parameter::PixelAngle
+            | Parameter::InversePixel
+            | Parameter::TransformedPixel => None,
+            Parameter::CursorX => Some(inputs.cursor.x),
+            Parameter::CursorY => Some(inputs.cursor.y),
+            Parameter::Time { speed, phase } => Some(phase + speed * time),
+            Parameter::Sine {
+                amplitude,
+                frequency,
+                phase,
+                center,
+            } => Some(center + amplitude * (TAU * frequency * time + phase).sin()),
+            Parameter::Circle {
+                center,
+                radius,
+                frequency,
+                phase,
+            } => {
+                let angle = TAU * frequency * time + phase;
+                let offset = match part {
+                    ComplexPart::Real => angle.cos(),
+                    ComplexPart::Imaginary => angle.sin(),
+                };
+                Some(center + radius * offset)
+            }
+        }
+    }
+
+    /// The parameter as a constant with its value for the given inputs
+    pub fn frozen(self, part: ComplexPart, inputs: ParameterInputs) -> Self {
+        self.value_at(part, inputs).map_or(self, Parameter::Value)
+    }
 }
 
 impl Default for Parameter {
@@ -21,8 +179,124 @@ impl Default for Parameter {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
 pub struct ComplexParameter {
     pub real: Parameter,
     pub imaginary: Parameter,
 }
+
+impl ComplexParameter {
+    /// The coordinates of the pixel
+    pub const PIXEL: Self = Self {
+        real: Parameter::PixelX,
+        imaginary: Parameter::PixelY,
+    };
+
+    pub fn depends_on_pixel(self) -> bool {
+        self.real.depends_on_pixel() || self.imaginary.depends_on_pixel()
+    }
+
+    pub fn is_animated(self) -> bool {
+        self.real.is_animated() || self.imaginary.is_animated()
+    }
+
+    pub fn follows_cursor(self) -> bool {
+        self.real.follows_cursor() || self.imaginary.follows_cursor()
+    }
+
+    /// The parameter as a constant with its value for the given inputs
+    pub fn frozen(self, inputs: ParameterInputs) -> Self {
+        Self {
+            real: self.real.frozen(ComplexPart::Real, inputs),
+            imaginary: self.imaginary.frozen(ComplexPart::Imaginary, inputs),
+        }
+    }
+}
+
+/// Affine transformation of the pixel coordinates, used by [`Parameter::TransformedPixel`]
+///
+/// The transformed pixel is `x_axis * x + y_axis * y + offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PixelTransform {
+    /// Where the x axis ends up, the first column of the matrix
+    pub x_axis: DVec2,
+    /// Where the y axis ends up, the second column of the matrix
+    pub y_axis: DVec2,
+    pub offset: DVec2,
+}
+
+impl Default for PixelTransform {
+    fn default() -> Self {
+        Self {
+            x_axis: DVec2::X,
+            y_axis: DVec2::Y,
+            offset: DVec2::ZERO,
+        }
+    }
+}
+
+/// Which part of a complex number a parameter is,
+/// parameters that aren't part of one count as real
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComplexPart {
+    Real,
+    Imaginary,
+}
+
+/// Time in seconds that animated parameters are evaluated at
+#[derive(Debug, Clone, Copy, Default, PartialEq, Resource)]
+pub struct ParameterTime {
+    pub seconds: f64,
+    /// Stops the time from advancing, the animation can still be moved by hand
+    pub paused: bool,
+}
+
+/// World space position of the cursor, used by cursor parameters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Resource)]
+pub struct ParameterCursor {
+    pub position: DVec2,
+    /// Keeps the position from following the cursor
+    pub frozen: bool,
+}
+
+/// Everything besides the pixel that parameters can depend on
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ParameterInputs {
+    pub time: f64,
+    pub cursor: DVec2,
+}
+
+/// Reads the current [`ParameterInputs`] from their resources
+#[derive(SystemParam)]
+pub struct CurrentParameterInputs<'w> {
+    time: Res<'w, ParameterTime>,
+    cursor: Res<'w, ParameterCursor>,
+}
+
+impl CurrentParameterInputs<'_> {
+    pub fn get(&self) -> ParameterInputs {
+        ParameterInputs {
+            time: self.time.seconds,
+            cursor: self.cursor.position,
+        }
+    }
+
+    /// Whether the inputs the fractal uses changed since the system last ran
+    pub fn changed_for(&self, fractal: &Fractal) -> bool {
+        (self.time.is_changed() && fractal.is_animated())
+            || (self.cursor.is_changed() && fractal.follows_cursor())
+    }
+}
+
+/// Updates [`ParameterTime`] and [`ParameterCursor`], can be given run conditions to hold them
+/// still, for example while every tile of an export has to show the same moment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemSet)]
+pub struct ParameterInputSystemSet;
+
+pub fn advance_parameter_time(mut parameter_time: ResMut<ParameterTime>, time: Res<Time<Real>>) {
+    if parameter_time.paused {
+        return;
+    }
+    parameter_time.seconds += time.delta_secs_f64();
+}
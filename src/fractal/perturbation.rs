@@ -0,0 +1,387 @@
+//! Deep zoom using perturbation theory.
+//!
+//! The orbit of a single reference point near the center of the screen is calculated on the CPU
+//! with arbitrary precision. The shader then only iterates the difference between the orbit
+//! of each pixel and the reference orbit, which is small enough to be represented with
+//! regular floats even when the coordinates themselves aren't.
+
+use super::{
+    coloring::TrapShape,
+    formula::Formula,
+    material::{update_fractal_material, DoubleSingle},
+    parameters::{ComplexParameter, ComplexPart, Parameter, ParameterInputs},
+    precise::{to_fbig, PreciseVec2},
+    precision::Precision,
+    Fractal,
+};
+use bevy::{
+    math::{dvec2, DVec2},
+    prelude::*,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+use dashu_float::FBig;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+// the reference orbit is recalculated once the center of the screen moves this far from it,
+// measured in the same units as `Fractal::scale`
+const MAX_REFERENCE_DISTANCE: f64 = 4.0;
+// the orbit is calculated with this many bits more than needed at the current scale,
+// so that it doesn't have to be recalculated every time the user zooms in a little
+const ORBIT_EXTRA_PRECISION_BITS: usize = 32;
+// highest integer exponent supported by the perturbation shader
+const MAX_PERTURBATION_EXPONENT: f64 = 16.0;
+// below this scale the differences between pixels get too close to the smallest f32, about 1e-38
+const MIN_SINGLE_PRECISION_SCALE: f64 = 1e-30;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PerturbationPlugin;
+
+impl Plugin for PerturbationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            (request_reference_orbit, receive_reference_orbit)
+                .chain()
+                .before(update_fractal_material::<f32>)
+                .before(update_fractal_material::<f64>)
+                .before(update_fractal_material::<DoubleSingle>),
+        );
+    }
+}
+
+/// Whether the fractal can be rendered with perturbation
+///
+/// Only the Mandelbrot and Tricorn formulas with integer exponents are supported,
+/// since the difference between two orbits can't be calculated precisely for the others.
+/// The starting point of the orbit can only be constant or the pixel itself. Animated ones would have
+/// to be recalculated every frame, and the differences between pixels are only known for the pixel.
+/// For the same reason an orbit trap in use can't depend on the pixel at all.
+pub fn perturbation_supported(fractal: &Fractal) -> bool {
+    let formula_supported = matches!(fractal.formula, Formula::Mandelbrot | Formula::Tricorn);
+    let start_supported = [fractal.initial_z, fractal.c]
+        .iter()
+        .flat_map(|param| [param.real, param.imaginary])
+        .all(|param| {
+            matches!(
+                param,
+                Parameter::Value(_) | Parameter::PixelX | Parameter::PixelY
+            )
+        });
+
+    let trap = &fractal.orbit_trap;
+    let trap_supported = trap.shape == TrapShape::None
+        || ![
+            trap.center.real,
+            trap.center.imaginary,
+            trap.radius,
+            trap.angle,
+        ]
+        .into_iter()
+        .any(Parameter::depends_on_pixel);
+
+    formula_supported && integer_exponent(fractal.p).is_some() && start_supported && trap_supported
+}
+
+/// Whether the view is too deep for perturbation in single or emulated precision
+///
+/// The differences from the reference orbit are f32 unless the precision is double,
+/// so deeper zooms switch to double precision, see [`Fractal::shader_precision`].
+pub fn needs_double_precision(fractal: &Fractal) -> bool {
+    fractal.use_perturbation
+        && perturbation_supported(fractal)
+        && fractal.precision != Precision::Double
+        && fractal.scale < MIN_SINGLE_PRECISION_SCALE
+}
+
+fn integer_exponent(p: ComplexParameter) -> Option<u32> {
+    let (Parameter::Value(real), Parameter::Value(0.0)) = (p.real, p.imaginary) else {
+        return None;
+    };
+
+    let supported = real.fract() == 0.0 && (2.0..=MAX_PERTURBATION_EXPONENT).contains(&real);
+    supported.then_some(real as u32)
+}
+
+/// The orbit of the reference point the pixels of the fractal are calculated relative to
+#[derive(Debug, Clone, Default, Component)]
+pub struct ReferenceOrbit {
+    /// The point the orbit was calculated for
+    pub center: PreciseVec2,
+    /// The iterated values rounded to f64, ending with the first escaped value
+    pub points: Vec<DVec2>,
+    /// Incremented every time a new orbit is calculated
+    pub generation: u64,
+    settings: OrbitSettings,
+    precision: usize,
+}
+
+impl ReferenceOrbit {
+    /// Whether the orbit belongs to the same fractal and can be used to render it
+    ///
+    /// The orbit can still be used if the view or the iteration count has changed,
+    /// pixels will just get rebased to the start of the orbit more often
+    pub fn is_usable_for(&self, fractal: &Fractal) -> bool {
+        !self.points.is_empty() && self.settings.same_fractal(&OrbitSettings::new(fractal))
+    }
+
+    /// Whether the fractal can be rendered exactly with this orbit, or doesn't need one
+    ///
+    /// Unlike [`Self::is_usable_for`], the orbit has to be the one that would be calculated
+    /// for the fractal as it is now
+    pub fn is_ready_for(&self, fractal: &Fractal) -> bool {
+        if !fractal.use_perturbation || !perturbation_supported(fractal) {
+            return true;
+        }
+        !self.points.is_empty() && self.is_up_to_date(fractal, &OrbitSettings::new(fractal))
+    }
+
+    fn is_up_to_date(&self, fractal: &Fractal, settings: &OrbitSettings) -> bool {
+        let distance = fractal.offset.difference(&self.center).abs().max_element();
+        let precision = fractal.offset.precision_for_scale(fractal.scale);
+
+        self.settings == *settings
+            && distance <= MAX_REFERENCE_DISTANCE * fractal.scale
+            && precision <= self.precision
+    }
+}
+
+#[derive(Debug, Default, Component)]
+pub struct ReferenceOrbitTask {
+    task: Option<Task<Option<ReferenceOrbit>>>,
+    // the orbit that is being calculated, without the points
+    pending: Option<ReferenceOrbit>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ReferenceOrbitTask {
+    fn cancel(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.task = None;
+        self.pending = None;
+    }
+}
+
+// everything that affects the reference orbit, except for its center
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct OrbitSettings {
+    formula: Formula,
+    initial_z: ComplexParameter,
+    c: ComplexParameter,
+    p: ComplexParameter,
+    iteration_count: u32,
+    escape_radius: f64,
+}
+
+impl OrbitSettings {
+    fn new(fractal: &Fractal) -> Self {
+        Self {
+            formula: fractal.formula,
+            initial_z: fractal.initial_z,
+            c: fractal.c,
+            p: fractal.p,
+            iteration_count: fractal.iteration_count,
+            escape_radius: fractal.escape_radius,
+        }
+    }
+
+    fn same_fractal(&self, other: &Self) -> bool {
+        self.formula == other.formula
+            && self.initial_z == other.initial_z
+            && self.c == other.c
+            && self.p == other.p
+    }
+}
+
+pub fn request_reference_orbit(
+    mut fractals: Query<(&Fractal, &ReferenceOrbit, &mut ReferenceOrbitTask), Changed<Fractal>>,
+) {
+    for (fractal, orbit, mut task) in fractals.iter_mut() {
+        if !fractal.use_perturbation || !perturbation_supported(fractal) {
+            task.cancel();
+            continue;
+        }
+
+        let settings = OrbitSettings::new(fractal);
+        if orbit.is_up_to_date(fractal, &settings) {
+            task.cancel();
+            continue;
+        }
+        if let Some(pending) = &task.pending {
+            if pending.is_up_to_date(fractal, &settings) {
+                continue;
+            }
+        }
+        task.cancel();
+
+        let precision =
+            fractal.offset.precision_for_scale(fractal.scale) + ORBIT_EXTRA_PRECISION_BITS;
+        let center = fractal.offset.clone().with_precision(precision);
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let pending = ReferenceOrbit {
+            center,
+            points: Vec::new(),
+            generation: orbit.generation + 1,
+            settings,
+            precision,
+        };
+
+        let mut new_orbit = pending.clone();
+        let task_cancelled = cancelled.clone();
+        let new_task = AsyncComputeTaskPool::get().spawn(async move {
+            new_orbit.points = calculate_orbit(
+                &new_orbit.center,
+                &new_orbit.settings,
+                new_orbit.precision,
+                &task_cancelled,
+            )?;
+            Some(new_orbit)
+        });
+        debug!(precision, "Started calculating reference orbit");
+
+        *task = ReferenceOrbitTask {
+            task: Some(new_task),
+            pending: Some(pending),
+            cancelled,
+        };
+    }
+}
+
+pub fn receive_reference_orbit(
+    mut fractals: Query<(&mut ReferenceOrbit, &mut ReferenceOrbitTask)>,
+) {
+    for (mut orbit, mut task) in fractals.iter_mut() {
+        let Some(running_task) = task.task.as_mut() else {
+            continue;
+        };
+        let Some(result) = block_on(future::poll_once(running_task)) else {
+            continue;
+        };
+
+        task.task = None;
+        task.pending = None;
+
+        if let Some(new_orbit) = result {
+            debug!(length = new_orbit.points.len(), "Received reference orbit");
+            *orbit = new_orbit;
+        }
+    }
+}
+
+// returns None if cancelled
+fn calculate_orbit(
+    center: &PreciseVec2,
+    settings: &OrbitSettings,
+    precision: usize,
+    cancelled: &AtomicBool,
+) -> Option<Vec<DVec2>> {
+    let p = integer_exponent(settings.p).unwrap_or(2);
+    let r_squared = settings.escape_radius * settings.escape_radius;
+
+    let c = BigComplex::from_parameter(settings.c, center, precision);
+    let mut z = BigComplex::from_parameter(settings.initial_z, center, precision);
+
+    let mut points = Vec::with_capacity(settings.iteration_count as usize + 1);
+    points.push(z.as_dvec2());
+
+    // the shader needs at least 2 points to take a step
+    for _ in 0..settings.iteration_count.max(1) {
+        if cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        if settings.formula == Formula::Tricorn {
+            z = z.conj();
+        }
+        z = z.pow(p).add(&c);
+
+        let point = z.as_dvec2();
+        points.push(point);
+
+        if point.is_nan() || point.length_squared() > r_squared {
+            break;
+        }
+    }
+
+    Some(points)
+}
+
+#[derive(Debug, Clone)]
+struct BigComplex {
+    re: FBig,
+    im: FBig,
+}
+
+impl BigComplex {
+    // value of the parameter at the center of the screen
+    fn from_parameter(param: ComplexParameter, center: &PreciseVec2, precision: usize) -> Self {
+        // other parameters aren't supported, see `perturbation_supported`
+        let component = |param: Parameter, part| {
+            let value = match param {
+                Parameter::PixelX => center.x.clone(),
+                Parameter::PixelY => center.y.clone(),
+                _ => to_fbig(
+                    param
+                        .value_at(part, ParameterInputs::default())
+                        .unwrap_or(0.0),
+                ),
+            };
+            value.with_precision(precision).value()
+        };
+
+        Self {
+            re: component(param.real, ComplexPart::Real),
+            im: component(param.imaginary, ComplexPart::Imaginary),
+        }
+    }
+
+    fn as_dvec2(&self) -> DVec2 {
+        dvec2(self.re.to_f64().value(), self.im.to_f64().value())
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        Self {
+            re: &self.re + &other.re,
+            im: &self.im + &other.im,
+        }
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        Self {
+            re: &self.re * &other.re - &self.im * &other.im,
+            im: &self.re * &other.im + &self.im * &other.re,
+        }
+    }
+
+    fn square(&self) -> Self {
+        Self {
+            re: self.re.sqr() - self.im.sqr(),
+            im: {
+                let product = &self.re * &self.im;
+                &product + &product
+            },
+        }
+    }
+
+    fn pow(&self, p: u32) -> Self {
+        if p == 2 {
+            return self.square();
+        }
+
+        let mut out = self.clone();
+        for _ in 1..p {
+            out = out.mul(self);
+        }
+        out
+    }
+
+    fn conj(self) -> Self {
+        Self {
+            re: self.re,
+            im: -self.im,
+        }
+    }
+}
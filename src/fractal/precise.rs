@@ -0,0 +1,199 @@
+//! Arbitrary precision numbers for positions that f64 can't represent at deep zoom levels.
+
+use std::{
+    f64::consts::{LOG10_2, LOG2_10},
+    str::FromStr,
+};
+
+use bevy::math::{dvec2, DVec2};
+use dashu_float::{round::mode::HalfAway, DBig, FBig};
+use serde::{Deserialize, Serialize};
+
+// decimal digits needed to tell apart any two f64
+const F64_DIGITS: usize = 17;
+
+// bits of precision kept on top of what's needed to tell apart points one `scale` apart,
+// this covers pixels being much smaller than `scale`, and then some
+const EXTRA_PRECISION_BITS: usize = 64;
+
+/// A 2D vector with arbitrary precision components
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct PreciseVec2 {
+    #[serde(with = "decimal_string")]
+    pub x: FBig,
+    #[serde(with = "decimal_string")]
+    pub y: FBig,
+}
+
+impl PreciseVec2 {
+    pub const ZERO: Self = Self {
+        x: FBig::ZERO,
+        y: FBig::ZERO,
+    };
+
+    /// Rounds the vector to f64
+    pub fn as_dvec2(&self) -> DVec2 {
+        dvec2(self.x.to_f64().value(), self.y.to_f64().value())
+    }
+
+    /// Returns `self + delta` with enough precision to represent the fractal at the given scale
+    pub fn offset_by(&self, delta: DVec2, scale: f64) -> Self {
+        // the precision has to be raised before adding, otherwise the delta gets rounded away
+        let moved = self.as_dvec2() + delta;
+        let x = with_precision(self.x.clone(), precision_for_scale(moved.x, scale));
+        let y = with_precision(self.y.clone(), precision_for_scale(moved.y, scale));
+
+        Self {
+            x: x + to_fbig(delta.x),
+            y: y + to_fbig(delta.y),
+        }
+    }
+
+    /// Returns `self - other` rounded to f64
+    pub fn difference(&self, other: &Self) -> DVec2 {
+        let x = &self.x - &other.x;
+        let y = &self.y - &other.y;
+
+        dvec2(x.to_f64().value(), y.to_f64().value())
+    }
+
+    /// Returns the vector with the precision of both components set to `precision` bits
+    pub fn with_precision(self, precision: usize) -> Self {
+        Self {
+            x: with_precision(self.x, precision),
+            y: with_precision(self.y, precision),
+        }
+    }
+
+    /// Precision of the vector in bits
+    pub fn precision(&self) -> usize {
+        usize::max(self.x.precision(), self.y.precision())
+    }
+
+    /// Number of bits needed to represent this vector at the given scale
+    pub fn precision_for_scale(&self, scale: f64) -> usize {
+        let value = self.as_dvec2();
+        usize::max(
+            precision_for_scale(value.x, scale),
+            precision_for_scale(value.y, scale),
+        )
+    }
+}
+
+impl From<DVec2> for PreciseVec2 {
+    fn from(value: DVec2) -> Self {
+        Self {
+            x: to_fbig(value.x),
+            y: to_fbig(value.y),
+        }
+    }
+}
+
+/// Converts an f64 into an arbitrary precision float, non-finite values become zero
+pub fn to_fbig(value: f64) -> FBig {
+    FBig::try_from(value).unwrap_or_default()
+}
+
+/// Error from parsing a decimal number
+pub type ParseError = <DBig as FromStr>::Err;
+
+/// Parses a decimal number, keeping all of its digits
+///
+/// The precision is the most bits the digits can tell apart,
+/// so the numbers [`PreciseVec2`] writes read back exactly.
+pub fn parse_decimal(text: &str) -> Result<FBig, ParseError> {
+    let decimal: DBig = text.parse()?;
+    // short numbers still need at least the precision of f64
+    let digits = significant_digits(text).max(F64_DIGITS);
+    let precision = ((digits - 1) as f64 * LOG2_10).floor() as usize;
+
+    Ok(decimal
+        .with_base_and_precision::<2>(precision)
+        .value()
+        .with_rounding())
+}
+
+// number of digits after the leading zeros, including trailing zeros
+fn significant_digits(text: &str) -> usize {
+    let mantissa = text.split(['e', 'E']).next().unwrap_or_default();
+    mantissa
+        .chars()
+        .filter(char::is_ascii_digit)
+        .skip_while(|&c| c == '0')
+        .count()
+}
+
+fn with_precision(value: FBig, precision: usize) -> FBig {
+    value.with_precision(precision).value()
+}
+
+/// Number of bits needed to represent `value` at the given scale
+fn precision_for_scale(value: f64, scale: f64) -> usize {
+    // clamped to the exponent range of f64 in case of infinities and zeros
+    let integer_bits = value.abs().log2().ceil().clamp(0.0, 1024.0);
+    let fraction_bits = (-scale.log2()).ceil().clamp(0.0, 1074.0);
+
+    (integer_bits + fraction_bits) as usize + EXTRA_PRECISION_BITS
+}
+
+// the components are stored as decimal strings with all of their digits,
+// since f64 would lose the precision of deep zooms
+mod decimal_string {
+    use super::*;
+    use serde::{de::Error, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &FBig, serializer: S) -> Result<S::Ok, S::Error> {
+        // `to_decimal` rounds to fewer digits than needed to get the same value back
+        let digits = (value.precision() as f64 * LOG10_2).ceil() as usize + 1;
+        let mut text = value
+            .clone()
+            .with_rounding::<HalfAway>()
+            .with_base_and_precision::<10>(digits)
+            .value()
+            .to_string();
+
+        // the trailing zeros tell `parse_decimal` how precise the number is
+        let missing_zeros = digits.saturating_sub(significant_digits(&text));
+        if value.repr().is_zero() || missing_zeros == 0 {
+            return serializer.serialize_str(&text);
+        }
+        if !text.contains('.') {
+            text.push('.');
+        }
+        text.extend(std::iter::repeat_n('0', missing_zeros));
+        serializer.serialize_str(&text)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<FBig, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        parse_decimal(&text).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimal_strings_round_trip() {
+        let mut scale = 1.0;
+        let mut value = PreciseVec2::ZERO;
+
+        // moving by smaller and smaller steps uses up more and more bits
+        for i in 0..200 {
+            scale *= 0.7;
+            let step = dvec2(0.37 + (i % 7) as f64 * 0.11, -0.29 - (i % 5) as f64 * 0.13);
+            value = value.offset_by(step * scale, scale);
+
+            let text = ron::to_string(&value).unwrap();
+            let read: PreciseVec2 = ron::from_str(&text).unwrap();
+            assert_eq!(read, value, "{text}");
+        }
+    }
+
+    #[test]
+    fn short_decimals_have_f64_precision() {
+        let value = parse_decimal("0.1").unwrap();
+        assert_eq!(value.to_f64().value(), 0.1);
+    }
+}
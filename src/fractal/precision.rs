@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+/// Floating point precision used by the shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Precision {
+    /// Native f32
+    #[default]
+    Single,
+    /// f64 emulated with a pair of f32s, about 48 bits of mantissa
+    ///
+    /// Works on every device, but only formulas with positive integer exponents use it,
+    /// the rest fall back to f32
+    DoubleSingle,
+    /// Native f64, only available on devices that support it
+    Double,
+}
+
+impl Precision {
+    pub const ALL: [Precision; 3] = [
+        Precision::Single,
+        Precision::DoubleSingle,
+        Precision::Double,
+    ];
+
+    pub fn variant_str(self) -> &'static str {
+        match self {
+            Precision::Single => "Single (f32)",
+            Precision::DoubleSingle => "Emulated (df64)",
+            Precision::Double => "Double (f64)",
+        }
+    }
+
+    /// The precision that will actually be used, given whether the device supports f64
+    pub fn supported(self, f64_supported: bool) -> Self {
+        if self == Precision::Double && !f64_supported {
+            Precision::DoubleSingle
+        } else {
+            self
+        }
+    }
+}
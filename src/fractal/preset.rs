@@ -0,0 +1,381 @@
+//! Saving fractals to preset files and loading them back.
+//!
+//! A preset is the whole [`Fractal`] along with the version of the format it was saved with,
+//! written in RON or TOML depending on the extension of the file.
+
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{parameters::CurrentParameterInputs, Fractal, OffscreenFractal};
+use crate::{ui::UiSystemSet, utils::show_error_dialog};
+
+/// Version of the preset format
+///
+/// Has to be bumped when a change to [`Fractal`] would make older presets load incorrectly,
+/// fields that are added with a sensible default don't need that.
+pub const PRESET_VERSION: u32 = 1;
+
+/// Extensions of the files presets can be saved to
+pub const PRESET_EXTENSIONS: [&str; 2] = ["ron", "toml"];
+
+// the presets are embedded in the executable, so they don't depend on the assets folder
+macro_rules! builtin_preset {
+    ($name:literal, $file:literal) => {
+        BuiltinPreset {
+            name: $name,
+            source: include_str!(concat!("../../assets/presets/", $file, ".ron")),
+        }
+    };
+}
+
+/// Presets that ship with Fractality, in the order they are listed in
+pub const BUILTIN_PRESETS: [BuiltinPreset; 13] = [
+    builtin_preset!("Mandelbrot Set", "mandelbrot_set"),
+    builtin_preset!("Seahorse Valley", "seahorse_valley"),
+    builtin_preset!("Elephant Valley", "elephant_valley"),
+    builtin_preset!("Triple Spiral Valley", "triple_spiral_valley"),
+    builtin_preset!("Mini Mandelbrot", "mini_mandelbrot"),
+    builtin_preset!("Julia: Douady Rabbit", "douady_rabbit"),
+    builtin_preset!("Julia: Dendrite", "dendrite"),
+    builtin_preset!("Julia: Siegel Disk", "siegel_disk"),
+    builtin_preset!("Julia: San Marco", "san_marco"),
+    builtin_preset!("Julia: Dragon", "dragon"),
+    builtin_preset!("Burning Ship", "burning_ship"),
+    builtin_preset!("Burning Ship Armada", "burning_ship_armada"),
+    builtin_preset!("Newton Fractal", "newton_fractal"),
+];
+
+/// A preset that ships with Fractality, see [`BUILTIN_PRESETS`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinPreset {
+    pub name: &'static str,
+    source: &'static str,
+}
+
+impl BuiltinPreset {
+    /// The fractal stored in the preset, settings the preset leaves out have their default values
+    pub fn fractal(&self) -> Fractal {
+        read_preset(self.source, PresetFormat::Ron).expect("built-in presets should be valid")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PresetPlugin;
+
+impl Plugin for PresetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<OpenPreset>()
+            .add_event::<SavePreset>()
+            .add_systems(Update, (open_preset, save_preset).after(UiSystemSet));
+    }
+}
+
+/// Replaces the fractal with the one in a preset file
+#[derive(Debug, Clone, Event)]
+pub struct OpenPreset {
+    pub path: PathBuf,
+}
+
+/// Saves the fractal to a preset file
+#[derive(Debug, Clone, Event)]
+pub struct SavePreset {
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetFormat {
+    Ron,
+    Toml,
+}
+
+impl PresetFormat {
+    /// The format of a file based on its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "ron" => Some(PresetFormat::Ron),
+            "toml" => Some(PresetFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+// borrows the fractal when saving
+#[derive(Debug, Serialize)]
+struct PresetRef<'a> {
+    version: u32,
+    fractal: &'a Fractal,
+}
+
+// the version is checked separately before reading the rest
+#[derive(Debug, Deserialize)]
+struct Preset {
+    fractal: Fractal,
+}
+
+// read first, so that presets from newer versions aren't parsed as the current one
+#[derive(Debug, Deserialize)]
+struct PresetVersion {
+    version: u32,
+}
+
+/// Writes the fractal as a preset in the given format
+pub fn write_preset(fractal: &Fractal, format: PresetFormat) -> Result<String, PresetError> {
+    let preset = PresetRef {
+        version: PRESET_VERSION,
+        fractal,
+    };
+
+    match format {
+        PresetFormat::Ron => Ok(ron::ser::to_string_pretty(&preset, default())?),
+        PresetFormat::Toml => Ok(toml::to_string_pretty(&preset)?),
+    }
+}
+
+/// Reads a preset in the given format
+pub fn read_preset(text: &str, format: PresetFormat) -> Result<Fractal, PresetError> {
+    let version = match format {
+        PresetFormat::Ron => ron::from_str::<PresetVersion>(text)?.version,
+        PresetFormat::Toml => toml::from_str::<PresetVersion>(text)?.version,
+    };
+    if version > PRESET_VERSION {
+        return Err(PresetError::NewerVersion(version));
+    }
+
+    let mut preset: Preset = match format {
+        PresetFormat::Ron => ron::from_str(text)?,
+        PresetFormat::Toml => toml::from_str(text)?,
+    };
+    preset.fractal.sanitize();
+    Ok(preset.fractal)
+}
+
+pub fn save_preset_file(fractal: &Fractal, path: &Path) -> Result<(), PresetError> {
+    let format = PresetFormat::from_path(path).ok_or(PresetError::UnknownFormat)?;
+    fs::write(path, write_preset(fractal, format)?)?;
+    Ok(())
+}
+
+pub fn load_preset_file(path: &Path) -> Result<Fractal, PresetError> {
+    let format = PresetFormat::from_path(path).ok_or(PresetError::UnknownFormat)?;
+    read_preset(&fs::read_to_string(path)?, format)
+}
+
+#[derive(Debug)]
+pub enum PresetError {
+    Io(io::Error),
+    /// The file is neither RON nor TOML
+    UnknownFormat,
+    /// The preset was saved by a newer version of Fractality
+    NewerVersion(u32),
+    Ron(ron::Error),
+    TomlRead(toml::de::Error),
+    TomlWrite(toml::ser::Error),
+}
+
+impl Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetError::Io(e) => write!(f, "{e}"),
+            PresetError::UnknownFormat => write!(f, "presets have to be .ron or .toml files"),
+            PresetError::NewerVersion(version) => write!(
+                f,
+                "the preset has version {version}, but only versions up to {PRESET_VERSION} are supported, \
+                try updating Fractality"
+            ),
+            PresetError::Ron(e) => write!(f, "{e}"),
+            PresetError::TomlRead(e) => write!(f, "{e}"),
+            PresetError::TomlWrite(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for PresetError {}
+
+impl From<io::Error> for PresetError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<ron::Error> for PresetError {
+    fn from(value: ron::Error) -> Self {
+        Self::Ron(value)
+    }
+}
+
+impl From<ron::error::SpannedError> for PresetError {
+    fn from(value: ron::error::SpannedError) -> Self {
+        Self::Ron(value.code)
+    }
+}
+
+impl From<toml::de::Error> for PresetError {
+    fn from(value: toml::de::Error) -> Self {
+        Self::TomlRead(value)
+    }
+}
+
+impl From<toml::ser::Error> for PresetError {
+    fn from(value: toml::ser::Error) -> Self {
+        Self::TomlWrite(value)
+    }
+}
+
+pub fn open_preset(
+    mut events: EventReader<OpenPreset>,
+    mut fractals: Query<&mut Fractal, Without<OffscreenFractal>>,
+) {
+    let Some(OpenPreset { path }) = events.read().last() else {
+        return;
+    };
+    let Ok(mut fractal) = fractals.get_single_mut() else {
+        return;
+    };
+
+    match load_preset_file(path) {
+        Ok(loaded) => {
+            info!(?path, "Opened preset");
+            *fractal = loaded;
+        }
+        Err(e) => show_error_dialog(
+            "Failed to open preset",
+            format!("Failed to open {}: {e}", path.display()),
+        ),
+    }
+}
+
+pub fn save_preset(
+    mut events: EventReader<SavePreset>,
+    fractals: Query<&Fractal, Without<OffscreenFractal>>,
+    inputs: CurrentParameterInputs,
+) {
+    let Some(SavePreset { path }) = events.read().last() else {
+        return;
+    };
+    let Ok(fractal) = fractals.get_single() else {
+        return;
+    };
+
+    // animated and cursor parameters are saved with the values they have now
+    match save_preset_file(&fractal.frozen(inputs.get()), path) {
+        Ok(()) => info!(?path, "Saved preset"),
+        Err(e) => show_error_dialog(
+            "Failed to save preset",
+            format!("Failed to save {}: {e}", path.display()),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::dvec2;
+
+    use super::*;
+    use crate::fractal::{
+        coloring::{ExteriorColoring, InteriorColoring, TrapShape},
+        formula::Formula,
+        palette::PalettePreset,
+        parameters::{ComplexParameter, Parameter, ParameterInputs},
+        precise::PreciseVec2,
+        precision::Precision,
+    };
+
+    // different from the default in every part that has its own serialization
+    fn non_default_fractal() -> Fractal {
+        let scale = 1.5e-20;
+        let mut fractal = Fractal {
+            formula: Formula::Tricorn,
+            iteration_count: 1234,
+            scale,
+            escape_radius: 4.0,
+            // more precise than an f64
+            offset: PreciseVec2::ZERO.offset_by(dvec2(-1.7490263, -0.0343711), scale),
+            initial_z: ComplexParameter {
+                real: Parameter::CursorX,
+                imaginary: Parameter::Value(0.25),
+            },
+            c: ComplexParameter {
+                real: Parameter::Sine {
+                    amplitude: 0.5,
+                    frequency: 0.2,
+                    phase: 1.0,
+                    center: -0.75,
+                },
+                imaginary: Parameter::Circle {
+                    center: 0.1,
+                    radius: 0.3,
+                    frequency: 0.05,
+                    phase: 0.0,
+                },
+            },
+            root_count: 5,
+            tolerance: 1e-5,
+            custom_formula: "sin(z) * c^-2 + p".into(),
+            precision: Precision::DoubleSingle,
+            use_perturbation: true,
+            palette: PalettePreset::Fire.palette(),
+            exterior_coloring: ExteriorColoring::StripeAverage,
+            boundary_thickness: 2.5,
+            stripe_density: 7,
+            interior_coloring: InteriorColoring::Period,
+            ..default()
+        };
+        fractal.roots[4] = ComplexParameter {
+            real: Parameter::Time {
+                speed: 0.5,
+                phase: 1.0,
+            },
+            imaginary: Parameter::InversePixel,
+        };
+        fractal.pixel_transform.offset = dvec2(0.5, -0.25);
+        fractal.orbit_trap.shape = TrapShape::Cross;
+        fractal.orbit_trap.angle = Parameter::PixelAngle;
+        fractal.lighting.enabled = true;
+        fractal.lighting.azimuth = 120.0;
+
+        fractal
+    }
+
+    #[test]
+    fn builtin_presets_are_valid() {
+        for preset in BUILTIN_PRESETS {
+            let result = read_preset(preset.source, PresetFormat::Ron);
+            assert!(result.is_ok(), "{}: {}", preset.name, result.unwrap_err());
+        }
+    }
+
+    #[test]
+    fn presets_round_trip() {
+        let fractal = non_default_fractal();
+
+        for format in [PresetFormat::Ron, PresetFormat::Toml] {
+            let text = write_preset(&fractal, format).unwrap();
+            let read = read_preset(&text, format).unwrap();
+            assert_eq!(read, fractal, "{format:?} preset:\n{text}");
+        }
+    }
+
+    #[test]
+    fn frozen_fractals_only_depend_on_the_pixel() {
+        let inputs = ParameterInputs {
+            time: 12.5,
+            cursor: dvec2(0.3, -0.4),
+        };
+        let frozen = non_default_fractal().frozen(inputs);
+
+        assert!(!frozen.is_animated());
+        assert!(!frozen.follows_cursor());
+        assert_eq!(frozen.initial_z.real, Parameter::Value(0.3));
+        assert_eq!(frozen.roots[4].real, Parameter::Value(1.0 + 0.5 * 12.5));
+        assert_eq!(frozen.roots[4].imaginary, Parameter::InversePixel);
+        assert_eq!(frozen.orbit_trap.angle, Parameter::PixelAngle);
+    }
+}
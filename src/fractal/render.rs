@@ -1,11 +1,21 @@
-use super::{material::FractalMaterial, Fractal};
+use super::{
+    histogram::{histogram_equalized, HISTOGRAM_BUFFER_SIZE},
+    lighting::lighting_enabled,
+    material::{DoubleSingle, EncodeShaderFloat, FractalMaterial, MaterialPass},
+    perturbation::ReferenceOrbit,
+    precision::Precision,
+    Fractal,
+};
 use crate::ui::UiSystemSet;
 use bevy::{
     asset::RenderAssetUsages,
     prelude::*,
     render::{
+        camera::camera_system,
         mesh::{MeshVertexAttribute, VertexAttributeValues},
         renderer::RenderDevice,
+        storage::ShaderStorageBuffer,
+        view::RenderLayers,
     },
 };
 use wgpu::{Features, PrimitiveTopology, VertexFormat};
@@ -19,9 +29,19 @@ pub struct FractalRenderPlugin;
 impl Plugin for FractalRenderPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(DoublePrecisionSupported(false));
+        app.init_resource::<PassBuffers>();
         app.add_systems(PreStartup, set_double_precision_supported);
         app.add_systems(PreUpdate, init_fractal_renderer);
-        app.add_systems(Update, swap_fractal_materials.after(UiSystemSet));
+        app.add_systems(
+            Update,
+            (swap_fractal_materials, show_passes).after(UiSystemSet),
+        );
+        app.add_systems(
+            PostUpdate,
+            resize_pass_buffers
+                .in_set(PassBufferSystemSet)
+                .after(camera_system::<OrthographicProjection>),
+        );
 
         let mut meshes = app.world_mut().resource_mut::<Assets<Mesh>>();
         meshes.insert(&FRACTAL_MESH_HANDLE, create_fractal_mesh());
@@ -35,6 +55,109 @@ pub struct DoublePrecisionSupported(pub bool);
 pub struct FractalRenderer {
     material_f32_handle: Handle<FractalMaterial<f32>>,
     material_f64_handle: Handle<FractalMaterial<f64>>,
+    material_df64_handle: Handle<FractalMaterial<DoubleSingle>>,
+}
+
+/// Buffers the fractal writes to for the passes drawn on top of it
+#[derive(Debug, Clone, Resource)]
+pub struct PassBuffers {
+    /// Smoothed iteration count of every pixel, see [`histogram`](super::histogram)
+    pub histogram_values: PixelBuffer,
+    /// The histogram of the values and its cumulative distribution
+    pub histogram: Handle<ShaderStorageBuffer>,
+    /// Height of every pixel, see [`lighting`](super::lighting)
+    pub heights: PixelBuffer,
+}
+
+impl FromWorld for PassBuffers {
+    fn from_world(world: &mut World) -> Self {
+        let mut buffers = world.resource_mut::<Assets<ShaderStorageBuffer>>();
+
+        Self {
+            histogram_values: PixelBuffer::new(&mut buffers),
+            histogram: buffers.add(ShaderStorageBuffer::with_size(
+                HISTOGRAM_BUFFER_SIZE,
+                RenderAssetUsages::RENDER_WORLD,
+            )),
+            heights: PixelBuffer::new(&mut buffers),
+        }
+    }
+}
+
+/// A storage buffer of f32s with room for every pixel of the viewport
+///
+/// Only has room for a single value while the pass that uses it isn't drawn
+#[derive(Debug, Clone)]
+pub struct PixelBuffer {
+    pub handle: Handle<ShaderStorageBuffer>,
+    value_count: u32,
+}
+
+impl PixelBuffer {
+    fn new(buffers: &mut Assets<ShaderStorageBuffer>) -> Self {
+        Self {
+            handle: buffers.add(Self::buffer(1)),
+            value_count: 1,
+        }
+    }
+
+    // returns whether the buffer was replaced
+    fn resize(&mut self, value_count: u32, buffers: &mut Assets<ShaderStorageBuffer>) -> bool {
+        let value_count = value_count.max(1);
+        if value_count == self.value_count {
+            return false;
+        }
+        debug!(value_count, "Resizing pixel buffer");
+
+        // a new buffer instead of replacing the old one, so that the materials
+        // can't get prepared with the old buffer before the new one is uploaded
+        self.handle = buffers.add(Self::buffer(value_count));
+        self.value_count = value_count;
+        true
+    }
+
+    fn buffer(value_count: u32) -> ShaderStorageBuffer {
+        ShaderStorageBuffer::with_size(
+            value_count as usize * size_of::<f32>(),
+            RenderAssetUsages::RENDER_WORLD,
+        )
+    }
+}
+
+/// Systems that replace the buffers in [`PassBuffers`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemSet)]
+pub struct PassBufferSystemSet;
+
+pub fn resize_pass_buffers(
+    fractals: Query<&Fractal>,
+    cameras: Query<&Camera>,
+    mut pass_buffers: ResMut<PassBuffers>,
+    mut buffers: ResMut<Assets<ShaderStorageBuffer>>,
+) {
+    // every view and fractal shares the buffers, exports and thumbnails are rendered by cameras
+    // other than the viewport
+    let pixel_count = cameras
+        .iter()
+        .filter(|camera| camera.is_active)
+        .filter_map(Camera::physical_viewport_size)
+        .map(|size| size.x * size.y)
+        .max()
+        .unwrap_or(1);
+    let count_if = |used| if used { pixel_count } else { 1 };
+
+    // only marked as changed when a buffer is actually replaced
+    let inner = pass_buffers.bypass_change_detection();
+    let replaced = inner.histogram_values.resize(
+        count_if(fractals.iter().any(histogram_equalized)),
+        &mut buffers,
+    ) | inner.heights.resize(
+        count_if(fractals.iter().any(lighting_enabled)),
+        &mut buffers,
+    );
+
+    if replaced {
+        pass_buffers.set_changed();
+    }
 }
 
 pub fn set_double_precision_supported(
@@ -49,22 +172,93 @@ pub fn set_double_precision_supported(
     info!(?supported, "Set DoublePrecisionSupported");
 }
 
+#[allow(clippy::type_complexity)]
 pub fn init_fractal_renderer(
     mut commands: Commands,
-    mut fractals: Query<(Entity, &Fractal, &mut FractalRenderer), Added<FractalRenderer>>,
+    mut fractals: Query<
+        (
+            Entity,
+            &Fractal,
+            &ReferenceOrbit,
+            &mut FractalRenderer,
+            Option<&RenderLayers>,
+        ),
+        Added<FractalRenderer>,
+    >,
     mut material_f32_assets: ResMut<Assets<FractalMaterial<f32>>>,
     mut material_f64_assets: ResMut<Assets<FractalMaterial<f64>>>,
+    mut material_df64_assets: ResMut<Assets<FractalMaterial<DoubleSingle>>>,
+    mut buffers: ResMut<Assets<ShaderStorageBuffer>>,
+    pass_buffers: Res<PassBuffers>,
 ) {
-    for (id, &fractal, mut renderer) in fractals.iter_mut() {
-        let material_f32_handle = material_f32_assets.add(FractalMaterial::from(fractal));
-        let material_f64_handle = material_f64_assets.add(FractalMaterial::from(fractal));
+    for (id, fractal, orbit, mut renderer, layers) in fractals.iter_mut() {
+        let material_f32 = FractalMaterial::new(
+            fractal,
+            orbit,
+            &mut buffers,
+            &pass_buffers,
+            MaterialPass::Fractal,
+        );
+        let material_f64 = FractalMaterial::new(
+            fractal,
+            orbit,
+            &mut buffers,
+            &pass_buffers,
+            MaterialPass::Fractal,
+        );
+        let material_df64 = FractalMaterial::new(
+            fractal,
+            orbit,
+            &mut buffers,
+            &pass_buffers,
+            MaterialPass::Fractal,
+        );
+        let material_f32_handle = material_f32_assets.add(material_f32);
+        let material_f64_handle = material_f64_assets.add(material_f64);
+        let material_df64_handle = material_df64_assets.add(material_df64);
 
         *renderer = FractalRenderer {
             material_f32_handle,
             material_f64_handle,
+            material_df64_handle,
         };
 
-        commands.entity(id).insert(Mesh2d(FRACTAL_MESH_HANDLE));
+        let mut fractal_ref = commands.entity(id);
+        fractal_ref.insert(Mesh2d(FRACTAL_MESH_HANDLE));
+
+        // the passes on top only read what the fractal wrote, so their precision doesn't matter
+        for pass in [MaterialPass::HistogramColors, MaterialPass::Lighting] {
+            let material = FractalMaterial::new(fractal, orbit, &mut buffers, &pass_buffers, pass);
+
+            // drawn by the same cameras as the fractal
+            fractal_ref.with_child((
+                pass,
+                Mesh2d(FRACTAL_MESH_HANDLE),
+                MeshMaterial2d(material_f32_assets.add(material)),
+                pass_visibility(pass, fractal),
+                layers.cloned().unwrap_or_default(),
+            ));
+        }
+    }
+}
+
+pub fn show_passes(
+    fractals: Query<(&Fractal, &Children), Changed<Fractal>>,
+    mut passes: Query<(&MaterialPass, &mut Visibility)>,
+) {
+    for (fractal, children) in fractals.iter() {
+        let mut iter = passes.iter_many_mut(children);
+        while let Some((&pass, mut visibility)) = iter.fetch_next() {
+            visibility.set_if_neq(pass_visibility(pass, fractal));
+        }
+    }
+}
+
+fn pass_visibility(pass: MaterialPass, fractal: &Fractal) -> Visibility {
+    if pass.is_drawn(fractal) {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
     }
 }
 
@@ -78,32 +272,50 @@ pub fn swap_fractal_materials(
             &FractalRenderer,
             Option<&MeshMaterial2d<FractalMaterial<f32>>>,
             Option<&MeshMaterial2d<FractalMaterial<f64>>>,
+            Option<&MeshMaterial2d<FractalMaterial<DoubleSingle>>>,
         ),
         Changed<Fractal>,
     >,
     f64_supported: Res<DoublePrecisionSupported>,
 ) {
-    for (id, fractal, renderer, material_f32, material_f64) in fractals.iter() {
+    for (id, fractal, renderer, material_f32, material_f64, material_df64) in fractals.iter() {
         let mut fractal_ref = commands.entity(id);
-        if fractal.use_f64 && f64_supported.0 {
-            if material_f32.is_some() {
-                fractal_ref.remove::<MeshMaterial2d<FractalMaterial<f32>>>();
-                debug!("Removed f32 material from fractal");
-            }
-            if material_f64.is_none() {
-                fractal_ref.insert(MeshMaterial2d(renderer.material_f64_handle.clone()));
-                debug!("Added f64 material to fractal");
-            }
-        } else {
-            if material_f64.is_some() {
-                fractal_ref.remove::<MeshMaterial2d<FractalMaterial<f64>>>();
-                debug!("Removed f64 material from fractal");
-            }
-            if material_f32.is_none() {
-                fractal_ref.insert(MeshMaterial2d(renderer.material_f32_handle.clone()));
-                debug!("Added f32 material to fractal");
-            }
-        }
+        let precision = fractal.shader_precision(f64_supported.0);
+
+        swap_material(
+            &mut fractal_ref,
+            precision == Precision::Single,
+            material_f32,
+            &renderer.material_f32_handle,
+        );
+        swap_material(
+            &mut fractal_ref,
+            precision == Precision::Double,
+            material_f64,
+            &renderer.material_f64_handle,
+        );
+        swap_material(
+            &mut fractal_ref,
+            precision == Precision::DoubleSingle,
+            material_df64,
+            &renderer.material_df64_handle,
+        );
+    }
+}
+
+fn swap_material<FP: EncodeShaderFloat>(
+    fractal_ref: &mut EntityCommands,
+    should_be_used: bool,
+    current: Option<&MeshMaterial2d<FractalMaterial<FP>>>,
+    handle: &Handle<FractalMaterial<FP>>,
+) {
+    if should_be_used && current.is_none() {
+        fractal_ref.insert(MeshMaterial2d(handle.clone()));
+        debug!(precision = ?FP::PRECISION, "Added material to fractal");
+    }
+    if !should_be_used && current.is_some() {
+        fractal_ref.remove::<MeshMaterial2d<FractalMaterial<FP>>>();
+        debug!(precision = ?FP::PRECISION, "Removed material from fractal");
     }
 }
 
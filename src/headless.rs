@@ -0,0 +1,126 @@
+//! Rendering the fractal to a PNG file without opening a window, for the `render` command.
+//!
+//! The image is rendered like any other [export](crate::export). It's started once the fractal
+//! shader is loaded and the reference orbit is calculated, and the app exits once the file
+//! is written.
+
+use std::{path::PathBuf, time::Duration};
+
+use bevy::{
+    app::{PluginGroupBuilder, ScheduleRunnerPlugin},
+    prelude::*,
+    render::RenderPlugin,
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+
+use crate::{
+    export::{
+        finish_export, is_ready_to_export, start_export, ExportFinished, ExportImage,
+        ExportSettings,
+    },
+    fractal::{
+        material::FractalShaderSource, parameters::ParameterTime, perturbation::ReferenceOrbit,
+        Fractal, OffscreenFractal,
+    },
+    utils::get_default_render_plugin,
+};
+
+/// [`DefaultPlugins`] without a window, the app runs as fast as it can instead
+///
+/// Without [`WinitPlugin`] the default runner only updates the app once,
+/// so it's replaced by one that loops until the app exits.
+pub fn headless_default_plugins() -> PluginGroupBuilder {
+    DefaultPlugins
+        .build()
+        .set(RenderPlugin {
+            // otherwise the first frames are rendered before the pipelines are ready
+            synchronous_pipeline_compilation: true,
+            ..get_default_render_plugin()
+        })
+        .set(WindowPlugin {
+            primary_window: None,
+            exit_condition: ExitCondition::DontExit,
+            close_when_requested: false,
+        })
+        .disable::<WinitPlugin>()
+        .add(ScheduleRunnerPlugin::run_loop(Duration::ZERO))
+}
+
+#[derive(Debug, Clone)]
+pub struct HeadlessRenderPlugin {
+    pub path: PathBuf,
+    pub settings: ExportSettings,
+}
+
+impl Plugin for HeadlessRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HeadlessRender {
+            path: self.path.clone(),
+            settings: self.settings,
+            started: false,
+        })
+        // animated parameters are rendered at the time given on the command line
+        .insert_resource(ParameterTime {
+            seconds: 0.0,
+            paused: true,
+        })
+        .add_systems(
+            Update,
+            (
+                start_headless_render.before(start_export),
+                exit_after_render.after(finish_export),
+            ),
+        );
+    }
+}
+
+/// The image rendered by [`HeadlessRenderPlugin`]
+#[derive(Debug, Clone, Resource)]
+pub struct HeadlessRender {
+    path: PathBuf,
+    settings: ExportSettings,
+    started: bool,
+}
+
+pub fn start_headless_render(
+    mut render: ResMut<HeadlessRender>,
+    shader_source: Res<FractalShaderSource>,
+    fractals: Query<(&Fractal, &ReferenceOrbit), Without<OffscreenFractal>>,
+    mut export_image: EventWriter<ExportImage>,
+) {
+    if render.started {
+        return;
+    }
+    let Ok((fractal, orbit)) = fractals.get_single() else {
+        return;
+    };
+    if !is_ready_to_export(&shader_source, fractal, orbit) {
+        return;
+    }
+
+    render.started = true;
+    export_image.send(ExportImage {
+        path: render.path.clone(),
+        settings: render.settings,
+    });
+}
+
+pub fn exit_after_render(
+    mut finished: EventReader<ExportFinished>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Some(ExportFinished { path, error }) = finished.read().last() else {
+        return;
+    };
+
+    match error {
+        Some(error) => {
+            error!(?path, "Failed to render: {error}");
+            exit.send(AppExit::error());
+        }
+        None => {
+            exit.send(AppExit::Success);
+        }
+    }
+}
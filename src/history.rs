@@ -0,0 +1,168 @@
+//! Undo and redo for every change to the fractal.
+//!
+//! Instead of hooking into every place that edits the fractal, the history watches it for changes.
+//! An entry is only added once the fractal stops changing for a moment and no mouse button
+//! is held, so a whole drag, a burst of scrolling or a slider drag can be undone at once.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{
+    fractal::{Fractal, OffscreenFractal},
+    input::fractal_input_system,
+    ui::UiSystemSet,
+};
+
+/// Largest number of changes that can be undone
+pub const MAX_HISTORY_LENGTH: usize = 100;
+
+// seconds without changes after which they are added to the history
+const SETTLE_TIME: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<HistoryAction>()
+            .init_resource::<FractalHistory>()
+            .add_systems(
+                Update,
+                (history_shortcuts, record_history, apply_history_actions)
+                    .chain()
+                    .after(UiSystemSet)
+                    .after(fractal_input_system),
+            );
+    }
+}
+
+/// Moves back or forward through the history of the fractal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub enum HistoryAction {
+    Undo,
+    Redo,
+}
+
+#[derive(Debug, Clone, Default, Resource)]
+pub struct FractalHistory {
+    undo: VecDeque<Fractal>,
+    redo: Vec<Fractal>,
+    // the fractal as of the last entry, `None` until the fractal is spawned
+    current: Option<Fractal>,
+    // whether the fractal differs from `current`
+    pending: bool,
+    last_change_time: f64,
+}
+
+impl FractalHistory {
+    pub fn can_undo(&self) -> bool {
+        self.pending || !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.pending && !self.redo.is_empty()
+    }
+
+    // makes the current state of the fractal an entry, if it changed
+    fn commit(&mut self, fractal: &Fractal) {
+        self.pending = false;
+        let Some(current) = self.current.as_mut() else {
+            self.current = Some(fractal.clone());
+            return;
+        };
+        if current == fractal {
+            return;
+        }
+
+        let previous = std::mem::replace(current, fractal.clone());
+        self.undo.push_back(previous);
+        if self.undo.len() > MAX_HISTORY_LENGTH {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+    }
+
+    // steps back, returning the fractal to restore
+    fn undo(&mut self) -> Option<&Fractal> {
+        let current = self.current.as_mut()?;
+        let previous = self.undo.pop_back()?;
+        self.redo.push(std::mem::replace(current, previous));
+        Some(current)
+    }
+
+    // steps forward, returning the fractal to restore
+    fn redo(&mut self) -> Option<&Fractal> {
+        let current = self.current.as_mut()?;
+        let next = self.redo.pop()?;
+        self.undo.push_back(std::mem::replace(current, next));
+        Some(current)
+    }
+}
+
+pub fn history_shortcuts(keys: Res<ButtonInput<KeyCode>>, mut actions: EventWriter<HistoryAction>) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    if ctrl && keys.just_pressed(KeyCode::KeyZ) {
+        actions.send(if shift {
+            HistoryAction::Redo
+        } else {
+            HistoryAction::Undo
+        });
+    }
+}
+
+pub fn record_history(
+    mut history: ResMut<FractalHistory>,
+    fractals: Query<Ref<Fractal>, Without<OffscreenFractal>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    time: Res<Time<Real>>,
+) {
+    let Ok(fractal) = fractals.get_single() else {
+        return;
+    };
+    let now = time.elapsed_secs_f64();
+
+    if history.current.is_none() {
+        history.commit(&fractal);
+        return;
+    }
+
+    if fractal.is_changed() && history.current.as_ref() != Some(&*fractal) {
+        history.pending = true;
+        history.last_change_time = now;
+    }
+
+    // drags are only finished once the button is released
+    let settled = now - history.last_change_time > SETTLE_TIME
+        && mouse_buttons.get_pressed().next().is_none();
+    if history.pending && settled {
+        history.commit(&fractal);
+    }
+}
+
+pub fn apply_history_actions(
+    mut actions: EventReader<HistoryAction>,
+    mut history: ResMut<FractalHistory>,
+    mut fractals: Query<&mut Fractal, Without<OffscreenFractal>>,
+) {
+    let Ok(mut fractal) = fractals.get_single_mut() else {
+        return;
+    };
+
+    for action in actions.read() {
+        // changes that haven't settled yet are undone as a whole too
+        if history.pending {
+            history.commit(&fractal);
+        }
+
+        let restored = match action {
+            HistoryAction::Undo => history.undo(),
+            HistoryAction::Redo => history.redo(),
+        };
+        if let Some(restored) = restored {
+            *fractal = restored.clone();
+        }
+    }
+}
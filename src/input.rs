@@ -1,4 +1,6 @@
 //! Allows panning and zooming the fractal using the left mouse button and the mouse wheel.
+//!
+//! Also keeps track of the cursor position for cursor parameters, which can be frozen with F.
 
 use bevy::{
     app::{Plugin, Update},
@@ -14,11 +16,17 @@ use bevy::{
 
 use crate::{compositing::ViewportCamera, ui::UiSystemSet};
 
-use crate::fractal::Fractal;
+use crate::fractal::{
+    parameters::{ParameterCursor, ParameterInputSystemSet},
+    precise::PreciseVec2,
+    Fractal, OffscreenFractal,
+};
 
 const PIXELS_PER_LINE: f64 = 12.0;
 const PIXELS_PER_HALF_SCALE: f64 = 50.0;
 const EPSILON: f64 = 0.0001;
+/// Toggles whether cursor parameters follow the cursor
+pub const FREEZE_CURSOR_KEY: KeyCode = KeyCode::KeyF;
 
 pub struct FractalInputPlugin;
 
@@ -26,18 +34,23 @@ impl Plugin for FractalInputPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.init_resource::<FractalInputState>();
         app.add_systems(Update, fractal_input_system.after(UiSystemSet));
+        app.add_systems(
+            PreUpdate,
+            update_parameter_cursor.in_set(ParameterInputSystemSet),
+        );
+        app.add_systems(Update, freeze_cursor_shortcut.after(UiSystemSet));
     }
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Default)]
 struct DragState {
     start_cursor_pos: DVec2,
     // used to avoid editing the fractal when holding the mouse in place
     previous_cursor_pos: DVec2,
-    start_offest: DVec2,
+    start_offest: PreciseVec2,
 }
 
-#[derive(Debug, Clone, Copy, Resource, Default)]
+#[derive(Debug, Clone, Resource, Default)]
 pub struct FractalInputState {
     drag_state: Option<DragState>,
 }
@@ -45,7 +58,7 @@ pub struct FractalInputState {
 pub fn fractal_input_system(
     mut camera: Query<&Camera, With<ViewportCamera>>,
     window: Query<&Window, With<PrimaryWindow>>,
-    mut fractal: Query<&mut Fractal>,
+    mut fractal: Query<&mut Fractal, Without<OffscreenFractal>>,
     mouse_buttons: Res<ButtonInput<MouseButton>>,
     mouse_wheel: Res<AccumulatedMouseScroll>,
     mut state: ResMut<FractalInputState>,
@@ -77,7 +90,7 @@ pub fn fractal_input_system(
         state.drag_state = Some(DragState {
             start_cursor_pos: cursor_pos,
             previous_cursor_pos: cursor_pos,
-            start_offest: fractal.offset,
+            start_offest: fractal.offset.clone(),
         });
     }
 
@@ -98,7 +111,9 @@ pub fn fractal_input_system(
             let fractal = &mut *fractal;
             let scaled_delta = (total_delta * dvec2(1.0, -1.0) / pixels_per_unit) * fractal.scale;
 
-            fractal.offset = drag_state.start_offest + scaled_delta;
+            fractal.offset = drag_state
+                .start_offest
+                .offset_by(scaled_delta, fractal.scale);
             drag_state.previous_cursor_pos = cursor_pos;
         }
     }
@@ -111,15 +126,65 @@ pub fn fractal_input_system(
         };
 
         // preserve cursor world position
-        let cursor_centered_pos = cursor_pos - viewport_size / 2.0;
-        let cursor_normalized_pos = cursor_centered_pos * dvec2(1.0, -1.0) / pixels_per_unit;
+        let cursor_normalized_pos = cursor_normalized_pos(cursor_pos, viewport_size);
 
-        let cursor_world_pos = cursor_normalized_pos * fractal.scale + fractal.offset;
+        let old_scale = fractal.scale;
         fractal.scale *= f64::exp2(-pixels_scrolled / PIXELS_PER_HALF_SCALE);
-        // if we rearrange the cursor_world pos equation we get this
-        // offset cursor_world_pos - cursor_normalized_pos * scale
-        fractal.offset = cursor_world_pos - cursor_normalized_pos * fractal.scale;
+        // cursor_world_pos = cursor_normalized_pos * scale + offset
+        // so for it to stay the same, the offset has to move by the change in the first term
+        let delta = cursor_normalized_pos * (old_scale - fractal.scale);
+        fractal.offset = fractal.offset.offset_by(delta, fractal.scale);
+    }
+}
+
+pub fn update_parameter_cursor(
+    camera: Query<&Camera, With<ViewportCamera>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    fractal: Query<&Fractal, Without<OffscreenFractal>>,
+    mut cursor: ResMut<ParameterCursor>,
+) {
+    if cursor.frozen {
+        return;
     }
+    let (Ok(camera), Ok(window), Ok(fractal)) = (
+        camera.get_single(),
+        window.get_single(),
+        fractal.get_single(),
+    ) else {
+        return;
+    };
+    let (Some(camera_rect), Some(cursor_pos)) =
+        (camera.logical_viewport_rect(), window.cursor_position())
+    else {
+        return;
+    };
+    // the position stays where the cursor left the fractal
+    if !camera_rect.contains(cursor_pos) {
+        return;
+    }
+
+    let viewport_size = DVec2::from(camera_rect.size());
+    let cursor_normalized_pos = cursor_normalized_pos(DVec2::from(cursor_pos), viewport_size);
+    let position = cursor_normalized_pos * fractal.scale + fractal.offset.as_dvec2();
+    if cursor.position != position {
+        cursor.position = position;
+    }
+}
+
+pub fn freeze_cursor_shortcut(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut cursor: ResMut<ParameterCursor>,
+) {
+    if keys.just_pressed(FREEZE_CURSOR_KEY) {
+        cursor.frozen = !cursor.frozen;
+    }
+}
+
+// cursor position relative to the center of the viewport, in the units of the fractal at scale 1
+fn cursor_normalized_pos(cursor_pos: DVec2, viewport_size: DVec2) -> DVec2 {
+    let pixels_per_unit = f64::max(viewport_size.x, viewport_size.y) / 2.0;
+    let cursor_centered_pos = cursor_pos - viewport_size / 2.0;
+    cursor_centered_pos * dvec2(1.0, -1.0) / pixels_per_unit
 }
 
 fn lines_to_pixels(lines: f64) -> f64 {
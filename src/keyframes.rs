@@ -0,0 +1,440 @@
+//! Animating the fractal between keyframes, and rendering the animation to a PNG sequence.
+//!
+//! Between two keyframes the scale changes exponentially, so the zoom has a constant speed,
+//! and the center moves so that a single point stays in place. Parameters that are constants
+//! and the offset of the palette are interpolated linearly, and every other setting switches
+//! to the next keyframe halfway through.
+//!
+//! Animated parameters follow the timeline too, at the time of a frame in the animation
+//! they have the value they would have that many seconds after starting.
+//!
+//! Every frame of the animation is rendered like an [export](crate::export),
+//! to `frame_00000.png`, `frame_00001.png` and so on, ready to be encoded into a video.
+
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    export::{
+        cancel_export, is_ready_to_export, CancelExport, ExportFinished, ExportImage,
+        ExportSettings,
+    },
+    fractal::{
+        material::FractalShaderSource,
+        parameters::{ComplexParameter, Parameter, ParameterInputSystemSet, ParameterTime},
+        perturbation::ReferenceOrbit,
+        precise::PreciseVec2,
+        Fractal, OffscreenFractal,
+    },
+    ui::UiSystemSet,
+    utils::show_error_dialog,
+};
+
+/// Largest number of frames per second of a rendered animation
+pub const MAX_FRAME_RATE: u32 = 240;
+
+// seconds between a new keyframe and the one before it
+const DEFAULT_KEYFRAME_DURATION: f64 = 5.0;
+// views with scales closer than this factor are interpolated as a pan instead of a zoom
+const PAN_SCALE_RATIO: f64 = 1.1;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeyframesPlugin;
+
+impl Plugin for KeyframesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AddKeyframe>()
+            .add_event::<RemoveKeyframe>()
+            .add_event::<RenderAnimation>()
+            .add_event::<CancelAnimationRender>()
+            .init_resource::<Timeline>()
+            // the frames set the time themselves
+            .configure_sets(
+                PreUpdate,
+                ParameterInputSystemSet.run_if(not(resource_exists::<AnimationRender>)),
+            )
+            .add_systems(
+                Update,
+                (
+                    add_keyframe,
+                    remove_keyframe,
+                    play_timeline,
+                    start_animation_render,
+                    cancel_animation_render,
+                    render_animation_frames,
+                )
+                    .chain()
+                    .after(UiSystemSet)
+                    .before(cancel_export),
+            );
+    }
+}
+
+/// Adds the current fractal as a keyframe after the last one
+#[derive(Debug, Clone, Copy, Event)]
+pub struct AddKeyframe;
+
+/// Removes the keyframe with the given index
+#[derive(Debug, Clone, Copy, Event)]
+pub struct RemoveKeyframe {
+    pub index: usize,
+}
+
+/// Renders every frame of the timeline to a numbered PNG file in the directory
+#[derive(Debug, Clone, Event)]
+pub struct RenderAnimation {
+    pub directory: PathBuf,
+    pub settings: ExportSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub fractal: Fractal,
+    /// Seconds it takes to get to the next keyframe
+    pub duration: f64,
+}
+
+/// The keyframes of the animation and the position in it
+#[derive(Debug, Clone, Resource)]
+pub struct Timeline {
+    pub keyframes: Vec<Keyframe>,
+    pub frame_rate: u32,
+    /// Position in the animation in seconds, the fractal is set to it when it changes
+    pub time: f64,
+    pub playing: bool,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            keyframes: Vec::new(),
+            frame_rate: 30,
+            time: 0.0,
+            playing: false,
+        }
+    }
+}
+
+impl Timeline {
+    /// Length of the animation in seconds, the duration of the last keyframe doesn't count
+    pub fn duration(&self) -> f64 {
+        let count = self.keyframes.len().saturating_sub(1);
+        self.keyframes[..count].iter().map(|k| k.duration).sum()
+    }
+
+    /// Number of frames in the animation, including the last keyframe
+    pub fn frame_count(&self) -> u32 {
+        if self.keyframes.is_empty() {
+            return 0;
+        }
+        (self.duration() * self.frame_rate as f64).floor() as u32 + 1
+    }
+
+    /// Time at which the keyframe with the given index is reached
+    pub fn keyframe_time(&self, index: usize) -> f64 {
+        self.keyframes[..index].iter().map(|k| k.duration).sum()
+    }
+
+    /// The fractal at the given time, `None` if there are no keyframes
+    pub fn fractal_at(&self, time: f64) -> Option<Fractal> {
+        let mut start = 0.0;
+        for pair in self.keyframes.windows(2) {
+            let (from, to) = (&pair[0], &pair[1]);
+            if time < start + from.duration {
+                let t = ((time - start) / from.duration).max(0.0);
+                return Some(interpolate_fractals(&from.fractal, &to.fractal, t));
+            }
+            start += from.duration;
+        }
+        self.keyframes.last().map(|k| k.fractal.clone())
+    }
+}
+
+/// Interpolates the scale and the offset between two views
+///
+/// The scale changes exponentially with `t`, and the offset moves so that the same point
+/// stays in place while zooming. The offset is relative to `to`, so it stays precise when zooming in.
+pub fn interpolate_view(from: &Fractal, to: &Fractal, t: f64) -> (f64, PreciseVec2) {
+    let scale = from.scale.powf(1.0 - t) * to.scale.powf(t);
+
+    // zooming keeps one point in place, so the offset moves with the scale
+    let progress = if f64::max(from.scale / to.scale, to.scale / from.scale) < PAN_SCALE_RATIO {
+        t
+    } else {
+        (from.scale - scale) / (from.scale - to.scale)
+    };
+    let remaining = to.offset.difference(&from.offset) * (1.0 - progress);
+
+    (scale, to.offset.offset_by(-remaining, scale))
+}
+
+/// Interpolates everything that can be interpolated between two fractals
+pub fn interpolate_fractals(from: &Fractal, to: &Fractal, t: f64) -> Fractal {
+    let mut fractal = if t < 0.5 { from.clone() } else { to.clone() };
+
+    (fractal.scale, fractal.offset) = interpolate_view(from, to, t);
+    fractal.initial_z = interpolate_complex(from.initial_z, to.initial_z, t);
+    fractal.c = interpolate_complex(from.c, to.c, t);
+    fractal.p = interpolate_complex(from.p, to.p, t);
+    for (root, (from, to)) in fractal
+        .roots
+        .iter_mut()
+        .zip(from.roots.iter().zip(&to.roots))
+    {
+        *root = interpolate_complex(*from, *to, t);
+    }
+    fractal.palette.offset = from.palette.offset.lerp(to.palette.offset, t as f32);
+
+    fractal
+}
+
+fn interpolate_complex(from: ComplexParameter, to: ComplexParameter, t: f64) -> ComplexParameter {
+    ComplexParameter {
+        real: interpolate_parameter(from.real, to.real, t),
+        imaginary: interpolate_parameter(from.imaginary, to.imaginary, t),
+    }
+}
+
+fn interpolate_parameter(from: Parameter, to: Parameter, t: f64) -> Parameter {
+    match (from, to) {
+        (Parameter::Value(from), Parameter::Value(to)) => Parameter::Value(from.lerp(to, t)),
+        _ if t < 0.5 => from,
+        _ => to,
+    }
+}
+
+/// Stops rendering the animation, the frames that are already written are kept
+#[derive(Debug, Clone, Copy, Event)]
+pub struct CancelAnimationRender;
+
+/// The animation being rendered, exists until every frame is written
+///
+/// The fractal and the time are put back the way they were once it's done or cancelled.
+#[derive(Debug, Clone, Resource)]
+pub struct AnimationRender {
+    directory: PathBuf,
+    settings: ExportSettings,
+    frame_index: u32,
+    frame_count: u32,
+    // whether the current frame is being exported, otherwise it's waiting to be ready
+    exporting: bool,
+    // replaced by the frames while rendering
+    previous_fractal: Fractal,
+    previous_time: f64,
+}
+
+impl AnimationRender {
+    /// Fraction of the frames that are rendered
+    pub fn progress(&self) -> f32 {
+        self.frame_index as f32 / self.frame_count as f32
+    }
+
+    fn frame_path(&self) -> PathBuf {
+        self.directory
+            .join(format!("frame_{:05}.png", self.frame_index))
+    }
+
+    // ends the render, putting back what the frames replaced
+    fn stop(
+        &self,
+        commands: &mut Commands,
+        fractal: &mut Fractal,
+        parameter_time: &mut ParameterTime,
+    ) {
+        *fractal = self.previous_fractal.clone();
+        parameter_time.seconds = self.previous_time;
+        commands.remove_resource::<AnimationRender>();
+    }
+}
+
+pub fn add_keyframe(
+    mut events: EventReader<AddKeyframe>,
+    mut timeline: ResMut<Timeline>,
+    fractals: Query<&Fractal, Without<OffscreenFractal>>,
+) {
+    let Ok(fractal) = fractals.get_single() else {
+        return;
+    };
+
+    for AddKeyframe in events.read() {
+        timeline.keyframes.push(Keyframe {
+            fractal: fractal.clone(),
+            duration: DEFAULT_KEYFRAME_DURATION,
+        });
+        // the new keyframe is the current view, so moving there doesn't change it
+        timeline.time = timeline.duration();
+    }
+}
+
+pub fn remove_keyframe(mut events: EventReader<RemoveKeyframe>, mut timeline: ResMut<Timeline>) {
+    for &RemoveKeyframe { index } in events.read() {
+        if index < timeline.keyframes.len() {
+            timeline.keyframes.remove(index);
+        }
+    }
+}
+
+// moves the fractal to the position in the timeline when it changes
+pub fn play_timeline(
+    mut timeline: ResMut<Timeline>,
+    mut fractals: Query<&mut Fractal, Without<OffscreenFractal>>,
+    time: Res<Time<Real>>,
+    render: Option<Res<AnimationRender>>,
+    mut parameter_time: ResMut<ParameterTime>,
+    mut last_time: Local<f64>,
+) {
+    // the frames being rendered set the fractal themselves
+    if render.is_some() {
+        timeline.playing = false;
+        return;
+    }
+
+    if timeline.playing {
+        let duration = timeline.duration();
+        timeline.time = (timeline.time + time.delta_secs_f64()).min(duration);
+        if timeline.time >= duration {
+            timeline.playing = false;
+        }
+    }
+
+    // editing the keyframes leaves the fractal alone
+    if timeline.time == *last_time {
+        return;
+    }
+    *last_time = timeline.time;
+
+    let Ok(mut fractal) = fractals.get_single_mut() else {
+        return;
+    };
+    if let Some(at_time) = timeline.fractal_at(timeline.time) {
+        fractal.set_if_neq(at_time);
+        parameter_time.seconds = timeline.time;
+    }
+}
+
+pub fn start_animation_render(
+    mut commands: Commands,
+    mut events: EventReader<RenderAnimation>,
+    timeline: Res<Timeline>,
+    render: Option<Res<AnimationRender>>,
+    mut fractals: Query<&mut Fractal, Without<OffscreenFractal>>,
+    mut parameter_time: ResMut<ParameterTime>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+    if render.is_some() {
+        warn!("An animation is already being rendered");
+        return;
+    }
+    let Some(first_frame) = timeline.fractal_at(0.0) else {
+        return;
+    };
+    let Ok(mut fractal) = fractals.get_single_mut() else {
+        return;
+    };
+
+    if let Err(e) = fs::create_dir_all(&event.directory) {
+        show_error_dialog(
+            "Rendering failed",
+            format!("Failed to create {}: {e}", event.directory.display()),
+        );
+        return;
+    }
+
+    let frame_count = timeline.frame_count();
+    info!(directory = ?event.directory, frame_count, "Rendering animation");
+    commands.insert_resource(AnimationRender {
+        directory: event.directory.clone(),
+        settings: event.settings,
+        frame_index: 0,
+        frame_count,
+        exporting: false,
+        previous_fractal: std::mem::replace(&mut *fractal, first_frame),
+        previous_time: std::mem::replace(&mut parameter_time.seconds, 0.0),
+    });
+}
+
+pub fn cancel_animation_render(
+    mut commands: Commands,
+    mut events: EventReader<CancelAnimationRender>,
+    render: Option<Res<AnimationRender>>,
+    mut fractals: Query<&mut Fractal, Without<OffscreenFractal>>,
+    mut parameter_time: ResMut<ParameterTime>,
+    mut cancel_export: EventWriter<CancelExport>,
+) {
+    let Some(CancelAnimationRender) = events.read().last() else {
+        return;
+    };
+    let Some(render) = render else {
+        return;
+    };
+    let Ok(mut fractal) = fractals.get_single_mut() else {
+        return;
+    };
+
+    info!(directory = ?render.directory, frame = render.frame_index, "Cancelled animation render");
+    if render.exporting {
+        cancel_export.send(CancelExport);
+    }
+    render.stop(&mut commands, &mut fractal, &mut parameter_time);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn render_animation_frames(
+    mut commands: Commands,
+    render: Option<ResMut<AnimationRender>>,
+    timeline: Res<Timeline>,
+    mut fractals: Query<(&mut Fractal, &ReferenceOrbit), Without<OffscreenFractal>>,
+    mut parameter_time: ResMut<ParameterTime>,
+    shader_source: Res<FractalShaderSource>,
+    mut finished: EventReader<ExportFinished>,
+    mut export_image: EventWriter<ExportImage>,
+) {
+    let Some(mut render) = render else {
+        return;
+    };
+    let Ok((mut fractal, orbit)) = fractals.get_single_mut() else {
+        return;
+    };
+
+    if render.exporting {
+        let Some(ExportFinished { error, .. }) = finished.read().last() else {
+            return;
+        };
+        // the error is shown by whoever shows failed exports
+        if error.is_some() {
+            render.stop(&mut commands, &mut fractal, &mut parameter_time);
+            return;
+        }
+
+        render.exporting = false;
+        render.frame_index += 1;
+        if render.frame_index == render.frame_count {
+            info!(directory = ?render.directory, "Rendered animation");
+            render.stop(&mut commands, &mut fractal, &mut parameter_time);
+            return;
+        }
+
+        let time = render.frame_index as f64 / timeline.frame_rate as f64;
+        if let Some(frame) = timeline.fractal_at(time) {
+            *fractal = frame;
+        }
+        parameter_time.seconds = time;
+        // the shader and the orbit only catch up with the new frame later
+        return;
+    }
+
+    // only the exports of the frames matter
+    finished.clear();
+    if !is_ready_to_export(&shader_source, &fractal, orbit) {
+        return;
+    }
+    render.exporting = true;
+    export_image.send(ExportImage {
+        path: render.frame_path(),
+        settings: render.settings,
+    });
+}
@@ -1,9 +1,16 @@
 // #![allow(dead_code, unused_imports)]
 
+pub mod bookmarks;
+pub mod cli;
 pub mod compositing;
+pub mod export;
 pub mod fps_title;
 pub mod fractal;
+pub mod headless;
+pub mod history;
 pub mod input;
+pub mod keyframes;
 pub mod ui;
 pub mod utils;
-pub mod panic_hook;
\ No newline at end of file
+pub mod panic_hook;
+pub mod session;
\ No newline at end of file
@@ -1,14 +1,64 @@
 #![windows_subsystem = "windows"]
 
 // #![allow(dead_code, unused_imports)]
+use std::path::PathBuf;
+
 use bevy::{prelude::*, render::renderer::RenderDevice};
 use fractality::{
-    compositing::CompositingPlugin, fps_title::FpsTitlePlugin, fractal::FractalPlugin,
-    input::FractalInputPlugin, panic_hook::PanicHookPlugin, ui::UiPlugin,
+    bookmarks::BookmarksPlugin,
+    cli::{CliArgs, CliPlugin, Command, FractalArgs, USAGE},
+    compositing::CompositingPlugin,
+    export::{ExportPlugin, ExportSettings},
+    fps_title::FpsTitlePlugin,
+    fractal::FractalPlugin,
+    headless::{headless_default_plugins, HeadlessRenderPlugin},
+    history::HistoryPlugin,
+    input::FractalInputPlugin,
+    keyframes::KeyframesPlugin,
+    panic_hook::PanicHookPlugin,
+    session::SessionPlugin,
+    ui::UiPlugin,
     utils::get_default_render_plugin,
 };
 
 fn main() -> AppExit {
+    // the windows subsystem keeps a console from opening with the window,
+    // but then the output of the command line has nowhere to go
+    #[cfg(windows)]
+    if std::env::args_os().len() > 1 {
+        attach_parent_console();
+    }
+
+    let args = match CliArgs::parse(std::env::args_os().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return AppExit::error();
+        }
+    };
+    if args.help {
+        println!("{USAGE}");
+        return AppExit::Success;
+    }
+
+    match args.command {
+        Command::Run => run(args),
+        Command::Render { path, settings } => render(args.fractal, path, settings),
+    }
+}
+
+// writes stdout and stderr to the console the app was started from, if there is one
+#[cfg(windows)]
+fn attach_parent_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+    // SAFETY: takes no pointers, and fails without side effects if there's no such console
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+fn run(args: CliArgs) -> AppExit {
     let mut app = App::new();
     app.add_plugins((
         DefaultPlugins.build().set(get_default_render_plugin()),
@@ -18,12 +68,36 @@ fn main() -> AppExit {
         FractalInputPlugin,
         CompositingPlugin,
         UiPlugin,
+        ExportPlugin,
+        HistoryPlugin,
+        BookmarksPlugin,
+        KeyframesPlugin,
+        SessionPlugin {
+            restore: !args.fresh,
+        },
+        CliPlugin {
+            fractal: args.fractal,
+        },
     ))
     .add_systems(Startup, on_start);
 
     app.run()
 }
 
+// renders without a window or the session, so the result only depends on the arguments
+fn render(fractal: FractalArgs, path: PathBuf, settings: ExportSettings) -> AppExit {
+    let mut app = App::new();
+    app.add_plugins((
+        headless_default_plugins(),
+        FractalPlugin,
+        ExportPlugin,
+        CliPlugin { fractal },
+        HeadlessRenderPlugin { path, settings },
+    ));
+
+    app.run()
+}
+
 fn on_start(
     mut msaa: Query<&mut Msaa>,
     // mut _window: Query<&mut Window>,
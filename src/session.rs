@@ -0,0 +1,186 @@
+//! Saving the state of the app and restoring it on the next start.
+//!
+//! The session is written to the [config directory](crate::utils::config_dir) periodically
+//! and when the app exits. It holds the fractal, the size of the window, the keyframes
+//! and the settings that aren't part of the fractal.
+
+use std::{fs, io, path::PathBuf};
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    export::ExportSettings,
+    fractal::{add_fractal_to_world, preset::PRESET_VERSION, Fractal, OffscreenFractal},
+    keyframes::{Keyframe, Timeline},
+    ui::bookmarks::BookmarksPanel,
+    utils::config_dir,
+};
+
+// seconds between saves while the app is running
+const AUTOSAVE_INTERVAL: f64 = 30.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SessionPlugin {
+    /// Restore the last session on startup, otherwise start with the defaults
+    /// and overwrite it on the next save
+    pub restore: bool,
+}
+
+impl Default for SessionPlugin {
+    fn default() -> Self {
+        Self { restore: true }
+    }
+}
+
+impl Plugin for SessionPlugin {
+    fn build(&self, app: &mut App) {
+        if self.restore {
+            app.add_systems(Startup, restore_session.after(add_fractal_to_world));
+        }
+        app.add_systems(Last, save_session);
+    }
+}
+
+/// Everything restored on startup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Version of the format of [`Fractal`], see [`PRESET_VERSION`]
+    pub version: u32,
+    pub fractal: Fractal,
+    /// Logical size of the window
+    pub window_size: Option<Vec2>,
+    pub export_settings: ExportSettings,
+    pub animate_jumps: bool,
+    pub keyframes: Vec<Keyframe>,
+    pub frame_rate: u32,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            version: PRESET_VERSION,
+            fractal: default(),
+            window_size: None,
+            export_settings: default(),
+            animate_jumps: false,
+            keyframes: Vec::new(),
+            frame_rate: Timeline::default().frame_rate,
+        }
+    }
+}
+
+fn session_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("session.ron"))
+}
+
+pub fn restore_session(
+    mut fractals: Query<&mut Fractal, Without<OffscreenFractal>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut export_settings: ResMut<ExportSettings>,
+    mut bookmarks_panel: ResMut<BookmarksPanel>,
+    mut timeline: ResMut<Timeline>,
+) {
+    let Some(path) = session_path() else {
+        return;
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        // nothing to restore on the first start
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+        Err(e) => {
+            warn!(?path, "Failed to read the last session: {e}");
+            return;
+        }
+    };
+    let mut session: Session = match ron::from_str(&text) {
+        Ok(session) => session,
+        Err(e) => {
+            warn!(?path, "Failed to restore the last session: {e}");
+            return;
+        }
+    };
+    if session.version > PRESET_VERSION {
+        warn!(
+            ?path,
+            version = session.version,
+            "The last session is from a newer version"
+        );
+        return;
+    }
+
+    info!(?path, "Restoring the last session");
+    session.fractal.sanitize();
+    for keyframe in &mut session.keyframes {
+        keyframe.fractal.sanitize();
+    }
+    if let Ok(mut fractal) = fractals.get_single_mut() {
+        *fractal = session.fractal;
+    }
+    if let (Ok(mut window), Some(size)) = (windows.get_single_mut(), session.window_size) {
+        window.resolution.set(size.x, size.y);
+    }
+    *export_settings = session.export_settings;
+    bookmarks_panel.animate = session.animate_jumps;
+    timeline.keyframes = session.keyframes;
+    timeline.frame_rate = session.frame_rate;
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn save_session(
+    mut exit: EventReader<AppExit>,
+    fractals: Query<&Fractal, Without<OffscreenFractal>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    export_settings: Res<ExportSettings>,
+    bookmarks_panel: Res<BookmarksPanel>,
+    timeline: Res<Timeline>,
+    time: Res<Time<Real>>,
+    mut last_save_time: Local<f64>,
+    // the window is already closed by the time the app exits
+    mut window_size: Local<Option<Vec2>>,
+    mut last_saved: Local<String>,
+) {
+    if let Ok(window) = windows.get_single() {
+        *window_size = Some(window.resolution.size());
+    }
+
+    let now = time.elapsed_secs_f64();
+    let exiting = exit.read().next().is_some();
+    if !exiting && now - *last_save_time < AUTOSAVE_INTERVAL {
+        return;
+    }
+    *last_save_time = now;
+
+    let Ok(fractal) = fractals.get_single() else {
+        return;
+    };
+    let Some(path) = session_path() else {
+        return;
+    };
+
+    let session = Session {
+        version: PRESET_VERSION,
+        fractal: fractal.clone(),
+        window_size: *window_size,
+        export_settings: *export_settings,
+        animate_jumps: bookmarks_panel.animate,
+        keyframes: timeline.keyframes.clone(),
+        frame_rate: timeline.frame_rate,
+    };
+    let text = ron::ser::to_string_pretty(&session, default())
+        .expect("sessions should always be serializable");
+    if text == *last_saved {
+        return;
+    }
+
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&path, &text));
+    match result {
+        Ok(()) => debug!(?path, "Saved session"),
+        Err(e) => warn!(?path, "Failed to save the session: {e}"),
+    }
+    *last_saved = text;
+}
@@ -1,18 +1,56 @@
-use bevy::{input::mouse::AccumulatedMouseScroll, math::uvec2, prelude::*, window::PrimaryWindow};
+use bevy::{
+    ecs::system::SystemParam, input::mouse::AccumulatedMouseScroll, math::uvec2, prelude::*,
+    window::PrimaryWindow,
+};
 use bevy_egui::{
-    egui::{self, Checkbox, Color32, Frame, Grid, Margin, RichText, ScrollArea, Ui},
+    egui::{
+        self, Button, Checkbox, Color32, ComboBox, DragValue, Frame, Grid, Margin, RichText,
+        ScrollArea, SelectableLabel, TextEdit, Ui,
+    },
     EguiContext, EguiContextSettings, EguiContexts, EguiPlugin,
 };
+use bookmarks::{bookmarks_ui_system, BookmarksPanel};
+use export::{show_export_errors, show_export_input, ExportAction};
+use lighting::LightingInput;
 use num_input::show_num_input;
-use parameter::ComplexParameterInput;
+use palette::PaletteInput;
+use parameter::{show_parameter_input, show_pixel_transform_input, ComplexParameterInput};
+use preset::{show_builtin_preset_input, show_preset_buttons, PresetAction};
+use timeline::timeline_ui_system;
 
-use crate::fractal::{render::DoublePrecisionSupported, Fractal};
+use crate::{
+    export::{ExportImage, ExportSettings, ImageExport},
+    fractal::{
+        coloring::{ExteriorColoring, InteriorColoring, OrbitTrap, TrapShape},
+        expression::Function,
+        formula::Formula,
+        material::CustomFormulaError,
+        metadata::OpenImage,
+        parameters::{ComplexParameter, ParameterCursor, ParameterTime},
+        perturbation::{needs_double_precision, perturbation_supported},
+        precision::Precision,
+        preset::{OpenPreset, SavePreset},
+        render::DoublePrecisionSupported,
+        Fractal, OffscreenFractal, MAX_ROOT_COUNT, MIN_TOLERANCE,
+    },
+    history::{FractalHistory, HistoryAction},
+};
+pub mod bookmarks;
+pub mod export;
+pub mod lighting;
 pub mod num_input;
+pub mod palette;
 pub mod parameter;
+pub mod preset;
+pub mod timeline;
 
 const UI_SCALE: f32 = 1.25;
 const DRAG_SENSITIVITY: f64 = 0.0025;
 const ITER_COUNT_SENSITIVITY_COEF: f32 = 0.0075;
+const TIME_SENSITIVITY: f64 = 0.05;
+const MIN_TOLERANCE_SENSITIVITY: f64 = 1e-6;
+const BOUNDARY_THICKNESS_SENSITIVITY: f64 = 0.02;
+const STRIPE_DENSITY_SENSITIVITY: f64 = 0.05;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct UiPlugin;
@@ -24,13 +62,21 @@ impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(EguiPlugin)
             .init_resource::<NonUiArea>()
+            .init_resource::<BookmarksPanel>()
             .add_systems(Startup, setup_ui)
             .add_systems(
                 Update,
-                (ui_system, consume_inputs_over_ui)
+                // the other panels have to be shown first to be excluded from the non UI area
+                (
+                    bookmarks_ui_system,
+                    timeline_ui_system,
+                    ui_system,
+                    consume_inputs_over_ui,
+                )
                     .chain()
                     .in_set(UiSystemSet),
-            );
+            )
+            .add_systems(Update, show_export_errors);
     }
 }
 
@@ -48,11 +94,29 @@ pub fn setup_ui(
     }
 }
 
+/// Events sent by the buttons that open or save files
+#[derive(SystemParam)]
+pub struct FileEvents<'w> {
+    open_preset: EventWriter<'w, OpenPreset>,
+    save_preset: EventWriter<'w, SavePreset>,
+    export_image: EventWriter<'w, ExportImage>,
+    open_image: EventWriter<'w, OpenImage>,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn ui_system(
     mut contexts: EguiContexts,
-    mut fractal: Query<&mut Fractal>,
+    mut fractal: Query<&mut Fractal, Without<OffscreenFractal>>,
     mut non_ui_area: ResMut<NonUiArea>,
     f64_supported: Res<DoublePrecisionSupported>,
+    custom_formula_error: Res<CustomFormulaError>,
+    mut export_settings: ResMut<ExportSettings>,
+    export: Option<Res<ImageExport>>,
+    mut file_events: FileEvents,
+    history: Res<FractalHistory>,
+    mut history_actions: EventWriter<HistoryAction>,
+    mut parameter_time: ResMut<ParameterTime>,
+    mut parameter_cursor: ResMut<ParameterCursor>,
 ) {
     let Some(ctx) = contexts.try_ctx_mut() else {
         return;
@@ -64,17 +128,74 @@ pub fn ui_system(
         .frame(Frame::side_top_panel(&ctx.style()).inner_margin(Margin::symmetric(10.0, 10.0)))
         .show(ctx, |ui| {
             ScrollArea::vertical().show(ui, |ui| {
+                if let Some(action) = show_history_buttons(ui, &history) {
+                    history_actions.send(action);
+                }
+                ui.add_space(5.0);
+
+                ui.label(RichText::new("Presets").strong().size(18.0));
+                ui.separator();
+
+                show_builtin_preset_input(ui, fractal.reborrow());
+                ui.add_space(5.0);
+
+                match show_preset_buttons(ui) {
+                    Some(PresetAction::Open(path)) => {
+                        file_events.open_preset.send(OpenPreset { path });
+                    }
+                    Some(PresetAction::Save(path)) => {
+                        file_events.save_preset.send(SavePreset { path });
+                    }
+                    None => {}
+                }
+                ui.add_space(10.0);
+
                 ui.label(RichText::new("Parameters").strong().size(18.0));
                 ui.separator();
 
                 Grid::new(ui.next_auto_id()).show(ui, |ui| {
-                    ui.add_enabled_ui(f64_supported.0, |ui| {
-                        ui.label("Double Precision:");
-                        show_checkbox(ui, fractal.reborrow().map_unchanged(|f| &mut f.use_f64));
+                    ui.label("Formula:");
+                    show_formula_input(ui, fractal.reborrow());
+                    ui.end_row();
+
+                    ui.label("Precision:");
+                    show_precision_input(
+                        ui,
+                        fractal.reborrow().map_unchanged(|f| &mut f.precision),
+                        f64_supported.0,
+                    );
+                    ui.end_row();
+
+                    if needs_double_precision(&fractal) {
+                        ui.label("");
+                        if f64_supported.0 {
+                            ui.label("Double precision is used at this depth");
+                        } else {
+                            ui.label(
+                                RichText::new(
+                                    "Too deep for single precision, \
+                                    and your device doesn't support double",
+                                )
+                                .color(Color32::LIGHT_RED),
+                            );
+                        }
+                        ui.end_row();
+                    }
+
+                    ui.add_enabled_ui(perturbation_supported(&fractal), |ui| {
+                        let value = fractal
+                            .reborrow()
+                            .map_unchanged(|f| &mut f.use_perturbation);
+                        ui.label("Perturbation:");
+                        show_checkbox(ui, value);
                     })
                     .response
                     .on_disabled_hover_text(
-                        RichText::new("Unsupported on your device").color(Color32::LIGHT_RED),
+                        RichText::new(
+                            "Only supported by Mandelbrot and Tricorn with integer exponents, \
+                            and with z and c that are constants or the pixel coordinates",
+                        )
+                        .color(Color32::LIGHT_RED),
                     );
                     ui.end_row();
 
@@ -91,14 +212,30 @@ pub fn ui_system(
                     );
                     ui.end_row();
 
-                    let r = fractal.reborrow().map_unchanged(|f| &mut f.escape_radius);
-                    let r_sensitivity = (r.abs() + 1.0) * DRAG_SENSITIVITY;
-                    ui.label("Escape Radius:");
-                    show_num_input(ui, r, r_sensitivity);
+                    if fractal.formula.is_escape_time() {
+                        let r = fractal.reborrow().map_unchanged(|f| &mut f.escape_radius);
+                        let r_sensitivity = (r.abs() + 1.0) * DRAG_SENSITIVITY;
+                        ui.label("Escape Radius:");
+                        show_num_input(ui, r, r_sensitivity);
+                    } else {
+                        let tolerance = fractal.reborrow().map_unchanged(|f| &mut f.tolerance);
+                        ui.label("Tolerance:");
+                        show_tolerance_input(ui, tolerance);
+                    }
                     ui.end_row();
                 });
                 ui.add_space(5.0);
 
+                if fractal.formula == Formula::Custom {
+                    ui.label("Custom Formula:");
+                    show_custom_formula_input(
+                        ui,
+                        fractal.reborrow().map_unchanged(|f| &mut f.custom_formula),
+                        custom_formula_error.0.as_deref(),
+                    );
+                    ui.add_space(5.0);
+                }
+
                 let initial_z = fractal.reborrow().map_unchanged(|f| &mut f.initial_z);
                 ui.label("Initial Z:");
                 ui.indent(ui.next_auto_id(), |ui| {
@@ -106,15 +243,126 @@ pub fn ui_system(
                 });
                 ui.add_space(5.0);
 
-                let c = fractal.reborrow().map_unchanged(|f| &mut f.c);
-                ui.label("C:");
-                ui.indent(ui.next_auto_id(), |ui| ui.add(ComplexParameterInput(c)));
-                ui.add_space(5.0);
+                if fractal.formula.is_escape_time() {
+                    let c = fractal.reborrow().map_unchanged(|f| &mut f.c);
+                    ui.label("C:");
+                    ui.indent(ui.next_auto_id(), |ui| ui.add(ComplexParameterInput(c)));
+                    ui.add_space(5.0);
 
-                let p = fractal.reborrow().map_unchanged(|f| &mut f.p);
-                ui.label("Exponent:");
-                ui.indent(ui.next_auto_id(), |ui| ui.add(ComplexParameterInput(p)));
-                ui.add_space(5.0);
+                    let p = fractal.reborrow().map_unchanged(|f| &mut f.p);
+                    ui.label("Exponent:");
+                    ui.indent(ui.next_auto_id(), |ui| ui.add(ComplexParameterInput(p)));
+                    ui.add_space(5.0);
+                } else {
+                    show_roots_input(ui, fractal.reborrow());
+                }
+
+                if fractal.is_animated() {
+                    show_parameter_time_input(ui, parameter_time.reborrow());
+                    ui.add_space(5.0);
+                }
+                if fractal.uses_pixel_transform() {
+                    let transform = fractal.reborrow().map_unchanged(|f| &mut f.pixel_transform);
+                    ui.label("Pixel Transform:");
+                    ui.indent(ui.next_auto_id(), |ui| {
+                        show_pixel_transform_input(ui, transform);
+                    });
+                    ui.add_space(5.0);
+                }
+                if fractal.follows_cursor() {
+                    show_parameter_cursor_input(ui, parameter_cursor.reborrow());
+                    ui.add_space(5.0);
+                }
+
+                // newton fractals are colored by the root they converge to instead
+                if fractal.formula.is_escape_time() {
+                    ui.add_space(10.0);
+                    ui.label(RichText::new("Coloring").strong().size(18.0));
+                    ui.separator();
+
+                    Grid::new(ui.next_auto_id()).show(ui, |ui| {
+                        let formula = fractal.formula;
+                        ui.label("Exterior:");
+                        show_exterior_coloring_input(
+                            ui,
+                            fractal
+                                .reborrow()
+                                .map_unchanged(|f| &mut f.exterior_coloring),
+                            formula,
+                        );
+                        ui.end_row();
+
+                        if fractal.exterior_coloring == ExteriorColoring::DistanceEstimate {
+                            ui.label("Boundary Thickness:");
+                            show_boundary_thickness_input(
+                                ui,
+                                fractal
+                                    .reborrow()
+                                    .map_unchanged(|f| &mut f.boundary_thickness),
+                            );
+                            ui.end_row();
+                        }
+
+                        if fractal.exterior_coloring == ExteriorColoring::StripeAverage {
+                            ui.label("Stripe Density:");
+                            show_stripe_density_input(
+                                ui,
+                                fractal.reborrow().map_unchanged(|f| &mut f.stripe_density),
+                            );
+                            ui.end_row();
+                        }
+
+                        ui.label("Interior:");
+                        show_interior_coloring_input(
+                            ui,
+                            fractal
+                                .reborrow()
+                                .map_unchanged(|f| &mut f.interior_coloring),
+                        );
+                        ui.end_row();
+
+                        ui.label("Orbit Trap:");
+                        show_trap_shape_input(
+                            ui,
+                            fractal
+                                .reborrow()
+                                .map_unchanged(|f| &mut f.orbit_trap.shape),
+                        );
+                        ui.end_row();
+                    });
+                    ui.add_space(5.0);
+
+                    show_orbit_trap_input(
+                        ui,
+                        fractal.reborrow().map_unchanged(|f| &mut f.orbit_trap),
+                    );
+
+                    let palette = fractal.reborrow().map_unchanged(|f| &mut f.palette);
+                    ui.add(PaletteInput(palette));
+                    ui.add_space(5.0);
+
+                    let formula = fractal.formula;
+                    let lighting = fractal.reborrow().map_unchanged(|f| &mut f.lighting);
+                    ui.add(LightingInput(lighting, formula));
+                }
+
+                ui.add_space(10.0);
+                ui.label(RichText::new("Export").strong().size(18.0));
+                ui.separator();
+
+                let settings = export_settings.reborrow();
+                match show_export_input(ui, settings, export.as_deref()) {
+                    Some(ExportAction::Export(path)) => {
+                        file_events.export_image.send(ExportImage {
+                            path,
+                            settings: *export_settings,
+                        });
+                    }
+                    Some(ExportAction::Open(path)) => {
+                        file_events.open_image.send(OpenImage { path });
+                    }
+                    None => {}
+                }
             });
         });
 
@@ -130,6 +378,7 @@ pub fn consume_inputs_over_ui(
     mut contexts: EguiContexts,
     mut buttons: ResMut<ButtonInput<MouseButton>>,
     mut wheel: ResMut<AccumulatedMouseScroll>,
+    mut keys: ResMut<ButtonInput<KeyCode>>,
 ) {
     let Some(ctx) = contexts.try_ctx_mut() else {
         return;
@@ -139,6 +388,10 @@ pub fn consume_inputs_over_ui(
         buttons.clear();
         wheel.delta = Vec2::ZERO;
     }
+    // text fields have their own undo
+    if ctx.wants_keyboard_input() {
+        keys.clear();
+    }
 }
 
 fn egui_rect_to_urect(egui_rect: egui::Rect) -> bevy::math::URect {
@@ -150,6 +403,65 @@ fn egui_rect_to_urect(egui_rect: egui::Rect) -> bevy::math::URect {
     URect { min, max }
 }
 
+fn show_history_buttons(ui: &mut Ui, history: &FractalHistory) -> Option<HistoryAction> {
+    ui.horizontal(|ui| {
+        let undo = ui
+            .add_enabled(history.can_undo(), Button::new("Undo"))
+            .on_hover_text("Ctrl+Z");
+        let redo = ui
+            .add_enabled(history.can_redo(), Button::new("Redo"))
+            .on_hover_text("Ctrl+Shift+Z");
+
+        if undo.clicked() {
+            Some(HistoryAction::Undo)
+        } else if redo.clicked() {
+            Some(HistoryAction::Redo)
+        } else {
+            None
+        }
+    })
+    .inner
+}
+
+fn show_parameter_time_input(ui: &mut Ui, mut parameter_time: Mut<ParameterTime>) {
+    let mut temp_time = *parameter_time;
+
+    ui.horizontal(|ui| {
+        ui.label("Animation Time:");
+        ui.add(
+            DragValue::new(&mut temp_time.seconds)
+                .speed(TIME_SENSITIVITY)
+                .fixed_decimals(2)
+                .suffix(" s"),
+        );
+        let text = if temp_time.paused { "Resume" } else { "Pause" };
+        if ui.button(text).clicked() {
+            temp_time.paused = !temp_time.paused;
+        }
+    });
+
+    if temp_time != *parameter_time {
+        *parameter_time = temp_time;
+    }
+}
+
+fn show_parameter_cursor_input(ui: &mut Ui, mut parameter_cursor: Mut<ParameterCursor>) {
+    let mut frozen = parameter_cursor.frozen;
+
+    ui.horizontal(|ui| {
+        let position = parameter_cursor.position;
+        ui.label(format!("Cursor: {:.4}, {:.4}", position.x, position.y));
+        let text = if frozen { "Unfreeze" } else { "Freeze" };
+        if ui.button(text).on_hover_text("F").clicked() {
+            frozen = !frozen;
+        }
+    });
+
+    if frozen != parameter_cursor.frozen {
+        parameter_cursor.frozen = frozen;
+    }
+}
+
 fn show_checkbox(ui: &mut Ui, mut value: Mut<bool>) {
     let mut temp_value = *value;
     ui.add(Checkbox::without_text(&mut temp_value));
@@ -157,3 +469,214 @@ fn show_checkbox(ui: &mut Ui, mut value: Mut<bool>) {
         *value = temp_value;
     }
 }
+
+fn show_formula_input(ui: &mut Ui, mut fractal: Mut<Fractal>) {
+    let mut temp_value = fractal.formula;
+    ComboBox::new(ui.next_auto_id(), "")
+        .selected_text(temp_value.variant_str())
+        .show_ui(ui, |ui| {
+            for formula in Formula::ALL {
+                ui.selectable_value(&mut temp_value, formula, formula.variant_str());
+            }
+        });
+    if temp_value != fractal.formula {
+        // newton fractals are colored by where each pixel converges to,
+        // so they're flat unless the orbits start at the pixels
+        if temp_value == Formula::Newton && !fractal.initial_z.depends_on_pixel() {
+            fractal.initial_z = ComplexParameter::PIXEL;
+        }
+        fractal.formula = temp_value;
+    }
+}
+
+fn show_tolerance_input(ui: &mut Ui, mut value: Mut<f64>) {
+    let mut temp_value = *value;
+    // the speed is relative so small tolerances can still be adjusted, but it can't get stuck at 0
+    let sensitivity = (temp_value * DRAG_SENSITIVITY).max(MIN_TOLERANCE_SENSITIVITY);
+    ui.add(
+        DragValue::new(&mut temp_value)
+            .speed(sensitivity)
+            .range(MIN_TOLERANCE..=f64::INFINITY),
+    );
+    if temp_value != *value {
+        *value = temp_value;
+    }
+}
+
+fn show_precision_input(ui: &mut Ui, mut value: Mut<Precision>, f64_supported: bool) {
+    let mut temp_value = *value;
+    ComboBox::new(ui.next_auto_id(), "")
+        .selected_text(temp_value.variant_str())
+        .show_ui(ui, |ui| {
+            for precision in Precision::ALL {
+                let supported = precision != Precision::Double || f64_supported;
+                let label = SelectableLabel::new(temp_value == precision, precision.variant_str());
+
+                let response = ui.add_enabled(supported, label).on_disabled_hover_text(
+                    RichText::new("Unsupported on your device").color(Color32::LIGHT_RED),
+                );
+                if response.clicked() {
+                    temp_value = precision;
+                }
+            }
+        });
+    if temp_value != *value {
+        *value = temp_value;
+    }
+}
+
+fn show_exterior_coloring_input(ui: &mut Ui, mut value: Mut<ExteriorColoring>, formula: Formula) {
+    let mut temp_value = *value;
+    ComboBox::new(ui.next_auto_id(), "")
+        .selected_text(temp_value.variant_str())
+        .show_ui(ui, |ui| {
+            for coloring in ExteriorColoring::ALL {
+                // the derivative of a custom formula isn't known
+                let supported =
+                    coloring != ExteriorColoring::DistanceEstimate || formula != Formula::Custom;
+                let label = SelectableLabel::new(temp_value == coloring, coloring.variant_str());
+
+                let response = ui.add_enabled(supported, label).on_disabled_hover_text(
+                    RichText::new("Not supported by custom formulas").color(Color32::LIGHT_RED),
+                );
+                if response.clicked() {
+                    temp_value = coloring;
+                }
+            }
+        });
+    if temp_value != *value {
+        *value = temp_value;
+    }
+}
+
+fn show_boundary_thickness_input(ui: &mut Ui, mut value: Mut<f32>) {
+    let mut temp_value = *value;
+    ui.add(
+        DragValue::new(&mut temp_value)
+            .speed(BOUNDARY_THICKNESS_SENSITIVITY)
+            .range(0.1..=100.0)
+            .suffix(" px"),
+    );
+    if temp_value != *value {
+        *value = temp_value;
+    }
+}
+
+fn show_stripe_density_input(ui: &mut Ui, mut value: Mut<u32>) {
+    let mut temp_value = *value;
+    ui.add(
+        DragValue::new(&mut temp_value)
+            .speed(STRIPE_DENSITY_SENSITIVITY)
+            .range(1..=32),
+    );
+    if temp_value != *value {
+        *value = temp_value;
+    }
+}
+
+fn show_interior_coloring_input(ui: &mut Ui, mut value: Mut<InteriorColoring>) {
+    let mut temp_value = *value;
+    ComboBox::new(ui.next_auto_id(), "")
+        .selected_text(temp_value.variant_str())
+        .show_ui(ui, |ui| {
+            for coloring in InteriorColoring::ALL {
+                ui.selectable_value(&mut temp_value, coloring, coloring.variant_str());
+            }
+        });
+    if temp_value != *value {
+        *value = temp_value;
+    }
+}
+
+fn show_trap_shape_input(ui: &mut Ui, mut value: Mut<TrapShape>) {
+    let mut temp_value = *value;
+    ComboBox::new(ui.next_auto_id(), "")
+        .selected_text(temp_value.variant_str())
+        .show_ui(ui, |ui| {
+            for shape in TrapShape::ALL {
+                ui.selectable_value(&mut temp_value, shape, shape.variant_str());
+            }
+        });
+    if temp_value != *value {
+        *value = temp_value;
+    }
+}
+
+fn show_orbit_trap_input(ui: &mut Ui, mut trap: Mut<OrbitTrap>) {
+    let shape = trap.shape;
+    if shape == TrapShape::None {
+        return;
+    }
+
+    let center = trap.reborrow().map_unchanged(|t| &mut t.center);
+    ui.label("Trap Center:");
+    ui.indent(ui.next_auto_id(), |ui| {
+        ui.add(ComplexParameterInput(center))
+    });
+    ui.add_space(5.0);
+
+    Grid::new(ui.next_auto_id())
+        .min_row_height(45.0)
+        .show(ui, |ui| {
+            if shape == TrapShape::Circle {
+                ui.label("Trap Radius:");
+                show_parameter_input(ui, trap.reborrow().map_unchanged(|t| &mut t.radius));
+                ui.end_row();
+            }
+            if matches!(shape, TrapShape::Line | TrapShape::Cross) {
+                ui.label("Trap Angle:");
+                show_parameter_input(ui, trap.reborrow().map_unchanged(|t| &mut t.angle));
+                ui.end_row();
+            }
+        });
+    ui.add_space(5.0);
+}
+
+fn show_custom_formula_input(ui: &mut Ui, mut value: Mut<String>, error: Option<&str>) {
+    let mut temp_value = value.clone();
+
+    ui.add(TextEdit::singleline(&mut temp_value).code_editor())
+        .on_hover_ui(|ui| {
+            ui.label("Variables: z, c, p");
+            ui.label("Constants: i, pi, e");
+            ui.label("Operators: + - * / ^");
+
+            let functions = Function::ALL.map(Function::name).join(", ");
+            ui.label(format!("Functions: {functions}"));
+        });
+
+    if let Some(error) = error {
+        ui.label(RichText::new(error).color(Color32::LIGHT_RED));
+    }
+
+    if temp_value != *value {
+        *value = temp_value;
+    }
+}
+
+fn show_roots_input(ui: &mut Ui, mut fractal: Mut<Fractal>) {
+    let root_count = fractal.root_count;
+
+    for i in 0..root_count.min(MAX_ROOT_COUNT) {
+        let root = fractal.reborrow().map_unchanged(|f| &mut f.roots[i]);
+        ui.label(format!("Root {}:", i + 1));
+        ui.indent(ui.next_auto_id(), |ui| ui.add(ComplexParameterInput(root)));
+        ui.add_space(5.0);
+    }
+
+    ui.horizontal(|ui| {
+        let add_button = Button::new("Add Root");
+        if ui
+            .add_enabled(root_count < MAX_ROOT_COUNT, add_button)
+            .clicked()
+        {
+            fractal.root_count += 1;
+        }
+
+        let remove_button = Button::new("Remove Root");
+        if ui.add_enabled(root_count > 1, remove_button).clicked() {
+            fractal.root_count -= 1;
+        }
+    });
+    ui.add_space(5.0);
+}
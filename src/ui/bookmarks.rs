@@ -0,0 +1,103 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_egui::{
+    egui::{
+        self, Button, Checkbox, Frame, ImageButton, Margin, RichText, ScrollArea, Spinner, TextEdit,
+    },
+    EguiContexts,
+};
+
+use crate::bookmarks::{AddBookmark, Bookmarks, JumpToBookmark, RemoveBookmark, THUMBNAIL_SIZE};
+
+/// What the user typed and chose in the bookmarks panel
+#[derive(Debug, Clone, Default, Resource)]
+pub struct BookmarksPanel {
+    name: String,
+    /// Whether clicking a bookmark zooms there smoothly
+    pub animate: bool,
+}
+
+/// Events sent by the bookmarks panel
+#[derive(SystemParam)]
+pub struct BookmarkEvents<'w> {
+    add: EventWriter<'w, AddBookmark>,
+    remove: EventWriter<'w, RemoveBookmark>,
+    jump: EventWriter<'w, JumpToBookmark>,
+}
+
+pub fn bookmarks_ui_system(
+    mut contexts: EguiContexts,
+    bookmarks: Res<Bookmarks>,
+    mut panel: ResMut<BookmarksPanel>,
+    mut events: BookmarkEvents,
+) {
+    // the textures have to be added before the context is borrowed
+    let thumbnails: Vec<_> = bookmarks
+        .iter()
+        .map(|bookmark| {
+            let thumbnail = bookmark.thumbnail.as_ref()?;
+            Some(contexts.add_image(thumbnail.clone_weak()))
+        })
+        .collect();
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+    let thumbnail_size = egui::vec2(THUMBNAIL_SIZE.x as f32, THUMBNAIL_SIZE.y as f32);
+
+    egui::SidePanel::left("BookmarksPanel")
+        .resizable(false)
+        .frame(Frame::side_top_panel(&ctx.style()).inner_margin(Margin::symmetric(10.0, 10.0)))
+        .show(ctx, |ui| {
+            ui.label(RichText::new("Bookmarks").strong().size(18.0));
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                let name_width = thumbnail_size.x - 50.0;
+                ui.add(
+                    TextEdit::singleline(&mut panel.name)
+                        .hint_text("Name")
+                        .desired_width(name_width),
+                );
+                if ui.button("Add").clicked() {
+                    let name = match panel.name.trim() {
+                        "" => format!("Bookmark {}", bookmarks.len() + 1),
+                        name => name.to_string(),
+                    };
+                    events.add.send(AddBookmark { name });
+                    panel.name.clear();
+                }
+            });
+            ui.add(Checkbox::new(&mut panel.animate, "Animate jumps"));
+            ui.add_space(5.0);
+
+            ScrollArea::vertical().show(ui, |ui| {
+                for (index, (bookmark, thumbnail)) in bookmarks.iter().zip(thumbnails).enumerate() {
+                    let clicked = match thumbnail {
+                        Some(texture) => ui
+                            .add(ImageButton::new((texture, thumbnail_size)))
+                            .on_hover_text("Jump to this view")
+                            .clicked(),
+                        None => {
+                            ui.add_sized(thumbnail_size, Spinner::new());
+                            false
+                        }
+                    };
+                    if clicked {
+                        events.jump.send(JumpToBookmark {
+                            index,
+                            animated: panel.animate,
+                        });
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label(&bookmark.name);
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.add(Button::new("Remove").small()).clicked() {
+                                events.remove.send(RemoveBookmark { index });
+                            }
+                        });
+                    });
+                    ui.add_space(5.0);
+                }
+            });
+        });
+}
@@ -0,0 +1,123 @@
+use std::path::PathBuf;
+
+use bevy::prelude::{EventReader, Mut};
+use bevy_egui::egui::{ComboBox, DragValue, Grid, ProgressBar, Ui};
+
+use crate::{
+    export::{ExportFinished, ExportSettings, ImageExport, MAX_EXPORT_SIZE, MAX_SUPERSAMPLING},
+    utils::show_error_dialog,
+};
+
+const SIZE_SENSITIVITY: f64 = 2.0;
+
+/// What the user asked for in the export section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportAction {
+    Export(PathBuf),
+    Open(PathBuf),
+}
+
+/// Shows the export settings, and buttons that ask where to save an image
+/// or which exported image to restore the fractal from
+pub fn show_export_input(
+    ui: &mut Ui,
+    mut settings: Mut<ExportSettings>,
+    export: Option<&ImageExport>,
+) -> Option<ExportAction> {
+    let mut temp_settings = *settings;
+
+    Grid::new("Export input").show(ui, |ui| {
+        ui.label("Width:");
+        ui.add(
+            DragValue::new(&mut temp_settings.width)
+                .speed(SIZE_SENSITIVITY)
+                .range(1..=MAX_EXPORT_SIZE)
+                .suffix(" px"),
+        );
+        ui.end_row();
+
+        ui.label("Height:");
+        ui.add(
+            DragValue::new(&mut temp_settings.height)
+                .speed(SIZE_SENSITIVITY)
+                .range(1..=MAX_EXPORT_SIZE)
+                .suffix(" px"),
+        );
+        ui.end_row();
+
+        ui.label("Supersampling:");
+        ComboBox::new(ui.next_auto_id(), "")
+            .selected_text(supersampling_str(temp_settings.supersampling))
+            .show_ui(ui, |ui| {
+                for supersampling in 1..=MAX_SUPERSAMPLING {
+                    ui.selectable_value(
+                        &mut temp_settings.supersampling,
+                        supersampling,
+                        supersampling_str(supersampling),
+                    );
+                }
+            });
+        ui.end_row();
+    });
+    ui.add_space(5.0);
+
+    if temp_settings != *settings {
+        *settings = temp_settings;
+    }
+
+    if let Some(export) = export {
+        let text = if export.is_saving() {
+            "Saving...".to_string()
+        } else {
+            format!("Rendering {:.0}%", export.progress() * 100.0)
+        };
+        ui.add(ProgressBar::new(export.progress()).text(text));
+        return None;
+    }
+
+    let (export_clicked, open_clicked) = ui
+        .horizontal(|ui| {
+            let export_clicked = ui.button("Export Image").clicked();
+            let open_clicked = ui
+                .button("Open Image")
+                .on_hover_text("Restores the fractal from an exported image")
+                .clicked();
+            (export_clicked, open_clicked)
+        })
+        .inner;
+
+    if export_clicked {
+        let path = rfd::FileDialog::new()
+            .add_filter("PNG image", &["png"])
+            .set_file_name("fractal.png")
+            .save_file()?;
+        return Some(ExportAction::Export(path.with_extension("png")));
+    }
+    if open_clicked {
+        let path = rfd::FileDialog::new()
+            .add_filter("PNG image", &["png"])
+            .pick_file()?;
+        return Some(ExportAction::Open(path));
+    }
+
+    None
+}
+
+fn supersampling_str(supersampling: u32) -> String {
+    match supersampling {
+        1 => "Off".to_string(),
+        n => format!("{n}×{n}"),
+    }
+}
+
+/// Tells the user about exports that failed
+pub fn show_export_errors(mut events: EventReader<ExportFinished>) {
+    for ExportFinished { path, error } in events.read() {
+        if let Some(error) = error {
+            show_error_dialog(
+                "Export failed",
+                format!("Failed to export {}: {error}", path.display()),
+            );
+        }
+    }
+}
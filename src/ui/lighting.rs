@@ -0,0 +1,80 @@
+use bevy::{log::debug, prelude::Mut};
+use bevy_egui::egui::{
+    self, Checkbox, Color32, ComboBox, Grid, RichText, SelectableLabel, Slider, Ui, Widget,
+};
+
+use crate::fractal::{
+    formula::Formula,
+    lighting::{HeightSource, Lighting},
+};
+
+const MAX_DEPTH: f32 = 100.0;
+
+#[derive(Debug)]
+pub struct LightingInput<'a>(pub Mut<'a, Lighting>, pub Formula);
+
+impl Widget for LightingInput<'_> {
+    fn ui(mut self, ui: &mut Ui) -> egui::Response {
+        let mut lighting = *self.0;
+        let formula = self.1;
+
+        let response = Grid::new("Lighting input")
+            .show(ui, |ui| {
+                ui.label("Lighting:");
+                ui.add(Checkbox::without_text(&mut lighting.enabled));
+                ui.end_row();
+
+                if !lighting.enabled {
+                    return;
+                }
+
+                ui.label("Height:");
+                show_height_source_input(ui, &mut lighting.height, formula);
+                ui.end_row();
+
+                ui.label("Azimuth:");
+                ui.add(Slider::new(&mut lighting.azimuth, 0.0..=360.0).suffix("°"));
+                ui.end_row();
+
+                ui.label("Elevation:");
+                ui.add(Slider::new(&mut lighting.elevation, 0.0..=90.0).suffix("°"));
+                ui.end_row();
+
+                ui.label("Depth:");
+                ui.add(Slider::new(&mut lighting.depth, 0.0..=MAX_DEPTH).logarithmic(true));
+                ui.end_row();
+
+                ui.label("Specular:");
+                ui.add(Slider::new(&mut lighting.specular, 0.0..=1.0));
+                ui.end_row();
+            })
+            .response;
+
+        if lighting != *self.0 {
+            debug!("Lighting modified");
+            *self.0 = lighting;
+        }
+
+        response
+    }
+}
+
+fn show_height_source_input(ui: &mut Ui, value: &mut HeightSource, formula: Formula) {
+    ComboBox::new(ui.next_auto_id(), "")
+        .selected_text(value.variant_str())
+        .show_ui(ui, |ui| {
+            for source in HeightSource::ALL {
+                // the derivative of a custom formula isn't known
+                let supported =
+                    source != HeightSource::DistanceEstimate || formula != Formula::Custom;
+                let label = SelectableLabel::new(*value == source, source.variant_str());
+
+                let response = ui.add_enabled(supported, label).on_disabled_hover_text(
+                    RichText::new("Not supported by custom formulas").color(Color32::LIGHT_RED),
+                );
+                if response.clicked() {
+                    *value = source;
+                }
+            }
+        });
+}
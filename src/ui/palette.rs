@@ -0,0 +1,159 @@
+use bevy::{color::LinearRgba, log::debug, prelude::Mut};
+use bevy_egui::egui::{
+    self, Button, Color32, ComboBox, DragValue, Grid, Rgba, Sense, Stroke, Ui, Widget,
+};
+
+use crate::fractal::palette::{Interpolation, Palette, PalettePreset, MAX_COLOR_STOPS};
+
+const GRADIENT_HEIGHT: f32 = 24.0;
+// number of rectangles the gradient preview is drawn with
+const GRADIENT_SEGMENTS: usize = 128;
+const POSITION_SENSITIVITY: f64 = 0.002;
+const CYCLE_LENGTH_SENSITIVITY: f64 = 0.1;
+
+#[derive(Debug)]
+pub struct PaletteInput<'a>(pub Mut<'a, Palette>);
+
+impl Widget for PaletteInput<'_> {
+    fn ui(mut self, ui: &mut Ui) -> egui::Response {
+        let mut palette = (*self.0).clone();
+
+        let response = ui
+            .vertical(|ui| {
+                Grid::new("Palette input").show(ui, |ui| {
+                    ui.label("Preset:");
+                    show_preset_input(ui, &mut palette);
+                    ui.end_row();
+
+                    ui.label("Interpolation:");
+                    show_interpolation_input(ui, &mut palette.interpolation);
+                    ui.end_row();
+
+                    ui.label("Cycle Length:");
+                    ui.add(
+                        DragValue::new(&mut palette.cycle_length)
+                            .speed(CYCLE_LENGTH_SENSITIVITY)
+                            .range(1.0..=f32::MAX),
+                    );
+                    ui.end_row();
+
+                    ui.label("Offset:");
+                    ui.add(
+                        DragValue::new(&mut palette.offset)
+                            .speed(POSITION_SENSITIVITY)
+                            .range(0.0..=1.0),
+                    );
+                    ui.end_row();
+                });
+                ui.add_space(5.0);
+
+                show_gradient(ui, &palette);
+                ui.add_space(5.0);
+
+                show_stops_input(ui, &mut palette);
+            })
+            .response;
+
+        if palette != *self.0 {
+            debug!("Palette modified");
+            *self.0 = palette;
+        }
+
+        response
+    }
+}
+
+fn show_preset_input(ui: &mut Ui, palette: &mut Palette) {
+    let current = PalettePreset::matching(palette);
+    let selected_text = current.map_or("Custom", PalettePreset::variant_str);
+
+    ComboBox::new(ui.next_auto_id(), "")
+        .selected_text(selected_text)
+        .show_ui(ui, |ui| {
+            for preset in PalettePreset::ALL {
+                if ui
+                    .selectable_label(current == Some(preset), preset.variant_str())
+                    .clicked()
+                {
+                    *palette = preset.palette();
+                }
+            }
+        });
+}
+
+fn show_interpolation_input(ui: &mut Ui, value: &mut Interpolation) {
+    ComboBox::new(ui.next_auto_id(), "")
+        .selected_text(value.variant_str())
+        .show_ui(ui, |ui| {
+            for interpolation in Interpolation::ALL {
+                ui.selectable_value(value, interpolation, interpolation.variant_str());
+            }
+        });
+}
+
+fn show_gradient(ui: &mut Ui, palette: &Palette) {
+    let size = egui::vec2(ui.available_width(), GRADIENT_HEIGHT);
+    let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+    let painter = ui.painter_at(rect);
+
+    let segment_width = rect.width() / GRADIENT_SEGMENTS as f32;
+    for i in 0..GRADIENT_SEGMENTS {
+        let t = (i as f32 + 0.5) / GRADIENT_SEGMENTS as f32;
+        let min = rect.left_top() + egui::vec2(i as f32 * segment_width, 0.0);
+        // slightly wider than a segment to avoid gaps between them
+        let segment =
+            egui::Rect::from_min_size(min, egui::vec2(segment_width + 1.0, rect.height()));
+
+        painter.rect_filled(segment, 0.0, to_color32(palette.sample(t)));
+    }
+
+    // mark where the stops are
+    for stop in &palette.stops {
+        let x = rect.left() + stop.position.clamp(0.0, 1.0) * rect.width();
+        let y_range = rect.bottom() - rect.height() / 3.0..=rect.bottom();
+        painter.vline(x, y_range, Stroke::new(2.0, Color32::WHITE));
+    }
+}
+
+fn show_stops_input(ui: &mut Ui, palette: &mut Palette) {
+    let stop_count = palette.stops.len();
+    let mut removed = None;
+
+    for (i, stop) in palette.stops.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            let mut rgb = [stop.color.red, stop.color.green, stop.color.blue];
+            if ui.color_edit_button_rgb(&mut rgb).changed() {
+                stop.color = LinearRgba::rgb(rgb[0], rgb[1], rgb[2]);
+            }
+
+            ui.add(
+                DragValue::new(&mut stop.position)
+                    .speed(POSITION_SENSITIVITY)
+                    .range(0.0..=1.0),
+            );
+
+            if ui
+                .add_enabled(stop_count > 1, Button::new("Remove"))
+                .clicked()
+            {
+                removed = Some(i);
+            }
+        });
+    }
+
+    if let Some(i) = removed {
+        palette.stops.remove(i);
+    }
+
+    let add_button = Button::new("Add Stop");
+    if ui
+        .add_enabled(stop_count < MAX_COLOR_STOPS, add_button)
+        .clicked()
+    {
+        palette.add_stop();
+    }
+}
+
+fn to_color32(color: LinearRgba) -> Color32 {
+    Rgba::from_rgb(color.red, color.green, color.blue).into()
+}
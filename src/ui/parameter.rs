@@ -1,7 +1,9 @@
+use std::mem::discriminant;
+
 use bevy::{log::debug, prelude::Mut};
 use bevy_egui::egui::{self, ComboBox, DragValue, Grid, Ui, Widget};
 
-use crate::fractal::parameters::{ComplexParameter, Parameter};
+use crate::fractal::parameters::{ComplexParameter, ComplexPart, Parameter, PixelTransform};
 
 use super::DRAG_SENSITIVITY;
 
@@ -10,21 +12,76 @@ pub struct ComplexParameterInput<'a>(pub Mut<'a, ComplexParameter>);
 
 impl Widget for ComplexParameterInput<'_> {
     fn ui(mut self, ui: &mut Ui) -> egui::Response {
+        let initial_value = *self.0;
+
         // ui.allocate_space(egui::vec2(300.0, 0.0));
-        Grid::new("Complex parameter input")
+        let response = Grid::new("Complex parameter input")
             .min_row_height(45.0)
             .show(ui, |ui| {
                 ui.label("real:");
-                show_parameter_input(ui, self.0.reborrow().map_unchanged(|v| &mut v.real));
+                show_input(
+                    ui,
+                    self.0.reborrow().map_unchanged(|v| &mut v.real),
+                    Some(ComplexPart::Real),
+                );
                 ui.end_row();
                 ui.label("imaginary:");
-                show_parameter_input(ui, self.0.reborrow().map_unchanged(|v| &mut v.imaginary));
+                show_input(
+                    ui,
+                    self.0.reborrow().map_unchanged(|v| &mut v.imaginary),
+                    Some(ComplexPart::Imaginary),
+                );
             })
-            .response
+            .response;
+
+        if let Some(synced) = sync_circle(initial_value, *self.0) {
+            *self.0 = synced;
+        }
+
+        response
     }
 }
 
-fn show_parameter_input(ui: &mut Ui, mut value: Mut<Parameter>) {
+/// Shows the input of a parameter that isn't part of a complex number
+pub fn show_parameter_input(ui: &mut Ui, value: Mut<Parameter>) {
+    show_input(ui, value, None);
+}
+
+/// Shows the transformation of [`Parameter::TransformedPixel`] as the equations of x and y
+pub fn show_pixel_transform_input(ui: &mut Ui, mut value: Mut<PixelTransform>) {
+    let mut temp_value = *value;
+
+    Grid::new(ui.next_auto_id()).show(ui, |ui| {
+        let PixelTransform {
+            x_axis,
+            y_axis,
+            offset,
+        } = &mut temp_value;
+
+        ui.label("x' =");
+        ui.add(DragValue::new(&mut x_axis.x).speed(DRAG_SENSITIVITY));
+        ui.label("x +");
+        ui.add(DragValue::new(&mut y_axis.x).speed(DRAG_SENSITIVITY));
+        ui.label("y +");
+        ui.add(DragValue::new(&mut offset.x).speed(DRAG_SENSITIVITY));
+        ui.end_row();
+
+        ui.label("y' =");
+        ui.add(DragValue::new(&mut x_axis.y).speed(DRAG_SENSITIVITY));
+        ui.label("x +");
+        ui.add(DragValue::new(&mut y_axis.y).speed(DRAG_SENSITIVITY));
+        ui.label("y +");
+        ui.add(DragValue::new(&mut offset.y).speed(DRAG_SENSITIVITY));
+        ui.end_row();
+    });
+
+    if temp_value != *value {
+        debug!("Pixel transform modified");
+        *value = temp_value;
+    }
+}
+
+fn show_input(ui: &mut Ui, mut value: Mut<Parameter>, part: Option<ComplexPart>) {
     let mut modifiable_param = *value;
     let initial_param = modifiable_param;
 
@@ -32,26 +89,20 @@ fn show_parameter_input(ui: &mut Ui, mut value: Mut<Parameter>) {
         ComboBox::new(ui.next_auto_id(), "")
             .selected_text(modifiable_param.variant_str())
             .show_ui(ui, |ui| {
-                ui.selectable_value(
-                    &mut modifiable_param,
-                    Parameter::Value(0.0),
-                    Parameter::Value(0.0).variant_str(),
-                );
-                ui.selectable_value(
-                    &mut modifiable_param,
-                    Parameter::PixelX,
-                    Parameter::PixelX.variant_str(),
-                );
-                ui.selectable_value(
-                    &mut modifiable_param,
-                    Parameter::PixelY,
-                    Parameter::PixelY.variant_str(),
-                );
+                for kind in Parameter::ALL {
+                    // only complex numbers can move around a circle
+                    if matches!(kind, Parameter::Circle { .. }) && part.is_none() {
+                        continue;
+                    }
+
+                    let selected = discriminant(&kind) == discriminant(&modifiable_param);
+                    if ui.selectable_label(selected, kind.variant_str()).clicked() && !selected {
+                        modifiable_param = with_kind(initial_param, kind);
+                    }
+                }
             });
 
-        if let Parameter::Value(ref mut inner_value) = modifiable_param {
-            ui.add(DragValue::new(inner_value).speed(DRAG_SENSITIVITY));
-        }
+        show_settings(ui, &mut modifiable_param);
     });
 
     if initial_param != modifiable_param {
@@ -59,3 +110,137 @@ fn show_parameter_input(ui: &mut Ui, mut value: Mut<Parameter>) {
         *value = modifiable_param;
     }
 }
+
+fn show_settings(ui: &mut Ui, param: &mut Parameter) {
+    match param {
+        Parameter::Value(value) => {
+            ui.add(DragValue::new(value).speed(DRAG_SENSITIVITY));
+        }
+        Parameter::PixelX
+        | Parameter::PixelY
+        | Parameter::PixelRadius
+        | Parameter::PixelAngle
+        | Parameter::InversePixel
+        | Parameter::TransformedPixel
+        | Parameter::CursorX
+        | Parameter::CursorY => {}
+        Parameter::Time { speed, phase } => {
+            Grid::new(ui.next_auto_id()).show(ui, |ui| {
+                show_setting(ui, "start:", phase, "");
+                show_setting(ui, "speed:", speed, " /s");
+            });
+        }
+        Parameter::Sine {
+            amplitude,
+            frequency,
+            phase,
+            center,
+        } => {
+            Grid::new(ui.next_auto_id()).show(ui, |ui| {
+                show_setting(ui, "center:", center, "");
+                show_setting(ui, "amplitude:", amplitude, "");
+                show_setting(ui, "frequency:", frequency, " Hz");
+                show_setting(ui, "phase:", phase, " rad");
+            });
+        }
+        Parameter::Circle {
+            center,
+            radius,
+            frequency,
+            phase,
+        } => {
+            Grid::new(ui.next_auto_id()).show(ui, |ui| {
+                show_setting(ui, "center:", center, "");
+                show_setting(ui, "radius:", radius, "");
+                show_setting(ui, "frequency:", frequency, " Hz");
+                show_setting(ui, "phase:", phase, " rad");
+            });
+        }
+    }
+}
+
+fn show_setting(ui: &mut Ui, label: &str, value: &mut f64, suffix: &str) {
+    ui.label(label);
+    ui.add(DragValue::new(value).speed(DRAG_SENSITIVITY).suffix(suffix));
+    ui.end_row();
+}
+
+// switches to another kind of parameter, keeping the constant it was as the starting point
+fn with_kind(param: Parameter, kind: Parameter) -> Parameter {
+    let Parameter::Value(value) = param else {
+        return kind;
+    };
+
+    match kind {
+        Parameter::Value(_) => param,
+        Parameter::Time { speed, .. } => Parameter::Time {
+            speed,
+            phase: value,
+        },
+        Parameter::Sine {
+            amplitude,
+            frequency,
+            phase,
+            ..
+        } => Parameter::Sine {
+            amplitude,
+            frequency,
+            phase,
+            center: value,
+        },
+        Parameter::Circle {
+            radius,
+            frequency,
+            phase,
+            ..
+        } => Parameter::Circle {
+            center: value,
+            radius,
+            frequency,
+            phase,
+        },
+        Parameter::PixelX
+        | Parameter::PixelY
+        | Parameter::PixelRadius
+        | Parameter::PixelAngle
+        | Parameter::InversePixel
+        | Parameter::TransformedPixel
+        | Parameter::CursorX
+        | Parameter::CursorY => kind,
+    }
+}
+
+// both parts of a circle share everything but their centers,
+// so making one of them a circle or changing it changes the other one too
+fn sync_circle(initial: ComplexParameter, current: ComplexParameter) -> Option<ComplexParameter> {
+    let mut synced = current;
+    let (source, target) = if current.real != initial.real {
+        (current.real, &mut synced.imaginary)
+    } else if current.imaginary != initial.imaginary {
+        (current.imaginary, &mut synced.real)
+    } else {
+        return None;
+    };
+
+    let Parameter::Circle {
+        radius,
+        frequency,
+        phase,
+        ..
+    } = source
+    else {
+        return None;
+    };
+    let center = match *target {
+        Parameter::Value(center) | Parameter::Circle { center, .. } => center,
+        _ => 0.0,
+    };
+
+    *target = Parameter::Circle {
+        center,
+        radius,
+        frequency,
+        phase,
+    };
+    (synced != current).then_some(synced)
+}
@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use bevy::prelude::Mut;
+use bevy_egui::egui::{ComboBox, Ui};
+use rfd::FileDialog;
+
+use crate::fractal::{
+    preset::{PresetFormat, BUILTIN_PRESETS, PRESET_EXTENSIONS},
+    Fractal,
+};
+
+/// What the user asked for in the presets section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresetAction {
+    Open(PathBuf),
+    Save(PathBuf),
+}
+
+/// Shows a dropdown with the built-in presets, choosing one replaces the whole fractal
+pub fn show_builtin_preset_input(ui: &mut Ui, mut fractal: Mut<Fractal>) {
+    let chosen = ComboBox::new(ui.next_auto_id(), "")
+        .selected_text("Choose a preset")
+        .width(ui.available_width())
+        .show_ui(ui, |ui| {
+            BUILTIN_PRESETS
+                .iter()
+                .filter(|preset| ui.selectable_label(false, preset.name).clicked())
+                .last()
+        })
+        .inner
+        .flatten();
+
+    if let Some(preset) = chosen {
+        *fractal = preset.fractal();
+    }
+}
+
+/// Shows the buttons that ask which preset file to open or where to save one
+pub fn show_preset_buttons(ui: &mut Ui) -> Option<PresetAction> {
+    let (open_clicked, save_clicked) = ui
+        .horizontal(|ui| {
+            let open_clicked = ui.button("Open Preset").clicked();
+            let save_clicked = ui.button("Save Preset").clicked();
+            (open_clicked, save_clicked)
+        })
+        .inner;
+
+    if open_clicked {
+        let path = preset_dialog().pick_file()?;
+        return Some(PresetAction::Open(path));
+    }
+    if save_clicked {
+        let mut path = preset_dialog().set_file_name("fractal.ron").save_file()?;
+        if PresetFormat::from_path(&path).is_none() {
+            path.set_extension("ron");
+        }
+        return Some(PresetAction::Save(path));
+    }
+
+    None
+}
+
+fn preset_dialog() -> FileDialog {
+    FileDialog::new()
+        .add_filter("Fractal preset", &PRESET_EXTENSIONS)
+        .add_filter("RON", &["ron"])
+        .add_filter("TOML", &["toml"])
+}
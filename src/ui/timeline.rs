@@ -0,0 +1,158 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_egui::{
+    egui::{self, Button, DragValue, Frame, Margin, ProgressBar, RichText, ScrollArea, Slider},
+    EguiContexts,
+};
+
+use crate::{
+    export::ExportSettings,
+    keyframes::{
+        AddKeyframe, AnimationRender, CancelAnimationRender, RemoveKeyframe, RenderAnimation,
+        Timeline, MAX_FRAME_RATE,
+    },
+};
+
+const DURATION_SENSITIVITY: f64 = 0.05;
+
+/// Events sent by the timeline panel
+#[derive(SystemParam)]
+pub struct TimelineEvents<'w> {
+    add: EventWriter<'w, AddKeyframe>,
+    remove: EventWriter<'w, RemoveKeyframe>,
+    render: EventWriter<'w, RenderAnimation>,
+    cancel: EventWriter<'w, CancelAnimationRender>,
+}
+
+pub fn timeline_ui_system(
+    mut contexts: EguiContexts,
+    mut timeline: ResMut<Timeline>,
+    render: Option<Res<AnimationRender>>,
+    export_settings: Res<ExportSettings>,
+    mut events: TimelineEvents,
+) {
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    egui::TopBottomPanel::bottom("TimelinePanel")
+        .resizable(false)
+        .frame(Frame::side_top_panel(&ctx.style()).inner_margin(Margin::symmetric(10.0, 10.0)))
+        .show(ctx, |ui| {
+            ui.label(RichText::new("Timeline").strong().size(18.0));
+            ui.separator();
+
+            let mut temp_time = timeline.time;
+            let mut temp_frame_rate = timeline.frame_rate;
+            let duration = timeline.duration();
+
+            ui.horizontal(|ui| {
+                if ui.button("Add Keyframe").clicked() {
+                    events.add.send(AddKeyframe);
+                }
+
+                let play_text = if timeline.playing { "Pause" } else { "Play" };
+                if ui
+                    .add_enabled(timeline.keyframes.len() > 1, Button::new(play_text))
+                    .clicked()
+                {
+                    // playing from the end starts over
+                    if !timeline.playing && timeline.time >= duration {
+                        temp_time = 0.0;
+                    }
+                    timeline.playing = !timeline.playing;
+                }
+
+                ui.add(
+                    Slider::new(&mut temp_time, 0.0..=duration)
+                        .suffix(" s")
+                        .fixed_decimals(2),
+                );
+            });
+            ui.add_space(5.0);
+
+            ScrollArea::horizontal().show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if timeline.keyframes.is_empty() {
+                        ui.label("Add keyframes to animate between them");
+                    }
+
+                    let last = timeline.keyframes.len().saturating_sub(1);
+                    for index in 0..timeline.keyframes.len() {
+                        let keyframe_time = timeline.keyframe_time(index);
+
+                        ui.group(|ui| {
+                            ui.vertical(|ui| {
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("Keyframe {}", index + 1));
+                                    if ui.add(Button::new("Remove").small()).clicked() {
+                                        events.remove.send(RemoveKeyframe { index });
+                                    }
+                                });
+                                ui.horizontal(|ui| {
+                                    if ui.button("Go to").clicked() {
+                                        temp_time = keyframe_time;
+                                    }
+                                    if index < last {
+                                        let mut temp_duration = timeline.keyframes[index].duration;
+                                        ui.add(
+                                            DragValue::new(&mut temp_duration)
+                                                .speed(DURATION_SENSITIVITY)
+                                                .range(0.0..=f64::INFINITY)
+                                                .suffix(" s"),
+                                        )
+                                        .on_hover_text("Time it takes to get to the next keyframe");
+                                        if temp_duration != timeline.keyframes[index].duration {
+                                            timeline.keyframes[index].duration = temp_duration;
+                                        }
+                                    }
+                                });
+                            });
+                        });
+                    }
+                });
+            });
+            ui.add_space(5.0);
+
+            ui.horizontal(|ui| {
+                ui.label("Frame Rate:");
+                ui.add(
+                    DragValue::new(&mut temp_frame_rate)
+                        .range(1..=MAX_FRAME_RATE)
+                        .suffix(" fps"),
+                );
+                ui.label(format!("{} frames", timeline.frame_count()));
+
+                if let Some(render) = render {
+                    ui.add(
+                        ProgressBar::new(render.progress())
+                            .text(format!("Rendering {:.0}%", render.progress() * 100.0))
+                            .desired_width(200.0),
+                    );
+                    if ui.button("Cancel").clicked() {
+                        events.cancel.send(CancelAnimationRender);
+                    }
+                    return;
+                }
+
+                let render_clicked = ui
+                    .add_enabled(!timeline.keyframes.is_empty(), Button::new("Render Frames"))
+                    .on_hover_text("Renders every frame to a PNG file, with the export settings")
+                    .clicked();
+                if render_clicked {
+                    if let Some(directory) = rfd::FileDialog::new().pick_folder() {
+                        events.render.send(RenderAnimation {
+                            directory,
+                            settings: *export_settings,
+                        });
+                    }
+                }
+            });
+
+            if temp_time != timeline.time {
+                timeline.time = temp_time;
+            }
+            if temp_frame_rate != timeline.frame_rate {
+                timeline.frame_rate = temp_frame_rate;
+            }
+        });
+}
@@ -1,10 +1,14 @@
+use std::{env, path::PathBuf};
+
 use bevy::{
+    log::error,
     render::{
         settings::{RenderCreation, WgpuSettings},
         RenderPlugin,
     },
     utils::default,
 };
+use rfd::{MessageButtons, MessageLevel};
 use wgpu::Backends;
 
 pub fn get_default_render_plugin() -> RenderPlugin {
@@ -19,6 +23,47 @@ pub fn get_default_render_plugin() -> RenderPlugin {
     }
 }
 
+/// Logs the error and shows it in a message box, blocks until the box is closed
+pub fn show_error_dialog(title: &str, description: String) {
+    error!("{title}: {description}");
+
+    rfd::MessageDialog::new()
+        .set_level(MessageLevel::Error)
+        .set_title(title)
+        .set_description(description)
+        .set_buttons(MessageButtons::Ok)
+        .show();
+}
+
+/// Directory where Fractality keeps its data between sessions, `None` if there is no home directory
+pub fn data_dir() -> Option<PathBuf> {
+    user_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// Directory where Fractality keeps its settings, `None` if there is no home directory
+pub fn config_dir() -> Option<PathBuf> {
+    user_dir("XDG_CONFIG_HOME", ".config")
+}
+
+// windows and macos don't separate data and settings, other systems follow the XDG spec
+fn user_dir(xdg_var: &str, home_fallback: &str) -> Option<PathBuf> {
+    let var = |name| {
+        env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+
+    let base = if cfg!(target_os = "windows") {
+        var("APPDATA")?
+    } else if cfg!(target_os = "macos") {
+        var("HOME")?.join("Library/Application Support")
+    } else {
+        var(xdg_var).or_else(|| Some(var("HOME")?.join(home_fallback)))?
+    };
+
+    Some(base.join("fractality"))
+}
+
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum EitherIterator<A, B> {
     A(A),